@@ -0,0 +1,369 @@
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+use chrono::Local;
+use storage::LocationConfig;
+use storage::data::{DataNode, DirEntry};
+use storage::files;
+use storage::fs::{Fs, RealFs};
+use storage::git_history;
+use storage::links::clean_path;
+use storage::search::SearchIndex;
+use storage::snapshots::Snapshots;
+use storage::trash;
+
+/// Relative paths are taken from the notes folder, paths out of it or in its trash, history
+/// and git folders are refused
+pub fn resolve(location: &LocationConfig, path: &Path) -> io::Result<PathBuf> {
+    let resolved = clean_path(&location.base_path.join(path));
+    if !resolved.starts_with(&location.base_path) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is outside of the notes folder", path.display()),
+        ));
+    }
+    if location.is_reserved(&resolved) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is in a reserved vault folder", path.display()),
+        ));
+    }
+    Ok(resolved)
+}
+
+/// Commit changes made by a command when the vault has git history
+pub fn commit(location: &LocationConfig) {
+    if !location.git_history {
+        return;
+    }
+    if let Err(err) = git_history::commit_all(&location.base_path, &location.scratch_pad_path) {
+        log::error!("Failed to commit {}: {err}", location.base_path.display());
+    }
+}
+
+/// Path as shown to the user, relative to the notes folder when it is inside
+fn display_path(location: &LocationConfig, path: &Path) -> String {
+    path.strip_prefix(&location.base_path)
+        .unwrap_or(path)
+        .display()
+        .to_string()
+}
+
+/// Words joined with spaces, or all of standard input when there are none
+pub fn text_or_stdin(words: Vec<String>) -> io::Result<String> {
+    if !words.is_empty() {
+        return Ok(words.join(" "));
+    }
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text)?;
+    Ok(text)
+}
+
+pub fn new(location: &LocationConfig, path: Option<&Path>, out: &mut impl Write) -> io::Result<()> {
+    let path = match path {
+        Some(path) => {
+            let path = resolve(location, path)?;
+            if let Some(parent) = path.parent() {
+                RealFs.create_dir_all(parent)?;
+            }
+            files::create_note_at(&RealFs, &path)?;
+            path
+        }
+        None => files::create_note(&RealFs, &location.base_path)?,
+    };
+    writeln!(out, "{}", display_path(location, &path))
+}
+
+pub fn ls(
+    location: &LocationConfig,
+    path: Option<&Path>,
+    all: bool,
+    recursive: bool,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut dirs = vec![path.map_or_else(
+        || Ok(location.base_path.to_path_buf()),
+        |path| resolve(location, path),
+    )?];
+    while let Some(dir_path) = dirs.pop() {
+        let dir = files::load_dir(&RealFs, &dir_path)?.data;
+        let mut entries = dir
+            .entries
+            .into_iter()
+            .filter(|(name, _)| all || !name.starts_with('.'))
+            .collect::<Vec<(String, DirEntry)>>();
+        entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let mut sub_dirs = Vec::new();
+        for (_, entry) in entries {
+            match entry {
+                DirEntry::Dir(path) => {
+                    writeln!(out, "{}/", display_path(location, &path))?;
+                    sub_dirs.push(path);
+                }
+                DirEntry::File(path) => writeln!(out, "{}", display_path(location, &path))?,
+            }
+        }
+        if recursive {
+            dirs.extend(sub_dirs.into_iter().rev());
+        }
+    }
+    Ok(())
+}
+
+pub fn cat(path: &Path, raw: bool, out: &mut impl Write) -> io::Result<()> {
+    let note = files::load_note(&RealFs, path)?.data;
+    let text = if raw { note.to_source() } else { note.text };
+    write!(out, "{text}")?;
+    if !text.is_empty() && !text.ends_with('\n') {
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Editor command may carry arguments, e.g. `code --wait`. With snapshots the note is kept as
+/// it is before editing
+pub fn edit(path: &Path, snapshots: Option<&Snapshots>) -> io::Result<()> {
+    if let Some(snapshots) = snapshots
+        && let Err(err) = snapshots.keep_current(&RealFs, path)
+    {
+        log::warn!("Failed to keep snapshot of {}: {err}", path.display());
+    }
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_owned());
+    let mut words = editor.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "editor is not set"))?;
+    let status = process::Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::other(format!("{program} exited with {status}")));
+    }
+    Ok(())
+}
+
+/// Add text on a new line, creating the note when it does not exist
+pub fn append(path: &Path, text: &str, snapshots: Option<&Snapshots>) -> io::Result<()> {
+    let mut note = files::read_disk_note(&RealFs, path)?.unwrap_or_default();
+    if !note.text.is_empty() && !note.text.ends_with('\n') {
+        note.text.push('\n');
+    }
+    note.text.push_str(text);
+    if !note.text.ends_with('\n') {
+        note.text.push('\n');
+    }
+    note.touch();
+    files::save_note(&RealFs, path, &DataNode::new(note), snapshots)?;
+    Ok(())
+}
+
+/// Hits as `path:line: snippet`, best first
+pub fn search(
+    location: &LocationConfig,
+    query: &str,
+    limit: usize,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let mut index = SearchIndex::default();
+    for (path, note) in files::read_notes(&RealFs, &location.base_path, &location.scratch_pad_path)?
+    {
+        index.update(&path, &note.text);
+    }
+    for hit in index.search(query, limit) {
+        writeln!(
+            out,
+            "{}:{}: {}",
+            display_path(location, &hit.path),
+            hit.line_number + 1,
+            hit.snippet.trim()
+        )?;
+    }
+    Ok(())
+}
+
+pub fn trash(location: &LocationConfig, path: &Path, out: &mut impl Write) -> io::Result<()> {
+    let path = resolve(location, path)?;
+    if path == *location.scratch_pad_path || path == *location.base_path {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("refuse to trash {}", path.display()),
+        ));
+    }
+    let name = trash::move_to_trash(&RealFs, &path, &location.trash_path)?;
+    writeln!(out, "{name}")
+}
+
+/// Without a name trash is listed as name, deletion time and origin, most recent first
+pub fn restore(
+    location: &LocationConfig,
+    name: Option<&str>,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let Some(name) = name else {
+        for entry in trash::load_trash(&RealFs, &location.trash_path)?.entries {
+            writeln!(
+                out,
+                "{}\t{}\t{}",
+                entry.name,
+                entry
+                    .deleted
+                    .deletion_time
+                    .with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M"),
+                display_path(location, &entry.deleted.origin_path)
+            )?;
+        }
+        return Ok(());
+    };
+    let path = trash::restore_from_trash(&RealFs, name, &location.trash_path)?;
+    writeln!(out, "{}", display_path(location, &path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use notes::Note;
+
+    use super::*;
+
+    fn location() -> (tempfile::TempDir, LocationConfig) {
+        let dir = tempfile::tempdir().unwrap();
+        let location = LocationConfig::new(dir.path(), None);
+        location.prepare(&RealFs).unwrap();
+        (dir, location)
+    }
+
+    fn output(run: impl FnOnce(&mut Vec<u8>) -> io::Result<()>) -> String {
+        let mut out = Vec::new();
+        run(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn new_notes_are_listed() {
+        let (_dir, location) = location();
+        assert_eq!(output(|out| new(&location, None, out)), "a note\n");
+        assert_eq!(
+            output(|out| new(&location, Some(Path::new("journal/today")), out)),
+            "journal/today\n"
+        );
+
+        assert_eq!(
+            output(|out| ls(&location, None, false, true, out)),
+            "a note\njournal/\njournal/today\n"
+        );
+        assert!(output(|out| ls(&location, None, true, false, out)).contains(".scratchpad\n"));
+    }
+
+    #[test]
+    fn append_keeps_front_matter() {
+        let (_dir, location) = location();
+        let path = resolve(&location, Path::new("log")).unwrap();
+        fs::write(&path, "---\nicon: star\n---\nfirst").unwrap();
+
+        append(&path, "second", None).unwrap();
+
+        assert_eq!(output(|out| cat(&path, false, out)), "first\nsecond\n");
+        let note = Note::from_source(fs::read_to_string(&path).unwrap());
+        assert_eq!(note.icon(), phosphor_icons::STAR);
+        assert!(note.metadata.updated.is_some());
+    }
+
+    #[test]
+    fn trash_and_restore() {
+        let (_dir, location) = location();
+        append(
+            &resolve(&location, Path::new("note")).unwrap(),
+            "text",
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            output(|out| trash(&location, Path::new("note"), out)),
+            "note\n"
+        );
+        assert!(output(|out| restore(&location, None, out)).starts_with("note\t"));
+        assert_eq!(
+            output(|out| restore(&location, Some("note"), out)),
+            "note\n"
+        );
+        assert!(trash(&location, &location.scratch_pad_path, &mut io::sink()).is_err());
+    }
+
+    #[test]
+    fn search_finds_scratch_pad() {
+        let (_dir, location) = location();
+        append(&location.scratch_pad_path, "remember the milk", None).unwrap();
+        append(
+            &resolve(&location, Path::new("note")).unwrap(),
+            "no match",
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            output(|out| search(&location, "milk", 10, out)),
+            ".scratchpad:1: remember the milk\n"
+        );
+    }
+
+    #[test]
+    fn paths_out_of_notes_folder_are_refused() {
+        let (dir, location) = location();
+
+        assert_eq!(
+            resolve(&location, Path::new("journal/../today")).unwrap(),
+            dir.path().join("today")
+        );
+        for path in ["../outside", "/etc/passwd", "journal/../../outside"] {
+            let err = resolve(&location, Path::new(path)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{path}");
+        }
+        for path in [
+            ".trash",
+            ".trash/evil",
+            ".history/note",
+            ".git",
+            "a/../.git/config",
+        ] {
+            let err = resolve(&location, Path::new(path)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput, "{path}");
+            assert!(err.to_string().contains("reserved vault folder"), "{path}");
+        }
+        assert!(new(&location, Some(Path::new(".trash/evil")), &mut io::sink()).is_err());
+        assert!(trash(&location, Path::new(".trash"), &mut io::sink()).is_err());
+        assert!(resolve(&location, Path::new(".gitignore")).is_ok());
+    }
+
+    #[test]
+    fn append_keeps_snapshot_and_commits() {
+        let dir = tempfile::tempdir().unwrap();
+        let location = LocationConfig::new(dir.path(), None)
+            .with_snapshots(true)
+            .with_git_history(true);
+        location.prepare(&RealFs).unwrap();
+        let snapshots = Snapshots {
+            base_path: dir.path().to_path_buf(),
+            history_path: location.history_path.to_path_buf(),
+            retention: Default::default(),
+        };
+        let path = resolve(&location, Path::new("note")).unwrap();
+        append(&path, "first", Some(&snapshots)).unwrap();
+        commit(&location);
+
+        append(&path, "second", Some(&snapshots)).unwrap();
+        commit(&location);
+
+        let kept = snapshots.list(&RealFs, &path).unwrap();
+        assert_eq!(kept.len(), 1);
+        assert!(kept[0].source.ends_with("first\n"));
+        let revisions = git_history::note_revisions(dir.path(), &path, 10).unwrap();
+        assert_eq!(revisions.len(), 2);
+    }
+}
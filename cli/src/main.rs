@@ -0,0 +1,166 @@
+//! `questionable` command line, works on the same vaults as the desktop application
+
+mod commands;
+
+use std::io;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use storage::LocationConfig;
+use storage::app::ApplicationConfig;
+use storage::fs::RealFs;
+use storage::settings::{self, NotesSettings};
+
+#[derive(Debug, Parser)]
+#[command(name = "questionable", version, about = "Notes from the command line")]
+struct Cli {
+    /// Notes folder, the open vault of the desktop application by default
+    #[arg(long, global = true, env = "QUESTIONABLE_DIR")]
+    dir: Option<PathBuf>,
+    /// Vault of the desktop application by name
+    #[arg(long, global = true, conflicts_with = "dir")]
+    vault: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Note paths are relative to the notes folder
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Create an empty note and print its path, a uniquely named one when no path is given
+    New { path: Option<PathBuf> },
+    /// List notes and folders, folders end with `/`
+    Ls {
+        path: Option<PathBuf>,
+        /// Include hidden entries
+        #[arg(short, long)]
+        all: bool,
+        /// List subfolders too
+        #[arg(short, long)]
+        recursive: bool,
+    },
+    /// Print note text
+    Cat {
+        path: PathBuf,
+        /// Print front matter as well
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Open note in $VISUAL or $EDITOR
+    Edit { path: PathBuf },
+    /// Append a line to a note, text is read from standard input when not given
+    Append { path: PathBuf, text: Vec<String> },
+    /// Search note text
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
+        #[arg(short = 'n', long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Move note or folder to trash and print its name in trash
+    Trash { path: PathBuf },
+    /// Restore item from trash by name, trash is listed when no name is given
+    Restore { name: Option<String> },
+    /// Print scratch pad, or append a line to it
+    Scratch {
+        text: Vec<String>,
+        /// Open scratch pad in editor instead
+        #[arg(short, long, conflicts_with = "text")]
+        edit: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    env_logger::init();
+    let cli = Cli::parse();
+    match vault_config(cli.dir, cli.vault.as_deref()).and_then(|config| run(&config, cli.command)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("questionable: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Folder given with `--dir` is used as is, vaults come from the desktop application settings
+fn vault_config(dir: Option<PathBuf>, name: Option<&str>) -> io::Result<ApplicationConfig> {
+    if let Some(dir) = dir {
+        let location = LocationConfig::new(&std::path::absolute(dir)?, None);
+        return Ok(ApplicationConfig::new(location));
+    }
+    let settings = settings::load_config::<NotesSettings>();
+    let vault = match name {
+        Some(name) => settings.vault_named(name).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("no vault named {name}"))
+        })?,
+        None => settings.vault(),
+    };
+    let config = settings.vault_config(vault);
+    // Default vault is left without a path when there is no home directory
+    if config.location.base_path.as_os_str().is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no home directory, pass --dir or --vault",
+        ));
+    }
+    Ok(config)
+}
+
+fn run(config: &ApplicationConfig, command: Command) -> io::Result<()> {
+    let location = &config.location;
+    location.prepare(&RealFs)?;
+    let snapshots = config.snapshots();
+    let out = &mut io::stdout().lock();
+    let changes = matches!(
+        command,
+        Command::New { .. }
+            | Command::Edit { .. }
+            | Command::Append { .. }
+            | Command::Trash { .. }
+            | Command::Restore { name: Some(_) }
+            | Command::Scratch { .. }
+    );
+    match command {
+        Command::New { path } => commands::new(location, path.as_deref(), out),
+        Command::Ls {
+            path,
+            all,
+            recursive,
+        } => commands::ls(location, path.as_deref(), all, recursive, out),
+        Command::Cat { path, raw } => commands::cat(&commands::resolve(location, &path)?, raw, out),
+        Command::Edit { path } => {
+            commands::edit(&commands::resolve(location, &path)?, snapshots.as_ref())
+        }
+        Command::Append { path, text } => {
+            let text = commands::text_or_stdin(text)?;
+            commands::append(
+                &commands::resolve(location, &path)?,
+                &text,
+                snapshots.as_ref(),
+            )
+        }
+        Command::Search { query, limit } => {
+            commands::search(location, &query.join(" "), limit, out)
+        }
+        Command::Trash { path } => commands::trash(location, &path, out),
+        Command::Restore { name } => commands::restore(location, name.as_deref(), out),
+        Command::Scratch { text, edit } => {
+            if edit {
+                commands::edit(&location.scratch_pad_path, snapshots.as_ref())
+            } else if text.is_empty() {
+                commands::cat(&location.scratch_pad_path, false, out)
+            } else {
+                commands::append(
+                    &location.scratch_pad_path,
+                    &text.join(" "),
+                    snapshots.as_ref(),
+                )
+            }
+        }
+    }?;
+    if changes {
+        commands::commit(location);
+    }
+    Ok(())
+}
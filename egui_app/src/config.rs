@@ -0,0 +1,211 @@
+//! User settings kept in `config.toml` of the platform config directory,
+//! e.g. `~/.config/questionable/config.toml` on Linux. Vaults and saving are shared with the
+//! command line, see [`storage::settings`]
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use egui::{Context, FontFamily, TextStyle, ThemePreference};
+use serde::{Deserialize, Serialize};
+
+use crate::egui_app::ExplorerLayout;
+use storage::settings::{self, NotesSettings, write_toml};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    #[serde(flatten)]
+    pub notes: NotesSettings,
+    pub explorer_layout: ExplorerLayout,
+    pub locale: String,
+    pub show_hidden_files: bool,
+    /// Note file follows its title when the title is edited
+    pub rename_file_with_title: bool,
+    pub appearance: Appearance,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Appearance {
+    pub theme: Theme,
+    pub body_font_size: f32,
+    pub heading_font_size: f32,
+    pub monospace_font_size: f32,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            notes: Default::default(),
+            explorer_layout: Default::default(),
+            locale: rust_i18n::locale().to_string(),
+            show_hidden_files: false,
+            rename_file_with_title: false,
+            appearance: Default::default(),
+        }
+    }
+}
+
+/// Sizes of egui default style
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            theme: Default::default(),
+            body_font_size: 12.5,
+            heading_font_size: 18.,
+            monospace_font_size: 12.,
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> Option<PathBuf> {
+        settings::config_path()
+    }
+
+    /// Defaults when there is no config file yet or it cannot be read
+    pub fn load() -> Self {
+        settings::load_config()
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        self.write(&path)
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        write_toml(path, self)
+    }
+
+    /// Locale is global, set it only when it is known to have translations
+    pub fn apply_locale(&self) {
+        if rust_i18n::available_locales!().contains(&self.locale.as_str()) {
+            rust_i18n::set_locale(&self.locale);
+        } else {
+            log::warn!("Unknown locale {}", self.locale);
+        }
+    }
+}
+
+impl Appearance {
+    pub fn apply(&self, ctx: &Context) {
+        ctx.set_theme(match self.theme {
+            Theme::System => ThemePreference::System,
+            Theme::Light => ThemePreference::Light,
+            Theme::Dark => ThemePreference::Dark,
+        });
+        ctx.all_styles_mut(|style| {
+            for (text_style, font_id) in style.text_styles.iter_mut() {
+                font_id.size = match text_style {
+                    TextStyle::Body | TextStyle::Button => self.body_font_size,
+                    TextStyle::Heading => self.heading_font_size,
+                    TextStyle::Monospace => self.monospace_font_size,
+                    TextStyle::Small => self.body_font_size * 0.72,
+                    TextStyle::Name(_) if font_id.family == FontFamily::Monospace => {
+                        self.monospace_font_size
+                    }
+                    TextStyle::Name(_) => self.body_font_size,
+                };
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use storage::settings::{CONFIG_FILE_NAME, LocationSettings, VaultSettings, read_toml};
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested").join(CONFIG_FILE_NAME);
+        let settings = Settings {
+            notes: NotesSettings {
+                autosave: false,
+                snapshot_max_count: 10,
+                current_vault: "work".to_owned(),
+                vaults: vec![
+                    VaultSettings::default(),
+                    VaultSettings {
+                        name: "work".to_owned(),
+                        location: LocationSettings {
+                            base_path: PathBuf::from("/work"),
+                            scratch_pad_path: Some(PathBuf::from("/work/scratch.md")),
+                            git_history: true,
+                            snapshots: true,
+                        },
+                    },
+                ],
+                ..Default::default()
+            },
+            explorer_layout: ExplorerLayout::Windowed,
+            appearance: Appearance {
+                theme: Theme::Dark,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        settings.write(&path).unwrap();
+        let read = read_toml::<Settings>(&path).unwrap();
+        assert_eq!(read, settings);
+        assert_eq!(read.notes.vault().location.base_path, Path::new("/work"));
+    }
+
+    #[test]
+    fn missing_keys_take_defaults() {
+        let settings = toml::from_str::<Settings>(
+            "autosave_delay_ms = 1000\n[appearance]\ntheme = \"light\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(settings.notes.save_delay(), Duration::from_secs(1));
+        assert_eq!(settings.appearance.theme, Theme::Light);
+        assert_eq!(settings.appearance.body_font_size, 12.5);
+        assert!(settings.notes.autosave);
+        assert_eq!(settings.notes.vaults, [VaultSettings::default()]);
+    }
+
+    #[test]
+    fn unknown_current_vault_falls_back_to_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(
+            &path,
+            "current_vault = \"gone\"\n[[vaults]]\nname = \"home\"\nbase_path = \"/home/notes\"\n",
+        )
+        .unwrap();
+
+        let settings = read_toml::<Settings>(&path).unwrap();
+        assert_eq!(settings.notes.vault().name, "home");
+
+        fs::write(&path, "vaults = []\n").unwrap();
+        let settings = read_toml::<Settings>(&path).unwrap();
+        assert_eq!(settings.notes.vault(), &VaultSettings::default());
+    }
+
+    #[test]
+    fn malformed_file_is_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(CONFIG_FILE_NAME);
+        fs::write(&path, "autosave = \"maybe\"").unwrap();
+
+        let err = read_toml::<Settings>(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,46 +1,146 @@
-use crate::app::ApplicationConfig;
-use crate::app::MemoryCellState;
-use crate::app::NonBlockingApplication;
-use crate::data::DataNode;
-use crate::data::DirEntry;
-use crate::data::Directory;
+use crate::config::{Settings, Theme};
+use crate::graph::{self, GraphView};
+use crate::preview::{MarkdownCache, MarkdownView, WikiLinkTargets};
+use crate::session::{NoteState, Session, VaultState, WindowGeometry};
 use crate::util::chrono::to_local_date_time;
+use crate::util::egui::item_spacing;
+use crate::util::{byte_offset, file_name_for_title, is_valid_file_name};
 use egui::CollapsingHeader;
 use egui::Popup;
+use egui::PopupAnchor;
+use egui::PopupCloseBehavior;
+use egui::collapsing_header::CollapsingState;
+use egui::text::{CCursor, CCursorRange, LayoutJob, TextFormat};
+use egui::text_edit::TextEditState;
 use notes::DEFAULT_ICON;
-use notes::Note;
 use notes::SCRATCH_PAD_ICON;
 use notes::SCRATCH_PAD_NAME;
-use phosphor_icons;
+use notes::links;
+use notes::tags;
 use rust_i18n::t;
 
-use std::collections::VecDeque;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::io;
 use std::path::Path;
 use std::path::PathBuf;
-use std::sync::{Arc, LazyLock};
+use std::sync::Arc;
+use std::time::Instant;
 
 use chrono::DateTime;
 use chrono::Utc;
 
+use egui::scroll_area::ScrollAreaOutput;
 use egui::{self, Label, RichText, ScrollArea, TextEdit, TextStyle, Ui, panel::Side};
 use egui::{
-    Align, Button, Context, FontData, FontDefinitions, FontFamily, Frame, Layout, Margin,
-    TopBottomPanel, Widget, Window,
+    Align, Align2, Button, Context, CursorIcon, FontData, FontDefinitions, FontFamily, Frame, Id,
+    Key, KeyboardShortcut, Layout, Margin, Modifiers, Sense, TextBuffer, TopBottomPanel,
+    ViewportCommand, Widget, Window,
 };
+use egui::{ComboBox, DragValue, Grid, Slider};
+use serde::{Deserialize, Serialize};
+use storage::app::{
+    ApplicationConfig, ConflictResolution, MemoryCell, MemoryCellState, NonBlockingApplication,
+};
+use storage::data::{DataNode, DirEntry, Directory, FileMetadata, TrashEntry};
+use storage::fs::Wake;
+use storage::history::{self, DiffLine};
+use storage::location::LocationConfig;
+use storage::search::SearchHit;
+use storage::settings::{LocationSettings, VaultSettings};
+use storage::tabs::Tab;
+use storage::tags::TagTree;
 
 #[derive(Debug)]
 pub enum Command {
+    /// Open note for preview, in place of the preview tab
     ReadAndSelectNote(PathBuf),
+    PinTab(PathBuf),
+    CloseTab(PathBuf),
+    MoveTab {
+        from: usize,
+        to: usize,
+    },
     ReadDir(PathBuf),
-    CreateNote(PathBuf),
+    ReloadNote(PathBuf),
+    ReloadDir(PathBuf),
+    DismissErrors,
     DeleteNote(PathBuf),
     DeleteDir(PathBuf),
     CreateNoteThenSelect(PathBuf),
     CreateSubDir(PathBuf),
+    StartRename(PathBuf),
+    /// Rename, after confirming the links it changes when there are any
+    Rename {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    /// Rename and rewrite links as shown in the rename window
+    ApplyRenamePlan(PathBuf),
+    /// Rename, leaving links to it broken
+    RenameWithoutLinks {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    CancelRename(PathBuf),
+    UndoRelink,
+    DismissRelink,
     MarkChanged(PathBuf),
     SaveNote(PathBuf),
+    /// Save without waiting for edits to settle
+    SaveNoteNow(PathBuf),
+    SwitchVault(String),
+    SetVaults(Vec<VaultDraft>),
+    ToggleDir(PathBuf),
+    SetIcon {
+        path: PathBuf,
+        icon: String,
+    },
+    SetTitle {
+        path: PathBuf,
+        title: String,
+    },
+    /// Rename note file after its title, when the name differs
+    RenameToTitle(PathBuf),
+    /// Rename tag and tags nested in it in every note
+    RenameTag {
+        from: String,
+        to: String,
+    },
+    ReadTrash,
+    RestoreFromTrash(String),
+    DeleteFromTrash(String),
+    EmptyTrash,
+    DismissRecentlyTrashed,
+    BuildIndex,
+    RebuildIndex,
+    /// Lay out link graph again when links changed
+    LayoutGraph,
+    /// Open note and put cursor at byte offset in its text
+    JumpToSearchHit {
+        path: PathBuf,
+        offset: usize,
+    },
+    ResolveConflict {
+        path: PathBuf,
+        resolution: ConflictResolution,
+    },
+    OpenMergeView(PathBuf),
+    ReadHistory(PathBuf),
+    ReloadHistory(PathBuf),
+    ReadSnapshots(PathBuf),
+    ReloadSnapshots(PathBuf),
+    RestoreRevision {
+        path: PathBuf,
+        source: String,
+    },
 }
 
+const SEARCH_RESULTS_LIMIT: usize = 100;
+const LINK_SUGGESTIONS_LIMIT: usize = 8;
+const SAVE_SHORTCUT: KeyboardShortcut = KeyboardShortcut::new(Modifiers::COMMAND, Key::S);
+const SEARCH_SHORTCUT: KeyboardShortcut =
+    KeyboardShortcut::new(Modifiers::COMMAND.plus(Modifiers::SHIFT), Key::F);
+
 pub struct NotesApp {
     app: NonBlockingApplication,
     command_queue: VecDeque<Command>,
@@ -48,45 +148,559 @@ pub struct NotesApp {
 }
 
 pub struct UiState {
-    pub explorer_layout: ExplorerLayout,
+    pub settings: Settings,
+    /// Settings the application and egui context were last set up with
+    pub applied_settings: Option<Settings>,
+    /// Settings as last written to config file
+    pub saved_settings: Option<Settings>,
+    pub settings_window: bool,
+    pub vault_drafts: Vec<VaultDraft>,
+    /// Remembered state of vaults other than the open one, by base path
+    pub vault_sessions: HashMap<PathBuf, VaultSession>,
+    pub expanded_dirs: HashSet<PathBuf>,
+    /// Editor positions of notes shown in this or previous sessions
+    pub note_views: HashMap<PathBuf, NoteView>,
+    /// Notes with positions from previous session not yet applied to editor
+    pub unrestored_views: HashSet<PathBuf>,
+    pub window: Option<WindowGeometry>,
     pub explorer: bool,
+    pub explorer_sort: ExplorerSort,
     pub egui_settings: bool,
     pub trash: bool,
+    pub selected_trash_entry: Option<String>,
+    pub renaming: Option<RenameState>,
+    pub icon_search: String,
+    pub search: bool,
+    pub search_query: String,
+    pub search_results: SearchResults,
+    /// Cursor position to set once the note is shown in editor
+    pub pending_jump: Option<(PathBuf, usize)>,
+    pub view_modes: HashMap<PathBuf, ViewMode>,
+    pub markdown_cache: MarkdownCache,
+    pub scroll_sync: ScrollSync,
+    pub merge_view: Option<MergeView>,
+    pub history: bool,
+    pub selected_revision: Option<String>,
+    pub history_diff: Option<HistoryDiff>,
+    pub versions: bool,
+    pub selected_snapshot: Option<String>,
+    /// Note whose title was edited since the title field got focus
+    pub edited_title: Option<PathBuf>,
+    pub tag_filter: TagFilter,
+    pub link_completion: LinkCompletion,
+    pub backlinks: bool,
+    pub backlink_results: BacklinkResults,
+    /// Rename waiting for the links it changes to be found and confirmed
+    pub pending_rename: Option<(PathBuf, PathBuf)>,
+    pub graph: bool,
+    pub graph_view: GraphView,
+    /// Window close is held back until unsaved notes are saved or discarded
+    pub close_prompt: bool,
+    /// Close goes through, unsaved notes were saved or are discarded
+    pub close_confirmed: bool,
+    pub discard_unsaved: bool,
+}
+
+/// Suggestions shown while a wiki link target is typed after `[[`
+#[derive(Debug, Default)]
+pub struct LinkCompletion {
+    query: String,
+    selected: usize,
+    /// Note and link start the suggestions are shown for
+    shown_for: Option<(PathBuf, usize)>,
+    /// Link suggestions were closed for with Escape
+    dismissed: Option<(PathBuf, usize)>,
+}
+
+pub struct LinkSuggestion {
+    /// Inserted into the link
+    target: String,
+    /// Path from the vault root
+    location: String,
+}
+
+/// Backlinks cached until note or link index changes
+#[derive(Default)]
+pub struct BacklinkResults {
+    path: PathBuf,
+    generation: u64,
+    hits: Vec<SearchHit>,
+}
+
+/// Tags picked in explorer tag tree, notes with all of them are listed
+#[derive(Debug, Default)]
+pub struct TagFilter {
+    pub selected: Vec<String>,
+    /// Tag being renamed and its new name as typed
+    pub renaming: Option<(String, String)>,
+}
+
+/// Diff of a revision against the current note source, kept until either changes
+pub struct HistoryDiff {
+    revision_id: String,
+    current_source: String,
+    lines: Vec<DiffLine>,
+}
+
+/// Merge of note on disk into unsaved edits, editable before it is applied
+pub struct MergeView {
+    pub path: PathBuf,
+    pub text: String,
+    pub has_conflicts: bool,
+}
+
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum ViewMode {
+    #[default]
+    Edit,
+    Preview,
+    Split,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SplitSide {
+    Editor,
+    Preview,
+}
+
+/// Scroll of split view sides, the side under pointer leads and the other follows in proportion
+#[derive(Default)]
+pub struct ScrollSync {
+    path: PathBuf,
+    leader: Option<SplitSide>,
+    /// Leader offset relative to its scroll range
+    fraction: f32,
+    editor_max_offset: f32,
+    preview_max_offset: f32,
+}
+
+/// Vertical scroll of a split view side after it was shown
+struct ScrollPosition {
+    offset: f32,
+    max_offset: f32,
+    hovered: bool,
+}
+
+impl ScrollPosition {
+    fn of_output<R>(ui: &Ui, output: &ScrollAreaOutput<R>) -> Self {
+        Self {
+            offset: output.state.offset.y,
+            max_offset: (output.content_size.y - output.inner_rect.height()).max(0.),
+            hovered: ui.rect_contains_pointer(output.inner_rect),
+        }
+    }
+}
+
+impl ScrollSync {
+    fn reset_for(&mut self, path: &Path) {
+        if self.path != path {
+            *self = Self {
+                path: path.to_path_buf(),
+                ..Default::default()
+            };
+        }
+    }
+
+    fn lead(&mut self, side: SplitSide) {
+        self.leader = Some(side);
+    }
+
+    /// Offset the side has to follow, `None` when the side leads
+    fn offset_for(&self, side: SplitSide) -> Option<f32> {
+        let max_offset = match side {
+            SplitSide::Editor => self.editor_max_offset,
+            SplitSide::Preview => self.preview_max_offset,
+        };
+        self.leader
+            .is_some_and(|leader| leader != side)
+            .then_some(self.fraction * max_offset)
+    }
+
+    fn update(&mut self, editor: ScrollPosition, preview: ScrollPosition) {
+        self.editor_max_offset = editor.max_offset;
+        self.preview_max_offset = preview.max_offset;
+        if editor.hovered {
+            self.leader = Some(SplitSide::Editor);
+        } else if preview.hovered {
+            self.leader = Some(SplitSide::Preview);
+        }
+        let leader = match self.leader {
+            Some(SplitSide::Editor) => editor,
+            Some(SplitSide::Preview) => preview,
+            None => return,
+        };
+        self.fraction = if leader.max_offset > 0. {
+            leader.offset / leader.max_offset
+        } else {
+            0.
+        };
+    }
 }
 
+/// Hits cached until query or search index changes
 #[derive(Default)]
+pub struct SearchResults {
+    query: String,
+    generation: u64,
+    hits: Vec<SearchHit>,
+}
+
+/// Inline rename of explorer item
+pub struct RenameState {
+    pub path: PathBuf,
+    pub name: String,
+    focus_requested: bool,
+}
+
+impl RenameState {
+    pub fn new(path: PathBuf) -> Self {
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Self {
+            path,
+            name,
+            focus_requested: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ExplorerLayout {
     Windowed,
     #[default]
     SideBar,
 }
 
+/// Vault as typed in settings, vaults are applied all at once
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct VaultDraft {
+    pub name: String,
+    pub base_path: String,
+    /// Empty for the default scratch pad
+    pub scratch_pad_path: String,
+    pub git_history: bool,
+    pub snapshots: bool,
+}
+
+impl VaultDraft {
+    fn of(vault: &VaultSettings) -> Self {
+        Self {
+            name: vault.name.clone(),
+            base_path: vault.location.base_path.to_string_lossy().into_owned(),
+            scratch_pad_path: vault
+                .location
+                .scratch_pad_path
+                .as_ref()
+                .map(|path| path.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            git_history: vault.location.git_history,
+            snapshots: vault.location.snapshots,
+        }
+    }
+
+    fn to_vault(&self) -> Option<VaultSettings> {
+        let name = self.name.trim();
+        let base_path = self.base_path.trim();
+        let scratch_pad_path = self.scratch_pad_path.trim();
+        (!name.is_empty() && !base_path.is_empty()).then(|| VaultSettings {
+            name: name.to_owned(),
+            location: LocationSettings {
+                base_path: PathBuf::from(base_path),
+                scratch_pad_path: (!scratch_pad_path.is_empty()).then(|| scratch_pad_path.into()),
+                git_history: self.git_history,
+                snapshots: self.snapshots,
+            },
+        })
+    }
+}
+
+/// Vaults from drafts, `None` when some draft is incomplete or names repeat
+fn vaults_of_drafts(drafts: &[VaultDraft]) -> Option<Vec<VaultSettings>> {
+    let vaults = drafts
+        .iter()
+        .map(VaultDraft::to_vault)
+        .collect::<Option<Vec<_>>>()?;
+    let names = vaults
+        .iter()
+        .map(|vault| vault.name.as_str())
+        .collect::<HashSet<_>>();
+    (!vaults.is_empty() && names.len() == vaults.len()).then_some(vaults)
+}
+
+/// What is restored when switching back to a vault
+pub struct VaultSession {
+    pub last_note: PathBuf,
+    pub tabs: Vec<Tab>,
+    pub expanded_dirs: HashSet<PathBuf>,
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct NoteView {
+    pub scroll: f32,
+    /// Character index
+    pub cursor: Option<usize>,
+    /// Unix time the note was last shown at
+    pub shown_at: i64,
+}
+
+/// Order of notes and folders in explorer, times are newest first
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum ExplorerSort {
+    #[default]
+    Name,
+    Created,
+    Modified,
+}
+
+/// How explorer lists folder content
+#[derive(Clone, Copy)]
+struct ExplorerView<'a> {
+    sort: ExplorerSort,
+    show_hidden: bool,
+    expanded: &'a HashSet<PathBuf>,
+}
+
+impl ExplorerSort {
+    fn sort(self, entries: &mut [(&str, &Path)], metadata: &HashMap<String, FileMetadata>) {
+        let time = |name: &str| {
+            metadata.get(name).map(|metadata| match self {
+                ExplorerSort::Name => DateTime::<Utc>::default(),
+                ExplorerSort::Created => metadata.creation_time,
+                ExplorerSort::Modified => metadata.modification_time,
+            })
+        };
+        match self {
+            ExplorerSort::Name => entries.sort_by_key(|(name, _)| *name),
+            ExplorerSort::Created | ExplorerSort::Modified => {
+                entries.sort_by(|(a, _), (b, _)| time(b).cmp(&time(a)).then_with(|| a.cmp(b)))
+            }
+        }
+    }
+}
+
+impl UiState {
+    fn open_settings_window(&mut self) {
+        self.settings_window = true;
+        self.vault_drafts = self
+            .settings
+            .notes
+            .vaults
+            .iter()
+            .map(VaultDraft::of)
+            .collect();
+    }
+
+    /// Drop state tied to notes of the previous location
+    fn forget_notes(&mut self) {
+        self.selected_trash_entry = None;
+        self.renaming = None;
+        self.search_results = Default::default();
+        self.pending_jump = None;
+        self.view_modes.clear();
+        self.markdown_cache = Default::default();
+        self.scroll_sync = Default::default();
+        self.merge_view = None;
+        self.selected_revision = None;
+        self.history_diff = None;
+        self.selected_snapshot = None;
+        self.edited_title = None;
+        self.tag_filter = Default::default();
+        self.link_completion = Default::default();
+        self.backlink_results = Default::default();
+        self.pending_rename = None;
+        self.graph_view = Default::default();
+    }
+}
+
 impl Default for UiState {
     fn default() -> Self {
         Self {
+            settings: Default::default(),
+            applied_settings: Default::default(),
+            saved_settings: Default::default(),
+            settings_window: Default::default(),
+            vault_drafts: Default::default(),
+            vault_sessions: Default::default(),
+            expanded_dirs: Default::default(),
+            note_views: Default::default(),
+            unrestored_views: Default::default(),
+            window: Default::default(),
             explorer: true,
+            explorer_sort: Default::default(),
             egui_settings: Default::default(),
-            explorer_layout: Default::default(),
             trash: Default::default(),
+            selected_trash_entry: Default::default(),
+            renaming: Default::default(),
+            icon_search: Default::default(),
+            search: Default::default(),
+            search_query: Default::default(),
+            search_results: Default::default(),
+            pending_jump: Default::default(),
+            view_modes: Default::default(),
+            markdown_cache: Default::default(),
+            scroll_sync: Default::default(),
+            merge_view: Default::default(),
+            history: Default::default(),
+            selected_revision: Default::default(),
+            history_diff: Default::default(),
+            versions: Default::default(),
+            selected_snapshot: Default::default(),
+            edited_title: Default::default(),
+            tag_filter: Default::default(),
+            link_completion: Default::default(),
+            backlinks: Default::default(),
+            backlink_results: Default::default(),
+            pending_rename: None,
+            graph: false,
+            graph_view: Default::default(),
+            close_prompt: false,
+            close_confirmed: false,
+            discard_unsaved: false,
         }
     }
 }
 
-/// Create demo instance
 impl NotesApp {
-    pub fn init() -> Self {
-        let app = NonBlockingApplication::init(ApplicationConfig::default()).unwrap();
-        Self {
+    pub fn init(session: Session, ctx: &Context) -> io::Result<Self> {
+        let settings = Settings::load();
+        let mut app = open_configured_vault(&settings)?;
+        // Changes made by other programs show up without waiting for input
+        let ctx = ctx.clone();
+        app.set_wake(Wake::new(move || ctx.request_repaint()));
+        let mut ui_state = UiState {
+            saved_settings: Some(settings.clone()),
+            settings,
+            explorer: session.explorer,
+            trash: session.trash,
+            window: session.window,
+            ..Default::default()
+        };
+        ui_state.vault_sessions = session
+            .vaults
+            .into_iter()
+            .map(|vault| {
+                let session = VaultSession {
+                    last_note: vault.current_note,
+                    tabs: vault.tabs,
+                    expanded_dirs: vault.expanded_dirs.into_iter().collect(),
+                };
+                (vault.base_path, session)
+            })
+            .collect();
+        ui_state.unrestored_views = session.notes.iter().map(|note| note.path.clone()).collect();
+        ui_state.note_views = session
+            .notes
+            .into_iter()
+            .map(|note| {
+                let view = NoteView {
+                    scroll: note.scroll,
+                    cursor: note.cursor,
+                    shown_at: note.shown_at,
+                };
+                (note.path, view)
+            })
+            .collect();
+        restore_vault_session(&mut app, &mut ui_state);
+
+        Ok(Self {
             app,
             command_queue: Default::default(),
-            ui_state: Default::default(),
+            ui_state,
+        })
+    }
+
+    fn session(&self) -> Session {
+        let current_vault = VaultState {
+            base_path: self.app.base_dir_path().to_path_buf(),
+            current_note: self.app.current_note_path().to_path_buf(),
+            tabs: self.app.tabs().iter().cloned().collect(),
+            expanded_dirs: self.ui_state.expanded_dirs.iter().cloned().collect(),
+        };
+        let other_vaults = self
+            .ui_state
+            .vault_sessions
+            .iter()
+            .map(|(base_path, session)| VaultState {
+                base_path: base_path.clone(),
+                current_note: session.last_note.clone(),
+                tabs: session.tabs.clone(),
+                expanded_dirs: session.expanded_dirs.iter().cloned().collect(),
+            });
+        Session {
+            explorer: self.ui_state.explorer,
+            trash: self.ui_state.trash,
+            window: self.ui_state.window.clone(),
+            vaults: std::iter::once(current_vault).chain(other_vaults).collect(),
+            notes: self
+                .ui_state
+                .note_views
+                .iter()
+                .map(|(path, view)| NoteState {
+                    path: path.clone(),
+                    scroll: view.scroll,
+                    cursor: view.cursor,
+                    shown_at: view.shown_at,
+                })
+                .collect(),
+        }
+    }
+
+    /// Apply settings changed in UI, they are written to config file once pointer is released,
+    /// so that dragging a slider does not write on every frame
+    fn sync_settings(&mut self, ctx: &Context) {
+        let settings = &self.ui_state.settings;
+        let applied = self.ui_state.applied_settings.as_ref();
+        if applied.is_some_and(|applied| applied == settings) {
+            return self.save_settings(ctx);
+        }
+
+        if applied.is_none_or(|applied| applied.appearance != settings.appearance) {
+            settings.appearance.apply(ctx);
+        }
+        if applied.is_none_or(|applied| applied.locale != settings.locale) {
+            settings.apply_locale();
+        }
+        self.app
+            .set_autosave(settings.notes.autosave, settings.notes.save_delay());
+        self.app
+            .set_snapshot_retention(settings.notes.snapshot_retention());
+        self.ui_state.applied_settings = Some(settings.clone());
+        self.save_settings(ctx);
+    }
+
+    fn save_settings(&mut self, ctx: &Context) {
+        if self.ui_state.saved_settings.as_ref() == Some(&self.ui_state.settings)
+            || ctx.input(|i| i.pointer.any_down())
+        {
+            return;
         }
+        // Not retried until the next change, failure is reported once
+        if let Err(err) = self.ui_state.settings.save() {
+            log::error!("Failed to save settings: {err}");
+            self.app
+                .report_error(format!("{}: {err}", t!("settings_not_saved")));
+        }
+        self.ui_state.saved_settings = Some(self.ui_state.settings.clone());
     }
 }
 
 impl eframe::App for NotesApp {
     fn update(&mut self, ctx: &eframe::egui::Context, _frame: &mut eframe::Frame) {
+        self.sync_settings(ctx);
+        ctx.input(|i| WindowGeometry::update(&mut self.ui_state.window, i.viewport()));
         self.app.poll_background_tasks();
+        self.hold_close_for_unsaved_notes(ctx);
+        if ctx.input_mut(|i| i.consume_shortcut(&SEARCH_SHORTCUT)) {
+            self.ui_state.search = !self.ui_state.search;
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&SAVE_SHORTCUT)) {
+            self.command_queue.push_back(Command::SaveNoteNow(
+                self.app.current_note_path().to_path_buf(),
+            ));
+        }
         // if ctx.input(|i| i.viewport().fullscreen.is_none_or(|fullscreen| !fullscreen)) {
         //     TopBottomPanel::top("native_title_bar_padding_panel")
         //         .frame(Frame::side_top_panel(&ctx.style()).inner_margin(0.))
@@ -112,6 +726,18 @@ impl eframe::App for NotesApp {
                     {
                         self.ui_state.egui_settings = !self.ui_state.egui_settings;
                     }
+                    if Button::selectable(self.ui_state.settings_window, phosphor_icons::GEAR)
+                        .ui(ui)
+                        .on_hover_text(t!("settings"))
+                        .clicked()
+                    {
+                        if self.ui_state.settings_window {
+                            self.ui_state.settings_window = false;
+                        } else {
+                            self.ui_state.open_settings_window();
+                        }
+                    }
+                    self.vault_menu_ui(ui);
                     if Button::selectable(self.ui_state.explorer, phosphor_icons::LIST_DASHES)
                         .ui(ui)
                         .on_hover_text(t!("explorer"))
@@ -126,6 +752,94 @@ impl eframe::App for NotesApp {
                     {
                         self.ui_state.trash = !self.ui_state.trash;
                     }
+                    if Button::selectable(self.ui_state.search, phosphor_icons::MAGNIFYING_GLASS)
+                        .ui(ui)
+                        .on_hover_text(format!(
+                            "{} ({})",
+                            t!("search"),
+                            ctx.format_shortcut(&SEARCH_SHORTCUT)
+                        ))
+                        .clicked()
+                    {
+                        self.ui_state.search = !self.ui_state.search;
+                    }
+                    if self.app.git_history()
+                        && Button::selectable(
+                            self.ui_state.history,
+                            phosphor_icons::CLOCK_COUNTER_CLOCKWISE,
+                        )
+                        .ui(ui)
+                        .on_hover_text(t!("history"))
+                        .clicked()
+                    {
+                        self.ui_state.history = !self.ui_state.history;
+                    }
+                    if self.app.snapshots_enabled()
+                        && Button::selectable(self.ui_state.versions, phosphor_icons::STACK)
+                            .ui(ui)
+                            .on_hover_text(t!("versions"))
+                            .clicked()
+                    {
+                        self.ui_state.versions = !self.ui_state.versions;
+                    }
+                    if Button::selectable(self.ui_state.backlinks, phosphor_icons::LINK)
+                        .ui(ui)
+                        .on_hover_text(t!("backlinks"))
+                        .clicked()
+                    {
+                        self.ui_state.backlinks = !self.ui_state.backlinks;
+                    }
+                    if Button::selectable(self.ui_state.graph, phosphor_icons::GRAPH)
+                        .ui(ui)
+                        .on_hover_text(t!("graph"))
+                        .clicked()
+                    {
+                        self.ui_state.graph = !self.ui_state.graph;
+                    }
+                    if let Some(error) = self.app.errors().last() {
+                        ui.separator();
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            format!("{} {}", phosphor_icons::WARNING, error),
+                        );
+                        if ui.small_button(phosphor_icons::X).clicked() {
+                            self.command_queue.push_back(Command::DismissErrors);
+                        }
+                    }
+                    if let Some(name) = self.app.recently_trashed() {
+                        ui.separator();
+                        ui.weak(t!("note_added_to_trash"));
+                        if ui
+                            .small_button(format!(
+                                "{} {}",
+                                phosphor_icons::ARROW_CCW,
+                                t!("restore_from_trash")
+                            ))
+                            .clicked()
+                        {
+                            self.command_queue
+                                .push_back(Command::RestoreFromTrash(name.to_owned()));
+                            self.command_queue
+                                .push_back(Command::DismissRecentlyTrashed);
+                        }
+                        if ui.small_button(phosphor_icons::X).clicked() {
+                            self.command_queue
+                                .push_back(Command::DismissRecentlyTrashed);
+                        }
+                    }
+                    if let Some(relink) = self.app.last_relink() {
+                        ui.separator();
+                        ui.weak(t!("links_updated", count = relink.link_count));
+                        if ui
+                            .small_button(format!("{} {}", phosphor_icons::ARROW_CCW, t!("undo")))
+                            .clicked()
+                        {
+                            self.command_queue.push_back(Command::UndoRelink);
+                        }
+                        if ui.small_button(phosphor_icons::X).clicked() {
+                            self.command_queue.push_back(Command::DismissRelink);
+                        }
+                    }
                 });
             });
 
@@ -135,11 +849,20 @@ impl eframe::App for NotesApp {
             .open(&mut self.ui_state.egui_settings)
             .show(ctx, |ui| ctx.settings_ui(ui));
 
+        self.settings_ui_windowed(ctx);
         self.trash_ui_windowed(ctx);
+        self.search_ui_windowed(ctx);
+        self.merge_ui_windowed(ctx);
+        self.rename_ui_windowed(ctx);
+        self.history_ui_windowed(ctx);
+        self.versions_ui_windowed(ctx);
+        self.backlinks_ui_windowed(ctx);
+        self.graph_ui_windowed(ctx);
+        self.close_prompt_ui_windowed(ctx);
 
         // Draw Explorer
         if self.ui_state.explorer {
-            match self.ui_state.explorer_layout {
+            match self.ui_state.settings.explorer_layout {
                 ExplorerLayout::Windowed => {
                     egui::Window::new("Explorer Window")
                         .title_bar(false)
@@ -164,7 +887,8 @@ impl eframe::App for NotesApp {
                                     .on_hover_text("To side bar")
                                     .clicked()
                                 {
-                                    self.ui_state.explorer_layout = ExplorerLayout::SideBar;
+                                    self.ui_state.settings.explorer_layout =
+                                        ExplorerLayout::SideBar;
                                 }
                                 ui.add(
                                     Label::new(format!(
@@ -175,7 +899,12 @@ impl eframe::App for NotesApp {
                                     .selectable(false),
                                 );
                             });
-                            Self::explorer_ui(&mut self.app, &mut self.command_queue, ui)
+                            Self::explorer_ui(
+                                &self.app,
+                                &mut self.command_queue,
+                                &mut self.ui_state,
+                                ui,
+                            )
                         });
                 }
                 ExplorerLayout::SideBar => {
@@ -186,9 +915,14 @@ impl eframe::App for NotesApp {
                             .on_hover_text("To window")
                             .clicked()
                         {
-                            self.ui_state.explorer_layout = ExplorerLayout::Windowed
+                            self.ui_state.settings.explorer_layout = ExplorerLayout::Windowed
                         }
-                        Self::explorer_ui(&mut self.app, &mut self.command_queue, ui);
+                        Self::explorer_ui(
+                            &self.app,
+                            &mut self.command_queue,
+                            &mut self.ui_state,
+                            ui,
+                        );
                     });
                 }
             }
@@ -207,7 +941,12 @@ impl eframe::App for NotesApp {
                                 ctx.style().spacing.item_spacing.y as i8 * 2,
                             )),
                         )
-                        .show_inside(ui, |ui| Self::path_bar_ui(&self.app, ui));
+                        .show_inside(ui, |ui| {
+                            self.tabs_ui(ui);
+                            ui.separator();
+                            self.path_bar_ui(ui);
+                            self.file_times_ui(ui);
+                        });
                 }
 
                 // Draw Title and Editor
@@ -222,7 +961,36 @@ impl eframe::App for NotesApp {
             });
 
         while let Some(command) = self.command_queue.pop_front() {
-            handle_command(&mut self.app, command);
+            handle_command(&mut self.app, &mut self.ui_state, command);
+        }
+
+        let next_due = self
+            .app
+            .next_save_due()
+            .into_iter()
+            .chain(self.app.next_commit_due())
+            .min();
+        if let Some(due) = next_due {
+            ctx.request_repaint_after(due.saturating_duration_since(Instant::now()));
+        }
+    }
+
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        // Without autosave unsaved notes were saved or discarded in the close prompt
+        if self.app.autosave()
+            && !self.ui_state.discard_unsaved
+            && let Err(err) = self.app.flush_saves()
+        {
+            log::error!("Failed to save notes: {err}");
+        }
+        self.app.flush_commit();
+        if self.ui_state.saved_settings.as_ref() != Some(&self.ui_state.settings)
+            && let Err(err) = self.ui_state.settings.save()
+        {
+            log::error!("Failed to save settings: {err}");
+        }
+        if let Err(err) = self.session().save() {
+            log::error!("Failed to save session: {err}");
         }
     }
 }
@@ -286,6 +1054,7 @@ impl NotesApp {
     fn explorer_ui(
         app: &NonBlockingApplication,
         command_queue: &mut VecDeque<Command>,
+        ui_state: &mut UiState,
         ui: &mut Ui,
     ) {
         ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
@@ -293,7 +1062,7 @@ impl NotesApp {
                 .stick_to_bottom(false)
                 .show(ui, |ui| {
                     {
-                        let selected = app.is_selected(&app.scratch_pad_path());
+                        let selected = app.is_selected(app.scratch_pad_path());
                         if ui.add(scratch_pad_label(selected)).clicked() {
                             command_queue.push_back(Command::ReadAndSelectNote(
                                 app.scratch_pad_path().to_path_buf(),
@@ -302,118 +1071,203 @@ impl NotesApp {
                     }
 
                     ui.separator();
-                    let dir_action = create_action_buttons_ui(ui, app.base_dir_path());
-                    // in root switch to created note
-                    let dir_action = dir_action.map(|action| match action {
-                        Command::CreateNote(dir) => Command::CreateNoteThenSelect(dir),
-                        _ => action,
-                    });
-                    if let Some(action) = dir_action {
+                    if let Some(action) = create_action_buttons_ui(ui, app.base_dir_path()) {
                         command_queue.push_back(action);
                     }
+                    ui.menu_button(
+                        format!("{} {}", phosphor_icons::SORT_ASCENDING, t!("sort_by")),
+                        |ui| explorer_sort_ui(ui, &mut ui_state.explorer_sort),
+                    );
 
                     let mut add_actions = VecDeque::new();
-                    if let Some(root) = app.base_dir() {
-                        explorer_folder_content_ui(&app, ui, root, &mut add_actions, false);
+                    if let Some(MemoryCell::ReadError(err)) = app.get_dir_cell(app.base_dir_path())
+                    {
+                        if io_error_ui(ui, &t!("read_error"), err) {
+                            command_queue
+                                .push_back(Command::ReloadDir(app.base_dir_path().to_path_buf()));
+                        }
+                    } else if let Some(root) = app.base_dir() {
+                        explorer_folder_content_ui(
+                            app,
+                            ui,
+                            root,
+                            &mut add_actions,
+                            &mut ui_state.renaming,
+                            ExplorerView {
+                                sort: ui_state.explorer_sort,
+                                show_hidden: ui_state.settings.show_hidden_files,
+                                expanded: &ui_state.expanded_dirs,
+                            },
+                        );
                         command_queue.extend(add_actions);
                     } else {
                         command_queue
                             .push_back(Command::ReadDir(app.base_dir_path().to_path_buf()));
                     }
+
+                    ui.separator();
+                    CollapsingHeader::new(format!("{} {}", phosphor_icons::TAG, t!("tags")))
+                        .id_salt("explorer_tags")
+                        .show(ui, |ui| {
+                            command_queue.extend(explorer_tags_ui(
+                                app,
+                                ui,
+                                &mut ui_state.tag_filter,
+                            ));
+                        });
                 });
         });
     }
 
-    fn path_bar_ui(app: &NonBlockingApplication, ui: &mut Ui) {
+    fn tabs_ui(&mut self, ui: &mut Ui) {
+        ScrollArea::horizontal()
+            .id_salt("tabs_scroll_area")
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for (index, tab) in self.app.tabs().iter().enumerate() {
+                        let selected = self.app.is_selected(&tab.path);
+                        if let Some(command) = tab_ui(&self.app, ui, index, tab, selected) {
+                            self.command_queue.push_back(command);
+                        }
+                    }
+                });
+            });
+    }
+
+    fn path_bar_ui(&mut self, ui: &mut Ui) {
         let layout = Layout::left_to_right(Align::TOP).with_main_align(Align::LEFT);
         ui.with_layout(layout, |ui| {
-            Label::new(RichText::new(app.current_note_path().to_string_lossy()))
+            let note_path = self.app.current_note_path().to_path_buf();
+            let is_scratch_pad = note_path == self.app.scratch_pad_path();
+            let icon = if is_scratch_pad {
+                SCRATCH_PAD_ICON
+            } else {
+                self.app.note_icon(&note_path)
+            };
+            let can_pick_icon = !is_scratch_pad && self.app.get_note(&note_path).is_some();
+            let icon_button = ui
+                .add_enabled(can_pick_icon, Button::new(icon).frame(false))
+                .on_hover_text(t!("change_icon"));
+            Popup::from_toggle_button_response(&icon_button)
+                .close_behavior(PopupCloseBehavior::CloseOnClickOutside)
+                .show(|ui| {
+                    if let Some(icon) = icon_picker_ui(ui, &mut self.ui_state.icon_search) {
+                        self.command_queue.push_back(Command::SetIcon {
+                            path: note_path.clone(),
+                            icon: icon.to_owned(),
+                        });
+                        ui.close();
+                    }
+                });
+
+            Label::new(RichText::new(note_path.to_string_lossy()))
                 .selectable(false)
                 .ui(ui);
+            // With autosave edits are saved anyway, no need to flicker
+            if !self.app.autosave() && self.app.note_is_dirty(&note_path) {
+                ui.weak(format!("{} {}", phosphor_icons::DOT_OUTLINE, t!("unsaved")))
+                    .on_hover_text(ui.ctx().format_shortcut(&SAVE_SHORTCUT));
+            }
+
+            if self.app.get_note(&note_path).is_some() {
+                ui.with_layout(Layout::right_to_left(Align::TOP), |ui| {
+                    self.view_mode_buttons_ui(ui, &note_path)
+                });
+            }
         });
     }
 
-    // fn title_ui(&mut self, ui: &mut Ui) {
-    //     let layout = Layout::top_down_justified(Align::LEFT);
-    //     ui.with_layout(layout, |ui| {
-    //         let current_note_id = self.state.current_note_id;
-    //         let node = self.state.lookup_current_note().node;
-    //         let note = &node.data;
-    //
-    //         self.state.get_item_path_str(current_note_id).map(|path| {
-    //             ui.weak(path);
-    //             ui.add_space(item_spacing(ui.ctx(), &layout));
-    //         });
-    //
-    //         let mod_date = format_date_time(&node.modification_time);
-    //         ui.weak(format!("{} {}", t!("modified"), mod_date));
-    //
-    //         ui.add_space(item_spacing(ui.ctx(), &layout));
-    //
-    //         let icon_label = Label::new(RichText::new(note.icon()).heading());
-    //         let title_text = match self.state.lookup_current_note_content() {
-    //             MaybeMutRef::Mut(content) => &mut content.title,
-    //             MaybeMutRef::Immut(content) => &mut content.title.clone(),
-    //         };
-    //         let title_text_edit = TextEdit::multiline(title_text)
-    //             .desired_rows(1)
-    //             .clip_text(false)
-    //             .font(TextStyle::Heading)
-    //             .frame(false)
-    //             .clip_text(true)
-    //             .return_key(None)
-    //             .background_color(ui.visuals().panel_fill);
-    //
-    //         ui.add(icon_label);
-    //
-    //         let title_edit_response = ui.add(title_text_edit);
-    //         if title_edit_response.changed() {
-    //             self.state.touch_current_note();
-    //         }
-    //
-    //         let switch_focus = title_edit_response
-    //             .ctx
-    //             .input(|i| i.key_pressed(Key::Enter) || i.key_pressed(Key::Tab));
-    //         if switch_focus {
-    //             title_edit_response.surrender_focus();
-    //         }
-    //         ui.add_space(item_spacing(ui.ctx(), &layout));
-    //     });
-    // }
+    fn file_times_ui(&self, ui: &mut Ui) {
+        let Some(metadata) = self.app.get_metadata(self.app.current_note_path()) else {
+            return;
+        };
+        ui.weak(format!(
+            "{} {}",
+            t!("modified"),
+            format_date_time(&metadata.modification_time)
+        ))
+        .on_hover_text(format!(
+            "{} {}",
+            t!("created"),
+            format_date_time(&metadata.creation_time)
+        ));
+    }
+
+    /// Heading-style title of the note, a note without an explicit title shows its first line
+    /// as a hint and gets a heading once typed into
+    fn title_ui(&mut self, ui: &mut Ui, note_path: &Path) {
+        let Some(note) = self.app.get_note(note_path) else {
+            return;
+        };
+        let layout = Layout::top_down_justified(Align::LEFT);
+        ui.add_space(item_spacing(ui.ctx(), &layout));
+        let mut title = note.data.explicit_title().unwrap_or_default().to_owned();
+        let hint = note
+            .data
+            .title()
+            .map(str::to_owned)
+            .or_else(|| Some(note_path.file_name()?.to_string_lossy().into_owned()))
+            .unwrap_or_default();
+        let response = TextEdit::singleline(&mut title)
+            .id_salt(("note_title", note_path))
+            .hint_text(hint)
+            .font(TextStyle::Heading)
+            .frame(false)
+            .desired_width(f32::INFINITY)
+            .background_color(ui.visuals().panel_fill)
+            .ui(ui);
+        ui.add_space(item_spacing(ui.ctx(), &layout));
+
+        if response.changed() {
+            self.ui_state.edited_title = Some(note_path.to_path_buf());
+            self.command_queue.push_back(Command::SetTitle {
+                path: note_path.to_path_buf(),
+                title,
+            });
+            if self.app.autosave() {
+                self.command_queue
+                    .push_back(Command::SaveNote(note_path.to_path_buf()));
+            }
+        }
+        if response.lost_focus() && self.ui_state.edited_title.as_deref() == Some(note_path) {
+            self.ui_state.edited_title = None;
+            if self.ui_state.settings.rename_file_with_title
+                && note_path != self.app.scratch_pad_path()
+            {
+                self.command_queue
+                    .push_back(Command::RenameToTitle(note_path.to_path_buf()));
+            }
+        }
+    }
 
     fn note_content_ui(&mut self, ui: &mut Ui) {
         let note_path = self.app.current_note_path().to_owned();
         let note_state = self.app.note_state(&note_path);
         match note_state {
             Some(MemoryCellState::Ready) => {
-                let current_note = self.app.get_note_mut(&note_path).unwrap();
-                let _scroll_area = ScrollArea::both().stick_to_bottom(false).show(ui, |ui| {
-                    ui.add_space(ui.spacing().item_spacing.y);
-
-                    if TextEdit::multiline(&mut current_note.data.text)
-                        .desired_width(f32::INFINITY)
-                        .font(TextStyle::Body)
-                        .background_color(ui.visuals().panel_fill)
-                        .lock_focus(true)
-                        .desired_rows(5)
-                        .clip_text(false)
-                        .frame(false)
-                        .ui(ui)
-                        .changed()
-                    {
-                        self.command_queue
-                            .push_back(Command::MarkChanged(note_path.to_path_buf()));
-                        self.command_queue
-                            .push_back(Command::SaveNote(note_path.to_path_buf()));
-                    }
-                });
+                self.note_view_ui(ui, &note_path);
             }
             Some(MemoryCellState::PendingRead) => {
                 ui.label("Loading...");
             }
-            Some(MemoryCellState::Error) => {
-                todo!("Display IO Error")
-            }
+            Some(MemoryCellState::Error) => match self.app.get_note_cell(&note_path) {
+                Some(MemoryCell::ValueWriteError(_, err)) => {
+                    ui.add_space(ui.spacing().item_spacing.y);
+                    if io_error_ui(ui, &t!("write_error"), err) {
+                        self.command_queue
+                            .push_back(Command::SaveNoteNow(note_path.to_path_buf()));
+                    }
+                    self.note_view_ui(ui, &note_path);
+                }
+                Some(MemoryCell::ReadError(err)) => {
+                    ui.add_space(ui.spacing().item_spacing.y);
+                    if io_error_ui(ui, &t!("read_error"), err) {
+                        self.command_queue
+                            .push_back(Command::ReloadNote(note_path.to_path_buf()));
+                    }
+                }
+                _ => {}
+            },
             None => {
                 self.command_queue
                     .push_back(Command::ReadAndSelectNote(note_path.to_path_buf()));
@@ -421,80 +1275,1730 @@ impl NotesApp {
         }
     }
 
-    fn trash_ui_windowed(&mut self, ctx: &Context) {
-        Window::new(t!("trash"))
-            .collapsible(true)
-            .vscroll(true)
-            .open(&mut self.ui_state.trash)
-            .show(ctx, |ui| { /*TODO*/ });
-    }
-}
-
-fn date_time_fmt() -> &'static str {
-    static DATE_TIME_FMT: LazyLock<String> =
-        std::sync::LazyLock::new(|| format!("%d.%m.%Y {} %H:%M", t!("at")));
-    &DATE_TIME_FMT
-}
-
-fn format_date_time(date: &DateTime<Utc>) -> String {
-    to_local_date_time(date).format(date_time_fmt()).to_string()
-}
+    fn note_view_ui(&mut self, ui: &mut Ui, note_path: &Path) {
+        if let Some(conflict) = self.app.conflict(note_path) {
+            ui.add_space(ui.spacing().item_spacing.y);
+            if let Some(command) = conflict_ui(ui, note_path, conflict.theirs.is_some()) {
+                self.command_queue.push_back(command);
+            }
+        }
 
-fn trash_label_ui(
-    ui: &mut Ui,
-    selected: &mut bool,
-    restore: &mut bool,
-    trashed: &Note,
-) -> egui::Response {
-    // let label = ui.add(note_label(*selected, todo!("note_name_in_dir"), trashed));
-    // label.context_menu(|ui| {
-    //     if ui
-    //         .button(format!(
-    //             "{} {}",
-    //             phosphor::ARROW_CCW,
-    //             t!("restore_from_trash")
-    //         ))
-    //         .clicked()
-    //     {
-    //         *restore = true;
-    //         ui.close();
-    //     }
-    // });
-    // if label.clicked() {
-    //     *selected = true
-    // }
-    // label
-    todo!()
-}
+        if note_path != self.app.scratch_pad_path() {
+            self.title_ui(ui, note_path);
+        }
 
-fn explorer_note_label_ui(
-    ui: &mut Ui,
-    selected: bool,
-    note_name_in_dir: &str,
-    note_path: &Path,
-) -> (egui::Response, VecDeque<Command>) {
-    let mut commands = VecDeque::new();
-    let label = ui.add(note_label(selected, note_name_in_dir));
-    label.context_menu(|ui| {
-        if ui
-            .button(format!("{} {}", phosphor_icons::TRASH, t!("trash_note")))
-            .clicked()
-        {
-            commands.push_back(Command::DeleteNote(note_path.to_path_buf()));
-            ui.close();
+        let view_mode = self.view_mode(note_path);
+        match view_mode {
+            ViewMode::Edit => {
+                self.note_editor_ui(ui, note_path, None);
+            }
+            ViewMode::Preview => {
+                self.note_preview_ui(ui, note_path, None);
+            }
+            ViewMode::Split => {
+                self.ui_state.scroll_sync.reset_for(note_path);
+                if self
+                    .ui_state
+                    .pending_jump
+                    .as_ref()
+                    .is_some_and(|(path, _)| path == note_path)
+                {
+                    self.ui_state.scroll_sync.lead(SplitSide::Editor);
+                }
+                let editor_offset = self.ui_state.scroll_sync.offset_for(SplitSide::Editor);
+                let preview_offset = self.ui_state.scroll_sync.offset_for(SplitSide::Preview);
+                ui.columns(2, |columns| {
+                    let editor = self.note_editor_ui(&mut columns[0], note_path, editor_offset);
+                    let preview = self.note_preview_ui(&mut columns[1], note_path, preview_offset);
+                    if let (Some(editor), Some(preview)) = (editor, preview) {
+                        self.ui_state.scroll_sync.update(editor, preview);
+                    }
+                });
+            }
         }
-    });
-    if label.clicked() && !selected {
-        commands.push_back(Command::ReadAndSelectNote(note_path.to_path_buf()));
     }
-    (label, commands)
-}
-
-fn note_label<'x>(selected: bool, note_name_in_dir: &str) -> Button<'x> {
-    let mut label_text = RichText::new(format!("{} {}", DEFAULT_ICON, &note_name_in_dir,));
 
-    if selected {
-        label_text = label_text.strong();
+    fn view_mode(&self, note_path: &Path) -> ViewMode {
+        self.ui_state
+            .view_modes
+            .get(note_path)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn view_mode_buttons_ui(&mut self, ui: &mut Ui, note_path: &Path) {
+        let current_mode = self.view_mode(note_path);
+        for (mode, icon, hint) in [
+            (ViewMode::Split, phosphor_icons::COLUMNS, t!("split_view")),
+            (ViewMode::Preview, phosphor_icons::EYE, t!("preview")),
+            (ViewMode::Edit, phosphor_icons::PENCIL_SIMPLE, t!("edit")),
+        ] {
+            if Button::selectable(current_mode == mode, icon)
+                .ui(ui)
+                .on_hover_text(hint)
+                .clicked()
+            {
+                self.ui_state
+                    .view_modes
+                    .insert(note_path.to_path_buf(), mode);
+            }
+        }
+    }
+
+    fn note_preview_ui(
+        &mut self,
+        ui: &mut Ui,
+        note_path: &Path,
+        scroll_offset: Option<f32>,
+    ) -> Option<ScrollPosition> {
+        let note = self.app.get_note(note_path)?;
+        if note.data.text.contains("[[") && self.app.get_index().is_none() {
+            self.command_queue.push_back(Command::BuildIndex);
+        }
+        let root = self.ui_state.markdown_cache.get(note_path, &note.data.text);
+        let base_path = self.app.base_dir_path();
+        let note_dir = note_path.parent().unwrap_or(base_path);
+        let names = self.app.note_names();
+
+        let mut scroll_area = ScrollArea::vertical()
+            .id_salt(("note_preview", note_path))
+            .auto_shrink(false);
+        if let Some(offset) = scroll_offset {
+            scroll_area = scroll_area.vertical_scroll_offset(offset);
+        }
+        let output = scroll_area.show(ui, |ui| {
+            ui.add_space(ui.spacing().item_spacing.y);
+            MarkdownView::new(note_dir, base_path)
+                .with_wiki_links(WikiLinkTargets {
+                    names: &names,
+                    base_path,
+                    note_path,
+                })
+                .show(ui, root)
+        });
+        if let Some(path) = &output.inner {
+            self.command_queue
+                .push_back(Command::ReadAndSelectNote(path.clone()));
+        }
+        Some(ScrollPosition::of_output(ui, &output))
+    }
+
+    fn note_editor_ui(
+        &mut self,
+        ui: &mut Ui,
+        note_path: &Path,
+        scroll_offset: Option<f32>,
+    ) -> Option<ScrollPosition> {
+        let autosave = self.app.autosave();
+        let editor_id = ui.make_persistent_id(("note_text", note_path));
+        let suggestions = self.link_suggestions(ui.ctx(), editor_id, note_path);
+        let mut picked_target = suggestions
+            .as_ref()
+            .and_then(|suggestions| self.link_completion_keys(ui, suggestions));
+
+        let current_note = self.app.get_note_mut(note_path)?;
+        let jump_to = match &self.ui_state.pending_jump {
+            Some((path, offset)) if path == note_path => {
+                let text = &current_note.data.text;
+                let offset = (0..=(*offset).min(text.len()))
+                    .rev()
+                    .find(|offset| text.is_char_boundary(*offset))
+                    .unwrap_or_default();
+                self.ui_state.pending_jump = None;
+                Some(CCursor::new(text[..offset].chars().count()))
+            }
+            _ => None,
+        };
+        let restored_view = self
+            .ui_state
+            .unrestored_views
+            .remove(note_path)
+            .then(|| self.ui_state.note_views.get(note_path).copied())
+            .flatten();
+        let mut scroll_area = ScrollArea::both()
+            .id_salt(("note_editor", note_path))
+            .stick_to_bottom(false);
+        if let Some(offset) = scroll_offset.or(restored_view.map(|view| view.scroll)) {
+            scroll_area = scroll_area.vertical_scroll_offset(offset);
+        }
+        let mut followed_target = None;
+        let output = scroll_area.show(ui, |ui| {
+            ui.add_space(ui.spacing().item_spacing.y);
+
+            let restored_cursor = restored_view
+                .and_then(|view| view.cursor)
+                .map(|index| CCursor::new(index.min(current_note.data.text.chars().count())));
+            if let Some(cursor) = jump_to.or(restored_cursor) {
+                let mut state = TextEditState::load(ui.ctx(), editor_id).unwrap_or_default();
+                state.cursor.set_char_range(Some(CCursorRange::one(cursor)));
+                state.store(ui.ctx(), editor_id);
+            }
+
+            let mut layouter = |ui: &Ui, text: &dyn TextBuffer, wrap_width: f32| {
+                let job = wiki_link_layout_job(ui, text.as_str(), wrap_width);
+                ui.fonts_mut(|fonts| fonts.layout_job(job))
+            };
+            let output = TextEdit::multiline(&mut current_note.data.text)
+                .id(editor_id)
+                .desired_width(f32::INFINITY)
+                .font(TextStyle::Body)
+                .background_color(ui.visuals().panel_fill)
+                .lock_focus(true)
+                .desired_rows(5)
+                .clip_text(false)
+                .frame(false)
+                .layouter(&mut layouter)
+                .show(ui);
+
+            if let Some(cursor) = jump_to {
+                let cursor_rect = output
+                    .galley
+                    .pos_from_cursor(cursor)
+                    .translate(output.galley_pos.to_vec2());
+                ui.scroll_to_rect(cursor_rect, Some(Align::Center));
+                output.response.request_focus();
+            }
+
+            // Ctrl+click follows wiki link under pointer
+            if ui.input(|input| input.modifiers.command)
+                && let Some(pointer) = output.response.hover_pos()
+            {
+                let cursor = output.galley.cursor_from_pos(pointer - output.galley_pos);
+                let text = &current_note.data.text;
+                if let Some(link) = links::link_at(text, byte_offset(text, cursor.index)) {
+                    ui.ctx().set_cursor_icon(CursorIcon::PointingHand);
+                    if output.response.clicked() {
+                        followed_target = Some(link.target.to_owned());
+                    }
+                }
+            }
+
+            if output.response.changed() {
+                self.command_queue
+                    .push_back(Command::MarkChanged(note_path.to_path_buf()));
+                if autosave {
+                    self.command_queue
+                        .push_back(Command::SaveNote(note_path.to_path_buf()));
+                }
+            }
+            let cursor_rect = output.cursor_range.map(|cursor_range| {
+                output
+                    .galley
+                    .pos_from_cursor(cursor_range.primary)
+                    .translate(output.galley_pos.to_vec2())
+            });
+            (output.cursor_range, cursor_rect)
+        });
+        let (cursor_range, cursor_rect) = output.inner;
+
+        if let (Some(suggestions), Some(cursor_rect)) = (&suggestions, cursor_rect) {
+            let selected = self.ui_state.link_completion.selected;
+            picked_target = picked_target
+                .or_else(|| link_suggestions_ui(ui, cursor_rect, suggestions, selected));
+        }
+        if let (Some(target), Some(cursor_range)) = (picked_target, cursor_range) {
+            let text = &mut current_note.data.text;
+            let cursor = byte_offset(text, cursor_range.primary.index);
+            if let Some(end) = links::complete_target(text, cursor, &target) {
+                let end = CCursor::new(text[..end].chars().count());
+                let mut state = TextEditState::load(ui.ctx(), editor_id).unwrap_or_default();
+                state.cursor.set_char_range(Some(CCursorRange::one(end)));
+                state.store(ui.ctx(), editor_id);
+                ui.memory_mut(|memory| memory.request_focus(editor_id));
+                self.command_queue
+                    .push_back(Command::MarkChanged(note_path.to_path_buf()));
+                if autosave {
+                    self.command_queue
+                        .push_back(Command::SaveNote(note_path.to_path_buf()));
+                }
+            }
+        }
+
+        let view = self
+            .ui_state
+            .note_views
+            .entry(note_path.to_path_buf())
+            .or_default();
+        view.scroll = output.state.offset.y;
+        if let Some(cursor_range) = cursor_range {
+            view.cursor = Some(cursor_range.primary.index);
+        }
+        view.shown_at = Utc::now().timestamp();
+
+        if let Some(target) = followed_target {
+            self.follow_link(note_path, &target);
+        }
+        Some(ScrollPosition::of_output(ui, &output))
+    }
+
+    /// Notes for the wiki link typed before the editor cursor, `None` when no link is typed
+    fn link_suggestions(
+        &mut self,
+        ctx: &Context,
+        editor_id: Id,
+        note_path: &Path,
+    ) -> Option<Vec<LinkSuggestion>> {
+        if !ctx.memory(|memory| memory.has_focus(editor_id)) {
+            return None;
+        }
+        let text = &self.app.get_note(note_path)?.data.text;
+        let cursor = TextEditState::load(ctx, editor_id)?
+            .cursor
+            .char_range()?
+            .primary
+            .index;
+        let typed = links::typed_target(text, byte_offset(text, cursor))?;
+        let completion = &mut self.ui_state.link_completion;
+        let shown_for = (note_path.to_path_buf(), typed.start);
+        if completion.dismissed.as_ref() == Some(&shown_for) {
+            return None;
+        }
+        completion.shown_for = Some(shown_for);
+        let query = &text[typed.clone()];
+        if completion.query != query {
+            completion.query = query.to_owned();
+            completion.selected = 0;
+        }
+        if self.app.get_index().is_none() {
+            self.command_queue.push_back(Command::BuildIndex);
+        }
+
+        let names = self.app.note_names();
+        let base_path = self.app.base_dir_path();
+        let suggestions = names
+            .matching(base_path, query, LINK_SUGGESTIONS_LIMIT)
+            .into_iter()
+            .filter(|path| *path != note_path)
+            .map(|path| LinkSuggestion {
+                target: names.link_target(base_path, path),
+                location: path
+                    .strip_prefix(base_path)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .into_owned(),
+            })
+            .collect::<Vec<_>>();
+        (!suggestions.is_empty()).then_some(suggestions)
+    }
+
+    /// Arrows pick a suggestion, Enter or Tab takes it and Escape closes suggestions. Keys are
+    /// taken before the editor gets them
+    fn link_completion_keys(&mut self, ui: &Ui, suggestions: &[LinkSuggestion]) -> Option<String> {
+        let completion = &mut self.ui_state.link_completion;
+        let count = suggestions.len();
+        ui.input_mut(|input| {
+            if input.consume_key(Modifiers::NONE, Key::ArrowDown) {
+                completion.selected = (completion.selected + 1) % count;
+            }
+            if input.consume_key(Modifiers::NONE, Key::ArrowUp) {
+                completion.selected = (completion.selected + count - 1) % count;
+            }
+            if input.consume_key(Modifiers::NONE, Key::Escape) {
+                completion.dismissed = completion.shown_for.take();
+            }
+            let picked = input.consume_key(Modifiers::NONE, Key::Enter)
+                || input.consume_key(Modifiers::NONE, Key::Tab);
+            picked.then(|| {
+                suggestions[completion.selected.min(count - 1)]
+                    .target
+                    .clone()
+            })
+        })
+    }
+
+    /// Open the note a wiki link of the note points to, missing notes are reported
+    fn follow_link(&mut self, note_path: &Path, target: &str) {
+        let resolved = self
+            .app
+            .note_names()
+            .resolve(self.app.base_dir_path(), note_path, target)
+            .map(Path::to_path_buf);
+        match resolved {
+            Some(path) => self
+                .command_queue
+                .push_back(Command::ReadAndSelectNote(path)),
+            None => self
+                .app
+                .report_error(t!("note_not_found", target = target).into_owned()),
+        }
+    }
+
+    fn settings_ui_windowed(&mut self, ctx: &Context) {
+        Window::new(t!("settings"))
+            .collapsible(true)
+            .resizable(false)
+            .open(&mut self.ui_state.settings_window)
+            .show(ctx, |ui| {
+                let settings = &mut self.ui_state.settings;
+                let drafts = &mut self.ui_state.vault_drafts;
+                ui.label(RichText::new(t!("vaults")).strong());
+                let single_vault = drafts.len() == 1;
+                let mut removed = None;
+                Grid::new("vault_settings")
+                    .num_columns(6)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.weak(t!("name"));
+                        ui.weak(t!("notes_folder"));
+                        ui.weak(t!("scratch_pad_file"));
+                        ui.weak(t!("git_history"));
+                        ui.weak(t!("snapshots"));
+                        ui.end_row();
+                        for (index, draft) in drafts.iter_mut().enumerate() {
+                            TextEdit::singleline(&mut draft.name)
+                                .desired_width(100.)
+                                .ui(ui);
+                            TextEdit::singleline(&mut draft.base_path).ui(ui);
+                            TextEdit::singleline(&mut draft.scratch_pad_path)
+                                .hint_text(t!("default"))
+                                .ui(ui);
+                            ui.checkbox(&mut draft.git_history, "")
+                                .on_hover_text(t!("git_history_hint"));
+                            ui.checkbox(&mut draft.snapshots, "")
+                                .on_hover_text(t!("snapshots_hint"));
+                            if ui
+                                .add_enabled(!single_vault, Button::new(phosphor_icons::X))
+                                .on_hover_text(t!("remove_vault"))
+                                .clicked()
+                            {
+                                removed = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(index) = removed {
+                    drafts.remove(index);
+                }
+                let vaults = vaults_of_drafts(drafts);
+                let changed = vaults
+                    .as_ref()
+                    .is_some_and(|vaults| *vaults != settings.notes.vaults);
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(format!("{} {}", phosphor_icons::PLUS, t!("add_vault")))
+                        .clicked()
+                    {
+                        drafts.push(Default::default());
+                    }
+                    if ui
+                        .add_enabled(changed, Button::new(t!("apply")))
+                        .on_disabled_hover_text(t!("vaults_hint"))
+                        .clicked()
+                    {
+                        self.command_queue
+                            .push_back(Command::SetVaults(drafts.clone()));
+                    }
+                    if ui.button(t!("cancel")).clicked() {
+                        *drafts = settings.notes.vaults.iter().map(VaultDraft::of).collect();
+                    }
+                });
+                ui.separator();
+
+                Grid::new("settings")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        ui.label(t!("autosave"));
+                        ui.horizontal(|ui| {
+                            ui.checkbox(&mut settings.notes.autosave, "");
+                            ui.add_enabled(
+                                settings.notes.autosave,
+                                DragValue::new(&mut settings.notes.autosave_delay_ms)
+                                    .range(0..=10_000)
+                                    .speed(10)
+                                    .suffix(t!("ms")),
+                            )
+                            .on_hover_text(t!("autosave_delay"));
+                        });
+                        ui.end_row();
+
+                        ui.label(t!("snapshots"));
+                        ui.horizontal(|ui| {
+                            DragValue::new(&mut settings.notes.snapshot_max_count)
+                                .range(1..=1000)
+                                .suffix(t!("versions_suffix"))
+                                .ui(ui)
+                                .on_hover_text(t!("snapshot_max_count"));
+                            DragValue::new(&mut settings.notes.snapshot_max_age_days)
+                                .range(1..=3650)
+                                .suffix(t!("days_suffix"))
+                                .ui(ui)
+                                .on_hover_text(t!("snapshot_max_age"));
+                        });
+                        ui.end_row();
+
+                        ui.label(t!("rename_file_with_title"));
+                        ui.checkbox(&mut settings.rename_file_with_title, "");
+                        ui.end_row();
+
+                        ui.label(t!("show_hidden_files"));
+                        ui.checkbox(&mut settings.show_hidden_files, "");
+                        ui.end_row();
+
+                        ui.label(t!("explorer"));
+                        ui.horizontal(|ui| {
+                            ui.radio_value(
+                                &mut settings.explorer_layout,
+                                ExplorerLayout::SideBar,
+                                t!("side_bar"),
+                            );
+                            ui.radio_value(
+                                &mut settings.explorer_layout,
+                                ExplorerLayout::Windowed,
+                                t!("window"),
+                            );
+                        });
+                        ui.end_row();
+
+                        ui.label(t!("language"));
+                        ComboBox::from_id_salt("locale")
+                            .selected_text(settings.locale.as_str())
+                            .show_ui(ui, |ui| {
+                                for locale in rust_i18n::available_locales!() {
+                                    ui.selectable_value(
+                                        &mut settings.locale,
+                                        locale.to_owned(),
+                                        locale,
+                                    );
+                                }
+                            });
+                        ui.end_row();
+
+                        let appearance = &mut settings.appearance;
+                        ui.label(t!("theme"));
+                        ui.horizontal(|ui| {
+                            for (theme, label) in [
+                                (Theme::System, t!("theme_system")),
+                                (Theme::Light, t!("theme_light")),
+                                (Theme::Dark, t!("theme_dark")),
+                            ] {
+                                ui.radio_value(&mut appearance.theme, theme, label);
+                            }
+                        });
+                        ui.end_row();
+
+                        for (label, size) in [
+                            (t!("body_font_size"), &mut appearance.body_font_size),
+                            (t!("heading_font_size"), &mut appearance.heading_font_size),
+                            (
+                                t!("monospace_font_size"),
+                                &mut appearance.monospace_font_size,
+                            ),
+                        ] {
+                            ui.label(label);
+                            ui.add(Slider::new(size, 8.0..=32.0).step_by(0.5));
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    fn vault_menu_ui(&mut self, ui: &mut Ui) {
+        let current = self.ui_state.settings.notes.vault().name.clone();
+        ui.menu_button(format!("{} {}", phosphor_icons::VAULT, current), |ui| {
+            for vault in &self.ui_state.settings.notes.vaults {
+                if ui
+                    .add(Button::selectable(vault.name == current, &vault.name))
+                    .on_hover_text(vault.location.base_path.to_string_lossy())
+                    .clicked()
+                {
+                    self.command_queue
+                        .push_back(Command::SwitchVault(vault.name.clone()));
+                }
+            }
+            ui.separator();
+            if ui
+                .button(format!("{} {}", phosphor_icons::GEAR, t!("manage_vaults")))
+                .clicked()
+            {
+                self.ui_state.open_settings_window();
+            }
+        });
+    }
+
+    fn trash_ui_windowed(&mut self, ctx: &Context) {
+        Window::new(t!("trash"))
+            .collapsible(true)
+            .vscroll(true)
+            .open(&mut self.ui_state.trash)
+            .show(ctx, |ui| {
+                let trash = match self.app.get_trash() {
+                    Some(MemoryCell::Value(trash)) => trash,
+                    Some(MemoryCell::ReadError(err) | MemoryCell::ValueWriteError(_, err)) => {
+                        ui.colored_label(ui.visuals().error_fg_color, err.to_string());
+                        return;
+                    }
+                    Some(MemoryCell::PendingRead) => {
+                        ui.label("Loading...");
+                        return;
+                    }
+                    None => {
+                        self.command_queue.push_back(Command::ReadTrash);
+                        return;
+                    }
+                };
+
+                if trash.entries.is_empty() {
+                    ui.weak(t!("trash_is_empty"));
+                    return;
+                }
+
+                ui.menu_button(
+                    format!("{} {}", phosphor_icons::TRASH, t!("empty_trash")),
+                    |ui| {
+                        if ui
+                            .button(format!(
+                                "{} {}",
+                                phosphor_icons::X,
+                                t!("delete_permanently")
+                            ))
+                            .clicked()
+                        {
+                            self.command_queue.push_back(Command::EmptyTrash);
+                            ui.close();
+                        }
+                    },
+                );
+                ui.separator();
+
+                for entry in &trash.entries {
+                    let mut selected =
+                        self.ui_state.selected_trash_entry.as_ref() == Some(&entry.name);
+                    let mut restore = false;
+                    let mut purge = false;
+                    trash_label_ui(ui, &mut selected, &mut restore, &mut purge, entry);
+                    if selected {
+                        self.ui_state.selected_trash_entry = Some(entry.name.clone());
+                    }
+                    if restore {
+                        self.command_queue
+                            .push_back(Command::RestoreFromTrash(entry.name.clone()));
+                    }
+                    if purge {
+                        self.command_queue
+                            .push_back(Command::DeleteFromTrash(entry.name.clone()));
+                    }
+                }
+
+                let selected_entry = trash
+                    .entries
+                    .iter()
+                    .find(|entry| self.ui_state.selected_trash_entry.as_ref() == Some(&entry.name));
+                if let Some(entry) = selected_entry {
+                    ui.separator();
+                    ui.weak(format!(
+                        "{} {}",
+                        t!("original_location"),
+                        entry.deleted.origin_path.to_string_lossy()
+                    ));
+                    ui.weak(format!(
+                        "{} {}",
+                        t!("deleted"),
+                        format_date_time(&entry.deleted.deletion_time)
+                    ));
+                }
+            });
+    }
+
+    fn merge_ui_windowed(&mut self, ctx: &Context) {
+        let Some(merge_view) = &mut self.ui_state.merge_view else {
+            return;
+        };
+        let mut open = true;
+        let mut close = false;
+        Window::new(t!("merge"))
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.weak(merge_view.path.to_string_lossy());
+                if merge_view.has_conflicts {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!("{} {}", phosphor_icons::WARNING, t!("merge_has_conflicts")),
+                    );
+                }
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(format!("{} {}", phosphor_icons::CHECK, t!("apply_merge")))
+                        .clicked()
+                    {
+                        self.command_queue.push_back(Command::ResolveConflict {
+                            path: merge_view.path.clone(),
+                            resolution: ConflictResolution::Merged(std::mem::take(
+                                &mut merge_view.text,
+                            )),
+                        });
+                        close = true;
+                    }
+                    if ui.button(t!("cancel")).clicked() {
+                        close = true;
+                    }
+                });
+                ui.separator();
+                ScrollArea::vertical().show(ui, |ui| {
+                    TextEdit::multiline(&mut merge_view.text)
+                        .code_editor()
+                        .desired_width(f32::INFINITY)
+                        .ui(ui);
+                });
+            });
+        if !open || close {
+            self.ui_state.merge_view = None;
+        }
+    }
+
+    /// Links a pending rename changes, rewritten along with it once confirmed
+    /// Close request cancelled while notes have unsaved edits. With autosave they are saved
+    /// right away and the prompt only shows up when that fails
+    fn hold_close_for_unsaved_notes(&mut self, ctx: &Context) {
+        if !ctx.input(|i| i.viewport().close_requested())
+            || self.ui_state.close_confirmed
+            || !self.app.has_unsaved_notes()
+        {
+            return;
+        }
+        if self.app.autosave() {
+            match self.app.flush_saves() {
+                Ok(()) => return,
+                Err(err) => self
+                    .app
+                    .report_error(format!("{}: {err}", t!("write_error"))),
+            }
+        }
+        ctx.send_viewport_cmd(ViewportCommand::CancelClose);
+        self.ui_state.close_prompt = true;
+    }
+
+    fn close_prompt_ui_windowed(&mut self, ctx: &Context) {
+        if !self.ui_state.close_prompt {
+            return;
+        }
+        let base_path = self.app.base_dir_path().to_path_buf();
+        let scratch_pad_path = self.app.scratch_pad_path().to_path_buf();
+        Window::new(t!("unsaved_changes"))
+            .collapsible(false)
+            .resizable(false)
+            .anchor(Align2::CENTER_CENTER, [0., 0.])
+            .show(ctx, |ui| {
+                ui.label(t!("save_before_closing"));
+                for path in self.app.unsaved_notes() {
+                    let name = if path == scratch_pad_path {
+                        SCRATCH_PAD_NAME.to_owned()
+                    } else {
+                        path.strip_prefix(&base_path)
+                            .unwrap_or(&path)
+                            .to_string_lossy()
+                            .into_owned()
+                    };
+                    ui.weak(name);
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .button(format!("{} {}", phosphor_icons::FLOPPY_DISK, t!("save")))
+                        .clicked()
+                    {
+                        match self.app.flush_saves() {
+                            Ok(()) => {
+                                self.ui_state.close_confirmed = true;
+                                ctx.send_viewport_cmd(ViewportCommand::Close);
+                            }
+                            Err(err) => self
+                                .app
+                                .report_error(format!("{}: {err}", t!("write_error"))),
+                        }
+                    }
+                    if ui
+                        .button(format!("{} {}", phosphor_icons::TRASH, t!("discard")))
+                        .clicked()
+                    {
+                        self.ui_state.close_confirmed = true;
+                        self.ui_state.discard_unsaved = true;
+                        ctx.send_viewport_cmd(ViewportCommand::Close);
+                    }
+                    if ui.button(t!("cancel")).clicked() {
+                        self.ui_state.close_prompt = false;
+                    }
+                });
+            });
+    }
+
+    fn rename_ui_windowed(&mut self, ctx: &Context) {
+        let Some((from, to)) = &self.ui_state.pending_rename else {
+            return;
+        };
+        let base_path = self.app.base_dir_path();
+        let relative = |path: &Path| {
+            path.strip_prefix(base_path)
+                .unwrap_or(path)
+                .to_string_lossy()
+                .into_owned()
+        };
+        let mut open = true;
+        Window::new(t!("rename"))
+            .collapsible(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} {} {}",
+                    relative(from),
+                    phosphor_icons::ARROW_RIGHT,
+                    relative(to)
+                ));
+                let rename = Command::Rename {
+                    from: from.clone(),
+                    to: to.clone(),
+                };
+                let mut plan_ready = false;
+                match self.app.get_rename_plan(from) {
+                    Some(MemoryCell::Value(plan)) if plan.notes.is_empty() => {
+                        self.command_queue.push_back(Command::RenameWithoutLinks {
+                            from: from.clone(),
+                            to: to.clone(),
+                        });
+                    }
+                    Some(MemoryCell::Value(plan)) => {
+                        plan_ready = true;
+                        ui.label(t!("links_to_update", count = plan.link_count()));
+                        ScrollArea::vertical().max_height(300.).show(ui, |ui| {
+                            for note in &plan.notes {
+                                ui.separator();
+                                ui.strong(relative(&note.path));
+                                for edit in &note.edits {
+                                    ui.horizontal_wrapped(|ui| {
+                                        ui.weak(format!(":{}", edit.line_number + 1));
+                                        ui.label(RichText::new(&edit.old).strikethrough());
+                                        ui.label(phosphor_icons::ARROW_RIGHT);
+                                        ui.label(RichText::new(&edit.new).code());
+                                    });
+                                }
+                            }
+                        });
+                    }
+                    Some(MemoryCell::ReadError(err) | MemoryCell::ValueWriteError(_, err)) => {
+                        if io_error_ui(ui, &t!("read_error"), err) {
+                            self.command_queue.push_back(rename);
+                        }
+                    }
+                    Some(MemoryCell::PendingRead) => {
+                        ui.weak(t!("finding_links"));
+                    }
+                    None => match self.app.get_index() {
+                        Some(MemoryCell::PendingRead) => {
+                            ui.weak(t!("indexing"));
+                        }
+                        // Index is ready, or links cannot be found and it is renamed as is
+                        _ => self.command_queue.push_back(rename),
+                    },
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            plan_ready,
+                            Button::new(format!(
+                                "{} {}",
+                                phosphor_icons::CHECK,
+                                t!("rename_and_update_links")
+                            )),
+                        )
+                        .clicked()
+                    {
+                        self.command_queue
+                            .push_back(Command::ApplyRenamePlan(from.clone()));
+                    }
+                    if ui.button(t!("rename_only")).clicked() {
+                        self.command_queue.push_back(Command::RenameWithoutLinks {
+                            from: from.clone(),
+                            to: to.clone(),
+                        });
+                    }
+                    if ui.button(t!("cancel")).clicked() {
+                        self.command_queue
+                            .push_back(Command::CancelRename(from.clone()));
+                    }
+                });
+            });
+        if !open {
+            self.command_queue
+                .push_back(Command::CancelRename(from.clone()));
+        }
+    }
+
+    /// Committed versions of the current note, the selected one is compared to the current text
+    fn history_ui_windowed(&mut self, ctx: &Context) {
+        if !self.app.git_history() {
+            return;
+        }
+        Window::new(t!("history"))
+            .collapsible(true)
+            .open(&mut self.ui_state.history)
+            .show(ctx, |ui| {
+                let note_path = self.app.current_note_path().to_path_buf();
+                let revisions = match self.app.get_note_history(&note_path) {
+                    Some(MemoryCell::Value(revisions)) => revisions,
+                    Some(MemoryCell::ReadError(err) | MemoryCell::ValueWriteError(_, err)) => {
+                        if io_error_ui(ui, &t!("read_error"), err) {
+                            self.command_queue
+                                .push_back(Command::ReloadHistory(note_path));
+                        }
+                        return;
+                    }
+                    Some(MemoryCell::PendingRead) => {
+                        ui.label("Loading...");
+                        return;
+                    }
+                    None => {
+                        self.command_queue
+                            .push_back(Command::ReadHistory(note_path));
+                        return;
+                    }
+                };
+                if revisions.is_empty() {
+                    ui.weak(t!("no_revisions"));
+                    return;
+                }
+
+                ScrollArea::vertical()
+                    .id_salt("revisions")
+                    .max_height(ui.text_style_height(&TextStyle::Body) * 8.)
+                    .show(ui, |ui| {
+                        for revision in revisions {
+                            let selected =
+                                self.ui_state.selected_revision.as_ref() == Some(&revision.id);
+                            if ui
+                                .add(Button::selectable(
+                                    selected,
+                                    format!(
+                                        "{} {}",
+                                        format_date_time(&revision.time),
+                                        revision.message
+                                    ),
+                                ))
+                                .on_hover_text(&revision.id)
+                                .clicked()
+                            {
+                                self.ui_state.selected_revision = Some(revision.id.clone());
+                            }
+                        }
+                    });
+
+                let selected = revisions.iter().find(|revision| {
+                    self.ui_state.selected_revision.as_ref() == Some(&revision.id)
+                });
+                let (Some(revision), Some(note)) = (selected, self.app.get_note(&note_path)) else {
+                    return;
+                };
+                ui.separator();
+                let current_source = note.data.to_source();
+                let stale = self.ui_state.history_diff.as_ref().is_none_or(|diff| {
+                    diff.revision_id != revision.id || diff.current_source != current_source
+                });
+                if stale {
+                    self.ui_state.history_diff = Some(HistoryDiff {
+                        lines: history::diff(&revision.source, &current_source),
+                        revision_id: revision.id.clone(),
+                        current_source,
+                    });
+                }
+                let Some(diff) = &self.ui_state.history_diff else {
+                    return;
+                };
+                if diff.lines.is_empty() {
+                    ui.weak(t!("same_as_current"));
+                    return;
+                }
+                if ui
+                    .button(format!(
+                        "{} {}",
+                        phosphor_icons::ARROW_CCW,
+                        t!("restore_revision")
+                    ))
+                    .clicked()
+                {
+                    self.command_queue.push_back(Command::RestoreRevision {
+                        path: note_path,
+                        source: revision.source.clone(),
+                    });
+                }
+                ScrollArea::vertical()
+                    .id_salt("revision_diff")
+                    .show(ui, |ui| diff_ui(ui, &diff.lines));
+            });
+    }
+
+    /// Snapshots of the current note, the selected one is shown read only
+    fn versions_ui_windowed(&mut self, ctx: &Context) {
+        if !self.app.snapshots_enabled() {
+            return;
+        }
+        Window::new(t!("versions"))
+            .collapsible(true)
+            .open(&mut self.ui_state.versions)
+            .show(ctx, |ui| {
+                let note_path = self.app.current_note_path().to_path_buf();
+                let snapshots = match self.app.get_note_snapshots(&note_path) {
+                    Some(MemoryCell::Value(snapshots)) => snapshots,
+                    Some(MemoryCell::ReadError(err) | MemoryCell::ValueWriteError(_, err)) => {
+                        if io_error_ui(ui, &t!("read_error"), err) {
+                            self.command_queue
+                                .push_back(Command::ReloadSnapshots(note_path));
+                        }
+                        return;
+                    }
+                    Some(MemoryCell::PendingRead) => {
+                        ui.label("Loading...");
+                        return;
+                    }
+                    None => {
+                        self.command_queue
+                            .push_back(Command::ReadSnapshots(note_path));
+                        return;
+                    }
+                };
+                if snapshots.is_empty() {
+                    ui.weak(t!("no_versions"));
+                    return;
+                }
+
+                ScrollArea::vertical()
+                    .id_salt("snapshots")
+                    .max_height(ui.text_style_height(&TextStyle::Body) * 8.)
+                    .show(ui, |ui| {
+                        for snapshot in snapshots {
+                            ui.horizontal(|ui| {
+                                let selected =
+                                    self.ui_state.selected_snapshot.as_ref() == Some(&snapshot.id);
+                                if ui
+                                    .add(Button::selectable(
+                                        selected,
+                                        format_date_time(&snapshot.time),
+                                    ))
+                                    .clicked()
+                                {
+                                    self.ui_state.selected_snapshot = Some(snapshot.id.clone());
+                                }
+                                if ui
+                                    .small_button(phosphor_icons::ARROW_CCW)
+                                    .on_hover_text(t!("restore_revision"))
+                                    .clicked()
+                                {
+                                    self.command_queue.push_back(Command::RestoreRevision {
+                                        path: note_path.clone(),
+                                        source: snapshot.source.clone(),
+                                    });
+                                }
+                            });
+                        }
+                    });
+
+                let Some(snapshot) = snapshots.iter().find(|snapshot| {
+                    self.ui_state.selected_snapshot.as_ref() == Some(&snapshot.id)
+                }) else {
+                    return;
+                };
+                ui.separator();
+                ScrollArea::vertical()
+                    .id_salt("snapshot_text")
+                    .show(ui, |ui| {
+                        TextEdit::multiline(&mut snapshot.source.as_str())
+                            .code_editor()
+                            .desired_width(f32::INFINITY)
+                            .ui(ui);
+                    });
+            });
+    }
+
+    fn backlinks_ui_windowed(&mut self, ctx: &Context) {
+        let note_path = self.app.current_note_path().to_path_buf();
+        Window::new(t!("backlinks"))
+            .collapsible(true)
+            .open(&mut self.ui_state.backlinks)
+            .show(ctx, |ui| {
+                let index = match self.app.get_index() {
+                    Some(MemoryCell::Value(index)) => &index.links,
+                    Some(MemoryCell::ReadError(err) | MemoryCell::ValueWriteError(_, err)) => {
+                        if io_error_ui(ui, &t!("read_error"), err) {
+                            self.command_queue.push_back(Command::RebuildIndex);
+                        }
+                        return;
+                    }
+                    Some(MemoryCell::PendingRead) => {
+                        ui.weak(t!("indexing"));
+                        return;
+                    }
+                    None => {
+                        self.command_queue.push_back(Command::BuildIndex);
+                        return;
+                    }
+                };
+
+                let results = &mut self.ui_state.backlink_results;
+                if results.path != note_path || results.generation != index.generation() {
+                    *results = BacklinkResults {
+                        hits: index.backlinks(self.app.base_dir_path(), &note_path),
+                        path: note_path,
+                        generation: index.generation(),
+                    };
+                }
+                if results.hits.is_empty() {
+                    ui.weak(t!("no_backlinks"));
+                    return;
+                }
+
+                ScrollArea::vertical().show(ui, |ui| {
+                    for hit in &results.hits {
+                        ui.separator();
+                        if search_hit_ui(&self.app, ui, hit) {
+                            self.command_queue.push_back(Command::JumpToSearchHit {
+                                path: hit.path.clone(),
+                                offset: hit.line_start,
+                            });
+                        }
+                    }
+                });
+            });
+    }
+
+    /// Notes and links between them, filtered by folder or tag
+    fn graph_ui_windowed(&mut self, ctx: &Context) {
+        Window::new(t!("graph"))
+            .collapsible(true)
+            .resizable(true)
+            .default_size([480., 360.])
+            .open(&mut self.ui_state.graph)
+            .show(ctx, |ui| {
+                let index = match self.app.get_index() {
+                    Some(MemoryCell::Value(index)) => index,
+                    Some(MemoryCell::ReadError(err) | MemoryCell::ValueWriteError(_, err)) => {
+                        if io_error_ui(ui, &t!("read_error"), err) {
+                            self.command_queue.push_back(Command::RebuildIndex);
+                        }
+                        return;
+                    }
+                    Some(MemoryCell::PendingRead) => {
+                        ui.weak(t!("indexing"));
+                        return;
+                    }
+                    None => {
+                        self.command_queue.push_back(Command::BuildIndex);
+                        return;
+                    }
+                };
+                let layout = match self.app.get_graph() {
+                    Some(MemoryCell::Value(layout)) => {
+                        if layout.generation != index.links.generation() {
+                            self.command_queue.push_back(Command::LayoutGraph);
+                        }
+                        layout
+                    }
+                    Some(MemoryCell::ReadError(err) | MemoryCell::ValueWriteError(_, err)) => {
+                        if io_error_ui(ui, &t!("read_error"), err) {
+                            self.command_queue.push_back(Command::LayoutGraph);
+                        }
+                        return;
+                    }
+                    Some(MemoryCell::PendingRead) => {
+                        ui.weak(t!("laying_out_graph"));
+                        return;
+                    }
+                    None => {
+                        self.command_queue.push_back(Command::LayoutGraph);
+                        return;
+                    }
+                };
+
+                let base_path = self.app.base_dir_path();
+                let current = self.app.current_note_path();
+                let view = &mut self.ui_state.graph_view;
+                ui.horizontal(|ui| {
+                    let folders = layout
+                        .graph
+                        .nodes
+                        .iter()
+                        .filter_map(|path| path.parent()?.strip_prefix(base_path).ok())
+                        .filter(|folder| !folder.as_os_str().is_empty())
+                        .collect::<BTreeSet<_>>();
+                    let all_folders = t!("all_folders").into_owned();
+                    ComboBox::from_id_salt("graph_folder")
+                        .selected_text(view.folder.as_ref().map_or(all_folders.clone(), |folder| {
+                            folder.to_string_lossy().into_owned()
+                        }))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut view.folder, None, all_folders);
+                            for folder in folders {
+                                ui.selectable_value(
+                                    &mut view.folder,
+                                    Some(folder.to_path_buf()),
+                                    folder.to_string_lossy(),
+                                );
+                            }
+                        });
+
+                    let mut tags = Vec::new();
+                    all_tags(&index.tags.tree(), &mut tags);
+                    let all_tags = t!("all_tags").into_owned();
+                    ComboBox::from_id_salt("graph_tag")
+                        .selected_text(view.tag.clone().unwrap_or(all_tags.clone()))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut view.tag, None, all_tags);
+                            for tag in tags {
+                                ui.selectable_value(&mut view.tag, Some(tag.clone()), tag);
+                            }
+                        });
+
+                    if ui
+                        .button(phosphor_icons::CROSSHAIR)
+                        .on_hover_text(t!("show_current_note"))
+                        .clicked()
+                    {
+                        view.center_on(layout, current);
+                    }
+                });
+
+                let tagged = view.tag.as_ref().map(|tag| {
+                    index
+                        .tags
+                        .notes_with_all(std::slice::from_ref(tag))
+                        .into_iter()
+                        .collect::<HashSet<_>>()
+                });
+                let folder = view.folder.as_ref().map(|folder| base_path.join(folder));
+                let visible = layout
+                    .graph
+                    .nodes
+                    .iter()
+                    .map(|path| {
+                        path != self.app.scratch_pad_path()
+                            && folder
+                                .as_ref()
+                                .is_none_or(|folder| path.starts_with(folder))
+                            && tagged.as_ref().is_none_or(|tagged| tagged.contains(path))
+                    })
+                    .collect::<Vec<_>>();
+                if let Some(path) = graph::graph_ui(ui, view, layout, &visible, current) {
+                    self.command_queue
+                        .push_back(Command::ReadAndSelectNote(path));
+                }
+            });
+    }
+
+    fn search_ui_windowed(&mut self, ctx: &Context) {
+        Window::new(t!("search"))
+            .collapsible(true)
+            .open(&mut self.ui_state.search)
+            .show(ctx, |ui| {
+                let query_edit = TextEdit::singleline(&mut self.ui_state.search_query)
+                    .hint_text(format!(
+                        "{} {}",
+                        phosphor_icons::MAGNIFYING_GLASS,
+                        t!("search_notes")
+                    ))
+                    .desired_width(f32::INFINITY)
+                    .ui(ui);
+                if ui.memory(|memory| memory.focused().is_none()) {
+                    query_edit.request_focus();
+                }
+
+                let index = match self.app.get_index() {
+                    Some(MemoryCell::Value(index)) => &index.search,
+                    Some(MemoryCell::ReadError(err) | MemoryCell::ValueWriteError(_, err)) => {
+                        if io_error_ui(ui, &t!("read_error"), err) {
+                            self.command_queue.push_back(Command::RebuildIndex);
+                        }
+                        return;
+                    }
+                    Some(MemoryCell::PendingRead) => {
+                        ui.weak(t!("indexing"));
+                        return;
+                    }
+                    None => {
+                        self.command_queue.push_back(Command::BuildIndex);
+                        return;
+                    }
+                };
+
+                let query = self.ui_state.search_query.trim();
+                if query.is_empty() {
+                    return;
+                }
+                let results = &mut self.ui_state.search_results;
+                if results.query != query || results.generation != index.generation() {
+                    *results = SearchResults {
+                        query: query.to_owned(),
+                        generation: index.generation(),
+                        hits: index.search(query, SEARCH_RESULTS_LIMIT),
+                    };
+                }
+                if results.hits.is_empty() {
+                    ui.weak(t!("no_results"));
+                    return;
+                }
+
+                ScrollArea::vertical().show(ui, |ui| {
+                    for hit in &results.hits {
+                        ui.separator();
+                        if search_hit_ui(&self.app, ui, hit) {
+                            self.command_queue.push_back(Command::JumpToSearchHit {
+                                path: hit.path.clone(),
+                                offset: hit.line_start,
+                            });
+                        }
+                    }
+                });
+            });
+    }
+}
+
+/// Not cached, locale can change at runtime
+fn date_time_fmt() -> String {
+    format!("%d.%m.%Y {} %H:%M", t!("at"))
+}
+
+fn format_date_time(date: &DateTime<Utc>) -> String {
+    to_local_date_time(date)
+        .format(&date_time_fmt())
+        .to_string()
+}
+
+/// Note name with its location and snippet with matched words highlighted, returns whether clicked
+fn search_hit_ui(app: &NonBlockingApplication, ui: &mut Ui, hit: &SearchHit) -> bool {
+    let (icon, name) = if hit.path == app.scratch_pad_path() {
+        (SCRATCH_PAD_ICON, SCRATCH_PAD_NAME.to_owned())
+    } else {
+        let name = hit
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        (app.note_icon(&hit.path), name)
+    };
+    let location = hit
+        .path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(app.base_dir_path()).ok())
+        .map(|parent| parent.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut clicked = false;
+    ui.horizontal(|ui| {
+        clicked |= Button::new(format!("{icon} {name}"))
+            .frame(false)
+            .ui(ui)
+            .clicked();
+        ui.weak(format!("{location} :{}", hit.line_number + 1));
+    });
+    clicked |= Label::new(snippet_layout_job(ui, hit))
+        .sense(Sense::click())
+        .selectable(false)
+        .ui(ui)
+        .clicked();
+    clicked
+}
+
+fn snippet_layout_job(ui: &Ui, hit: &SearchHit) -> LayoutJob {
+    let font_id = TextStyle::Body.resolve(ui.style());
+    let normal = TextFormat::simple(font_id.clone(), ui.visuals().weak_text_color());
+    let highlighted = TextFormat {
+        background: ui.visuals().selection.bg_fill,
+        ..TextFormat::simple(font_id, ui.visuals().strong_text_color())
+    };
+
+    let mut job = LayoutJob::default();
+    let mut offset = 0;
+    for range in &hit.highlights {
+        job.append(&hit.snippet[offset..range.start], 0., normal.clone());
+        job.append(&hit.snippet[range.clone()], 0., highlighted.clone());
+        offset = range.end;
+    }
+    job.append(&hit.snippet[offset..], 0., normal);
+    job
+}
+
+/// Note text with wiki links in link color
+fn wiki_link_layout_job(ui: &Ui, text: &str, wrap_width: f32) -> LayoutJob {
+    let font_id = TextStyle::Body.resolve(ui.style());
+    let normal = TextFormat::simple(font_id.clone(), ui.visuals().widgets.inactive.text_color());
+    let link = TextFormat::simple(font_id, ui.visuals().hyperlink_color);
+
+    let mut job = LayoutJob::default();
+    job.wrap.max_width = wrap_width;
+    let mut offset = 0;
+    for wiki_link in links::wiki_links(text) {
+        job.append(&text[offset..wiki_link.range.start], 0., normal.clone());
+        job.append(&text[wiki_link.range.clone()], 0., link.clone());
+        offset = wiki_link.range.end;
+    }
+    job.append(&text[offset..], 0., normal);
+    job
+}
+
+/// Suggested notes below the editor cursor, returns target of the clicked one
+fn link_suggestions_ui(
+    ui: &Ui,
+    cursor_rect: egui::Rect,
+    suggestions: &[LinkSuggestion],
+    selected: usize,
+) -> Option<String> {
+    let anchor = PopupAnchor::Position(cursor_rect.left_bottom());
+    Popup::new(
+        Id::new("link_suggestions"),
+        ui.ctx().clone(),
+        anchor,
+        ui.layer_id(),
+    )
+    .open(true)
+    .show(|ui| {
+        let mut picked = None;
+        for (index, suggestion) in suggestions.iter().enumerate() {
+            let text = format!("{} {}", phosphor_icons::LINK, suggestion.target);
+            if Button::selectable(index == selected, text)
+                .ui(ui)
+                .on_hover_text(&suggestion.location)
+                .clicked()
+            {
+                picked = Some(suggestion.target.clone());
+            }
+        }
+        picked
+    })
+    .and_then(|response| response.inner)
+}
+
+/// Lines of a revision diff, removed ones are what restoring brings back
+fn diff_ui(ui: &mut Ui, lines: &[DiffLine]) {
+    let added_color = egui::Color32::from_rgb(0x3f, 0xa3, 0x4d);
+    for line in lines {
+        let text = match line {
+            DiffLine::Context(text) => RichText::new(format!("  {text}")).weak(),
+            DiffLine::Removed(text) => {
+                RichText::new(format!("- {text}")).color(ui.visuals().error_fg_color)
+            }
+            DiffLine::Added(text) => RichText::new(format!("+ {text}")).color(added_color),
+            DiffLine::Gap => RichText::new("  …").weak(),
+        };
+        Label::new(text.monospace()).wrap().ui(ui);
+    }
+}
+
+/// Searchable grid of all icons, returns picked glyph
+fn icon_picker_ui(ui: &mut Ui, search: &mut String) -> Option<&'static str> {
+    const COLUMNS: usize = 8;
+
+    let mut picked = None;
+    ui.horizontal(|ui| {
+        TextEdit::singleline(search)
+            .hint_text(t!("search_icons"))
+            .desired_width(ui.spacing().interact_size.x * 3.)
+            .ui(ui)
+            .request_focus();
+        if ui
+            .button(DEFAULT_ICON)
+            .on_hover_text(t!("default_icon"))
+            .clicked()
+        {
+            picked = Some(DEFAULT_ICON);
+        }
+    });
+
+    let icons = phosphor_icons::search(search).collect::<Vec<_>>();
+    let cell_size = ui.text_style_height(&TextStyle::Heading) + ui.spacing().button_padding.y * 2.;
+    ScrollArea::vertical().max_height(cell_size * 8.).show_rows(
+        ui,
+        cell_size,
+        icons.len().div_ceil(COLUMNS),
+        |ui, rows| {
+            for row in rows {
+                ui.horizontal(|ui| {
+                    for icon in icons.iter().skip(row * COLUMNS).take(COLUMNS) {
+                        if Button::new(RichText::new(icon.glyph).heading())
+                            .min_size(egui::vec2(cell_size, cell_size))
+                            .ui(ui)
+                            .on_hover_text(icon.name)
+                            .clicked()
+                        {
+                            picked = Some(icon.glyph);
+                        }
+                    }
+                });
+            }
+        },
+    );
+    picked
+}
+
+/// Error message with retry button, returns whether retry was clicked
+/// Prompt to pick a version of note changed on disk while it had unsaved edits
+fn conflict_ui(ui: &mut Ui, note_path: &Path, theirs_exists: bool) -> Option<Command> {
+    let mut command = None;
+    let resolve = |resolution| Command::ResolveConflict {
+        path: note_path.to_path_buf(),
+        resolution,
+    };
+    ui.horizontal_wrapped(|ui| {
+        let message = if theirs_exists {
+            t!("changed_on_disk")
+        } else {
+            t!("deleted_on_disk")
+        };
+        ui.colored_label(
+            ui.visuals().warn_fg_color,
+            format!("{} {}", phosphor_icons::WARNING, message),
+        );
+        if ui.small_button(t!("keep_mine")).clicked() {
+            command = Some(resolve(ConflictResolution::KeepMine));
+        }
+        if ui.small_button(t!("take_theirs")).clicked() {
+            command = Some(resolve(ConflictResolution::TakeTheirs));
+        }
+        if theirs_exists && ui.small_button(t!("merge")).clicked() {
+            command = Some(Command::OpenMergeView(note_path.to_path_buf()));
+        }
+    });
+    command
+}
+
+/// Tags of the trees and their nested tags, parents first
+fn all_tags(trees: &[TagTree], tags: &mut Vec<String>) {
+    for tree in trees {
+        tags.push(tree.tag.clone());
+        all_tags(&tree.children, tags);
+    }
+}
+
+fn io_error_ui(ui: &mut Ui, message: &str, err: &io::Error) -> bool {
+    let mut retry = false;
+    ui.horizontal_wrapped(|ui| {
+        ui.colored_label(
+            ui.visuals().error_fg_color,
+            format!("{} {}: {}", phosphor_icons::WARNING, message, err),
+        );
+        retry = ui
+            .small_button(format!("{} {}", phosphor_icons::ARROW_CW, t!("retry")))
+            .clicked();
+    });
+    retry
+}
+
+fn trash_label_ui(
+    ui: &mut Ui,
+    selected: &mut bool,
+    restore: &mut bool,
+    purge: &mut bool,
+    trashed: &TrashEntry,
+) -> egui::Response {
+    let icon = if trashed.is_dir {
+        phosphor_icons::FOLDER
+    } else {
+        DEFAULT_ICON
+    };
+    let label = ui.add(Button::selectable(
+        *selected,
+        format!("{} {}", icon, trashed.name),
+    ));
+    label.context_menu(|ui| {
+        if ui
+            .button(format!(
+                "{} {}",
+                phosphor_icons::ARROW_CCW,
+                t!("restore_from_trash")
+            ))
+            .clicked()
+        {
+            *restore = true;
+            ui.close();
+        }
+        if ui
+            .button(format!(
+                "{} {}",
+                phosphor_icons::X,
+                t!("delete_permanently")
+            ))
+            .clicked()
+        {
+            *purge = true;
+            ui.close();
+        }
+    });
+    if label.clicked() {
+        *selected = true
+    }
+    label
+}
+
+fn explorer_note_label_ui(
+    app: &NonBlockingApplication,
+    ui: &mut Ui,
+    selected: bool,
+    note_name_in_dir: &str,
+    note_path: &Path,
+) -> (egui::Response, VecDeque<Command>) {
+    let mut commands = VecDeque::new();
+    let error = app.get_note_cell(note_path).and_then(MemoryCell::error);
+    let icon = if error.is_some() {
+        phosphor_icons::WARNING
+    } else {
+        app.note_icon(note_path)
+    };
+    let mut label = ui.add(note_label(selected, icon, note_name_in_dir));
+    if let Some(err) = error {
+        label = label.on_hover_text(err.to_string());
+    }
+    label.context_menu(|ui| {
+        if ui
+            .button(format!("{} {}", phosphor_icons::PENCIL_LINE, t!("rename")))
+            .clicked()
+        {
+            commands.push_back(Command::StartRename(note_path.to_path_buf()));
+            ui.close();
+        }
+        ui.menu_button(
+            format!("{} {}", phosphor_icons::FOLDER, t!("move_to")),
+            |ui| {
+                commands.extend(move_to_menu_ui(app, ui, note_path, app.base_dir_path()));
+            },
+        );
+        if ui
+            .button(format!("{} {}", phosphor_icons::TRASH, t!("trash_note")))
+            .clicked()
+        {
+            commands.push_back(Command::DeleteNote(note_path.to_path_buf()));
+            ui.close();
+        }
+    });
+    if label.clicked() && !selected {
+        commands.push_back(Command::ReadAndSelectNote(note_path.to_path_buf()));
+    }
+    if label.double_clicked() {
+        commands.push_back(Command::PinTab(note_path.to_path_buf()));
+    }
+    (label, commands)
+}
+
+/// Folder picker listing `dir_path` and its subfolders as nested menus
+fn move_to_menu_ui(
+    app: &NonBlockingApplication,
+    ui: &mut Ui,
+    moved_path: &Path,
+    dir_path: &Path,
+) -> Option<Command> {
+    let mut command = None;
+    let is_current_parent = moved_path.parent() == Some(dir_path);
+    if ui
+        .add_enabled(
+            !is_current_parent,
+            Button::new(format!("{} {}", phosphor_icons::PLUS, t!("move_here"))),
+        )
+        .clicked()
+    {
+        if let Some(name) = moved_path.file_name() {
+            command = Some(Command::Rename {
+                from: moved_path.to_path_buf(),
+                to: dir_path.join(name),
+            });
+        }
+        ui.close();
+    }
+
+    let Some(dir) = app.get_dir(dir_path) else {
+        command.get_or_insert(Command::ReadDir(dir_path.to_path_buf()));
+        ui.weak("Loading...");
+        return command;
+    };
+
+    let mut sub_folders = dir
+        .data
+        .entries
+        .iter()
+        .filter_map(|(name, ent)| match ent {
+            DirEntry::Dir(path) if !name.starts_with('.') && path != moved_path => {
+                Some((name.as_str(), path.as_path()))
+            }
+            _ => None,
+        })
+        .collect::<Vec<(&str, &Path)>>();
+    sub_folders.sort_by_key(|(name, _path)| name.to_owned());
+    if !sub_folders.is_empty() {
+        ui.separator();
+    }
+    for (name, path) in sub_folders {
+        ui.menu_button(format!("{} {}", phosphor_icons::FOLDER, name), |ui| {
+            if let Some(sub_command) = move_to_menu_ui(app, ui, moved_path, path) {
+                command = Some(sub_command);
+            }
+        });
+    }
+    command
+}
+
+/// Tab of open note, preview tab in italics. Dropped on another tab it takes its place
+fn tab_ui(
+    app: &NonBlockingApplication,
+    ui: &mut Ui,
+    index: usize,
+    tab: &Tab,
+    selected: bool,
+) -> Option<Command> {
+    let mut command = None;
+    let (icon, name) = if tab.path == app.scratch_pad_path() {
+        (SCRATCH_PAD_ICON, SCRATCH_PAD_NAME.to_owned())
+    } else {
+        let name = tab
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        (app.note_icon(&tab.path), name)
+    };
+    let mut label_text = RichText::new(format!("{icon} {name}"));
+    if selected {
+        label_text = label_text.strong();
+    }
+    if !tab.pinned {
+        label_text = label_text.italics();
+    }
+
+    let default_item_spacing = ui.spacing().item_spacing.x;
+    ui.spacing_mut().item_spacing.x = 0.;
+    let label = Button::selectable(selected, label_text)
+        .sense(Sense::click_and_drag())
+        .ui(ui)
+        .on_hover_text(tab.path.to_string_lossy());
+    label.dnd_set_drag_payload(index);
+    if let Some(from) = label.dnd_release_payload::<usize>() {
+        command = Some(Command::MoveTab {
+            from: *from,
+            to: index,
+        });
+    }
+    if label.clicked() && !selected {
+        command = Some(Command::ReadAndSelectNote(tab.path.clone()));
+    }
+    if label.double_clicked() {
+        command = Some(Command::PinTab(tab.path.clone()));
+    }
+
+    let close_icon = if app.note_is_dirty(&tab.path) {
+        phosphor_icons::DOT
+    } else {
+        phosphor_icons::X
+    };
+    let close_button = Button::selectable(selected, close_icon)
+        .ui(ui)
+        .on_hover_text(t!("close_tab"));
+    if close_button.clicked() || label.middle_clicked() {
+        command = Some(Command::CloseTab(tab.path.clone()));
+    }
+    ui.spacing_mut().item_spacing.x = default_item_spacing;
+    command
+}
+
+fn note_label<'x>(selected: bool, icon: &str, note_name_in_dir: &str) -> Button<'x> {
+    let mut label_text = RichText::new(format!("{} {}", icon, &note_name_in_dir,));
+
+    if selected {
+        label_text = label_text.strong();
     }
 
     Button::selectable(selected, label_text)
@@ -510,6 +3014,164 @@ fn scratch_pad_label<'x>(selected: bool) -> Button<'x> {
     Button::selectable(selected, label_text)
 }
 
+/// Tag tree of the vault index, picked tags list notes having all of them
+fn explorer_tags_ui(
+    app: &NonBlockingApplication,
+    ui: &mut Ui,
+    filter: &mut TagFilter,
+) -> VecDeque<Command> {
+    let mut commands = VecDeque::new();
+    let index = match app.get_index() {
+        Some(MemoryCell::Value(index)) => index,
+        Some(MemoryCell::ReadError(err) | MemoryCell::ValueWriteError(_, err)) => {
+            if io_error_ui(ui, &t!("read_error"), err) {
+                commands.push_back(Command::RebuildIndex);
+            }
+            return commands;
+        }
+        Some(MemoryCell::PendingRead) => {
+            ui.weak(t!("indexing"));
+            return commands;
+        }
+        None => {
+            commands.push_back(Command::BuildIndex);
+            return commands;
+        }
+    };
+    if index.tags.is_empty() {
+        ui.weak(t!("no_tags"));
+        return commands;
+    }
+    for tree in index.tags.tree() {
+        tag_tree_ui(ui, &tree, filter, &mut commands);
+    }
+    if filter.selected.is_empty() {
+        return commands;
+    }
+
+    ui.separator();
+    ui.horizontal_wrapped(|ui| {
+        for tag in &filter.selected {
+            ui.weak(format!("#{tag}"));
+        }
+        if ui
+            .small_button(phosphor_icons::X)
+            .on_hover_text(t!("clear_tag_filter"))
+            .clicked()
+        {
+            filter.selected.clear();
+        }
+    });
+    let paths = index.tags.notes_with_all(&filter.selected);
+    if paths.is_empty() {
+        ui.weak(t!("no_results"));
+    }
+    for path in paths {
+        let selected = app.is_selected(&path);
+        let (icon, name) = if path == app.scratch_pad_path() {
+            (SCRATCH_PAD_ICON, SCRATCH_PAD_NAME.to_owned())
+        } else {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            (app.note_icon(&path), name)
+        };
+        let location = path
+            .strip_prefix(app.base_dir_path())
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .into_owned();
+        if ui
+            .add(note_label(selected, icon, &name))
+            .on_hover_text(location)
+            .clicked()
+        {
+            commands.push_back(Command::ReadAndSelectNote(path));
+        }
+    }
+    commands
+}
+
+fn tag_tree_ui(
+    ui: &mut Ui,
+    tree: &TagTree,
+    filter: &mut TagFilter,
+    commands: &mut VecDeque<Command>,
+) {
+    let id = ui.make_persistent_id(("tag", &tree.tag));
+    let mut header = |ui: &mut Ui, filter: &mut TagFilter| {
+        if let Some((from, name)) = &mut filter.renaming
+            && *from == tree.tag
+        {
+            let response = TextEdit::singleline(name).desired_width(120.).ui(ui);
+            if !response.has_focus() && !response.lost_focus() {
+                response.request_focus();
+            }
+            if response.lost_focus() {
+                let name = name.trim().trim_start_matches('#').to_owned();
+                if ui.input(|i| i.key_pressed(Key::Enter))
+                    && tags::is_valid_tag(&name)
+                    && name != tree.tag
+                {
+                    commands.push_back(Command::RenameTag {
+                        from: tree.tag.clone(),
+                        to: name,
+                    });
+                }
+                filter.renaming = None;
+            }
+            return;
+        }
+        let selected = filter.selected.contains(&tree.tag);
+        let label = Button::selectable(
+            selected,
+            format!("{} {} {}", phosphor_icons::HASH, tree.name, tree.count),
+        )
+        .ui(ui)
+        .on_hover_text(format!("#{}", tree.tag));
+        if label.clicked() {
+            if selected {
+                filter.selected.retain(|tag| *tag != tree.tag);
+            } else {
+                filter.selected.push(tree.tag.clone());
+            }
+        }
+        label.context_menu(|ui| {
+            if ui
+                .button(format!("{} {}", phosphor_icons::PENCIL_LINE, t!("rename")))
+                .clicked()
+            {
+                filter.renaming = Some((tree.tag.clone(), tree.tag.clone()));
+                ui.close();
+            }
+        });
+    };
+    if tree.children.is_empty() {
+        header(ui, filter);
+        return;
+    }
+    CollapsingState::load_with_default_open(ui.ctx(), id, false)
+        .show_header(ui, |ui| header(ui, filter))
+        .body(|ui| {
+            for child in &tree.children {
+                tag_tree_ui(ui, child, filter, commands);
+            }
+        });
+}
+
+fn explorer_sort_ui(ui: &mut Ui, sort: &mut ExplorerSort) {
+    for (value, label) in [
+        (ExplorerSort::Name, t!("sort_by_name")),
+        (ExplorerSort::Created, t!("sort_by_created")),
+        (ExplorerSort::Modified, t!("sort_by_modified")),
+    ] {
+        if ui.radio_value(sort, value, label).clicked() {
+            ui.close();
+        }
+    }
+}
+
 fn create_action_buttons_ui(ui: &mut Ui, dir_path: &Path) -> Option<Command> {
     let mut action = None;
     if ui
@@ -527,8 +3189,26 @@ fn create_action_buttons_ui(ui: &mut Ui, dir_path: &Path) -> Option<Command> {
     action
 }
 
-fn dir_action_buttons_ui(ui: &mut Ui, dir_path: &Path) -> Option<Command> {
+fn dir_action_buttons_ui(
+    app: &NonBlockingApplication,
+    ui: &mut Ui,
+    dir_path: &Path,
+) -> Option<Command> {
     let mut action = create_action_buttons_ui(ui, dir_path);
+    if ui
+        .button(format!("{} {}", phosphor_icons::PENCIL_LINE, t!("rename")))
+        .clicked()
+    {
+        action = Some(Command::StartRename(dir_path.to_path_buf()));
+    }
+    ui.menu_button(
+        format!("{} {}", phosphor_icons::FOLDER, t!("move_to")),
+        |ui| {
+            if let Some(move_action) = move_to_menu_ui(app, ui, dir_path, app.base_dir_path()) {
+                action = Some(move_action);
+            }
+        },
+    );
     if ui
         .button(format!("{} {}", phosphor_icons::TRASH, t!("trash_note")))
         .clicked()
@@ -538,30 +3218,81 @@ fn dir_action_buttons_ui(ui: &mut Ui, dir_path: &Path) -> Option<Command> {
     action
 }
 
+/// Name edit in place of explorer item label, commits on Enter and cancels on focus loss
+fn rename_edit_ui(ui: &mut Ui, renaming: &mut Option<RenameState>) -> Option<Command> {
+    let state = renaming.as_mut()?;
+    let response = TextEdit::singleline(&mut state.name)
+        .desired_width(f32::INFINITY)
+        .ui(ui);
+    if !state.focus_requested {
+        response.request_focus();
+        state.focus_requested = true;
+    }
+
+    if !response.lost_focus() {
+        return None;
+    }
+
+    let state = renaming.take()?;
+    let name = state.name.trim();
+    if !ui.input(|i| i.key_pressed(egui::Key::Enter)) || !is_valid_file_name(name) {
+        return None;
+    }
+    let to = state.path.with_file_name(name);
+    Some(Command::Rename {
+        from: state.path,
+        to,
+    })
+}
+
+fn is_renaming(renaming: &Option<RenameState>, path: &Path) -> bool {
+    renaming.as_ref().is_some_and(|state| state.path == path)
+}
+
 fn explorer_folder_ui(
     app: &NonBlockingApplication,
     ui: &mut Ui,
     dir_name: &str,
     dir_path: &Path,
     command_queue: &mut VecDeque<Command>,
+    renaming: &mut Option<RenameState>,
+    view: ExplorerView,
 ) {
+    if is_renaming(renaming, dir_path) {
+        ui.horizontal(|ui| {
+            ui.label(phosphor_icons::FOLDER);
+            command_queue.extend(rename_edit_ui(ui, renaming));
+        });
+        return;
+    }
+
     ui.horizontal(|ui| {
         let collapsing = CollapsingHeader::new(dir_name)
-            .id_salt(&dir_path)
-            .show(ui, |ui| {
-                if let Some(dir) = app.get_dir(dir_path) {
-                    explorer_folder_content_ui(app, ui, dir, command_queue, false);
+            .id_salt(dir_path)
+            .open(Some(view.expanded.contains(dir_path)))
+            .show(ui, |ui| match app.get_dir_cell(dir_path) {
+                Some(MemoryCell::ReadError(err)) => {
+                    let retry = io_error_ui(ui, &t!("read_error"), err);
+                    if retry {
+                        command_queue.push_back(Command::ReloadDir(dir_path.to_path_buf()));
+                    }
                 }
+                Some(cell) => {
+                    if let Some(dir) = cell.value() {
+                        explorer_folder_content_ui(app, ui, dir, command_queue, renaming, view);
+                    }
+                }
+                None => command_queue.push_back(Command::ReadDir(dir_path.to_path_buf())),
             });
         if collapsing.header_response.clicked() {
-            command_queue.push_back(Command::ReadDir(dir_path.to_path_buf()));
+            command_queue.push_back(Command::ToggleDir(dir_path.to_path_buf()));
         }
         [Some(collapsing.header_response), collapsing.body_response]
             .iter()
             .flatten()
             .for_each(|response| {
                 Popup::context_menu(response).show(|ui| {
-                    if let Some(dir_action) = dir_action_buttons_ui(ui, &dir_path) {
+                    if let Some(dir_action) = dir_action_buttons_ui(app, ui, dir_path) {
                         command_queue.push_back(dir_action);
                     }
                 });
@@ -574,7 +3305,8 @@ fn explorer_folder_content_ui(
     ui: &mut Ui,
     dir: &DataNode<Directory>,
     command_queue: &mut VecDeque<Command>,
-    show_hidden: bool,
+    renaming: &mut Option<RenameState>,
+    view: ExplorerView,
 ) {
     let mut notes = dir
         .data
@@ -582,7 +3314,7 @@ fn explorer_folder_content_ui(
         .iter()
         .filter_map(|(name, ent)| {
             if let DirEntry::File(path) = ent
-                && (show_hidden || !path.file_name().unwrap().to_string_lossy().starts_with("."))
+                && (view.show_hidden || !name.starts_with('.'))
             {
                 Some((name.as_str(), path.as_path()))
             } else {
@@ -590,10 +3322,17 @@ fn explorer_folder_content_ui(
             }
         })
         .collect::<Vec<(&str, &Path)>>();
-    notes.sort_by_key(|(name, _)| name.to_owned());
+    view.sort.sort(&mut notes, &dir.data.metadata);
     notes.into_iter().for_each(|(note_name, note_path)| {
+        if is_renaming(renaming, note_path) {
+            ui.horizontal(|ui| {
+                ui.label(app.note_icon(note_path));
+                command_queue.extend(rename_edit_ui(ui, renaming));
+            });
+            return;
+        }
         let selected = app.is_selected(note_path);
-        let (_, commands) = explorer_note_label_ui(ui, selected, note_name, note_path);
+        let (_, commands) = explorer_note_label_ui(app, ui, selected, note_name, note_path);
         command_queue.extend(commands);
     });
     let mut sub_folders = dir
@@ -601,36 +3340,197 @@ fn explorer_folder_content_ui(
         .entries
         .iter()
         .filter_map(|(name, ent)| {
-            if let DirEntry::Dir(path) = ent {
+            if let DirEntry::Dir(path) = ent
+                && (view.show_hidden || !name.starts_with('.'))
+            {
                 Some((name.as_str(), path.as_path()))
             } else {
                 None
             }
         })
         .collect::<Vec<(&str, &Path)>>();
-    sub_folders.sort_by_key(|(name, _path)| name.to_owned());
+    view.sort.sort(&mut sub_folders, &dir.data.metadata);
     sub_folders.iter().for_each(|(name, path)| {
-        explorer_folder_ui(app, ui, name, path, command_queue);
+        explorer_folder_ui(app, ui, name, path, command_queue, renaming, view);
     });
 }
 
-fn handle_command(app: &mut NonBlockingApplication, command: Command) {
+/// Open the current vault, or the default one with an error shown when it fails to open
+fn open_configured_vault(settings: &Settings) -> io::Result<NonBlockingApplication> {
+    let config = settings.notes.application_config();
+    let base_path = config.location.base_path.clone();
+    let err = match NonBlockingApplication::init(config) {
+        Ok(app) => return Ok(app),
+        Err(err) => err,
+    };
+    log::error!("Failed to open {}: {err}", base_path.display());
+    let default_base_path = LocationConfig::default_base_path()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "No home directory"))?;
+    let config = ApplicationConfig {
+        location: LocationConfig::new(&default_base_path, None),
+        ..settings.notes.application_config()
+    };
+    let mut app = NonBlockingApplication::init(config)?;
+    app.report_error(format!(
+        "{} {}: {err}",
+        t!("open_folder_failed"),
+        base_path.display()
+    ));
+    Ok(app)
+}
+
+/// Switch to the vault, remembering open tabs and expanded folders of the current one
+fn open_vault(
+    app: &mut NonBlockingApplication,
+    ui_state: &mut UiState,
+    vault: &VaultSettings,
+) -> bool {
+    let base_path = app.base_dir_path().to_path_buf();
+    let session = VaultSession {
+        last_note: app.current_note_path().to_path_buf(),
+        tabs: app.tabs().iter().cloned().collect(),
+        expanded_dirs: std::mem::take(&mut ui_state.expanded_dirs),
+    };
+    if let Err(err) = app.switch_location(vault.location.location_config()) {
+        ui_state.expanded_dirs = session.expanded_dirs;
+        let base_path = vault.location.base_path.display();
+        log::error!("Failed to open {base_path}: {err}");
+        app.report_error(format!("{} {base_path}: {err}", t!("open_folder_failed")));
+        return false;
+    }
+    ui_state.vault_sessions.insert(base_path, session);
+    ui_state.forget_notes();
+    ui_state.settings.notes.current_vault = vault.name.clone();
+    restore_vault_session(app, ui_state);
+    true
+}
+
+/// Reopen tabs and folders of the open vault, tabs of notes deleted since close
+fn restore_vault_session(app: &mut NonBlockingApplication, ui_state: &mut UiState) {
+    if let Some(session) = ui_state.vault_sessions.remove(app.base_dir_path()) {
+        ui_state.expanded_dirs = session.expanded_dirs;
+        app.restore_tabs(session.tabs, session.last_note);
+    }
+}
+
+/// Rename right away when no note links to the path, otherwise find the links it changes for
+/// the rename window first. The index is built for that when missing
+fn start_rename(
+    app: &mut NonBlockingApplication,
+    ui_state: &mut UiState,
+    from: PathBuf,
+    to: PathBuf,
+) {
+    if from == to {
+        return;
+    }
+    let links_change = match app.get_index() {
+        Some(MemoryCell::Value(index)) => !index
+            .links
+            .affected_by_rename(app.base_dir_path(), &from)
+            .is_empty(),
+        // Links cannot be found without the index
+        Some(MemoryCell::ReadError(_) | MemoryCell::ValueWriteError(..)) => false,
+        Some(MemoryCell::PendingRead) | None => {
+            app.build_index_in_background();
+            ui_state.pending_rename = Some((from, to));
+            return;
+        }
+    };
+    if links_change {
+        app.plan_rename_in_background(&from, &to);
+        ui_state.pending_rename = Some((from, to));
+    } else {
+        app.rename_in_background(&from, &to);
+        ui_state.pending_rename = None;
+    }
+}
+
+fn handle_command(app: &mut NonBlockingApplication, ui_state: &mut UiState, command: Command) {
     match command {
         Command::ReadAndSelectNote(path_buf) => {
-            app.read_note_in_background(&path_buf);
-            app.set_current_note_path(path_buf);
+            app.open_note(path_buf, false);
+        }
+        Command::PinTab(path_buf) => {
+            app.pin_tab(&path_buf);
+        }
+        Command::CloseTab(path_buf) => {
+            app.close_tab(&path_buf);
+        }
+        Command::MoveTab { from, to } => {
+            app.move_tab(from, to);
         }
         Command::ReadDir(path_buf) => {
             if !app.dir_in_memory(&path_buf) {
                 app.read_dir_in_background(&path_buf);
             }
         }
-        Command::CreateNote(path_buf) => todo!(),
-        Command::DeleteNote(path_buf) => todo!(),
-        Command::DeleteDir(path_buf) => todo!(),
-        Command::CreateNoteThenSelect(path_buf) => todo!(),
-        Command::CreateSubDir(path_buf) => todo!(),
+        Command::ReloadNote(path_buf) => {
+            app.reload_note_in_background(&path_buf);
+        }
+        Command::ReloadDir(path_buf) => {
+            app.reload_dir_in_background(&path_buf);
+        }
+        Command::DismissErrors => {
+            app.dismiss_errors();
+        }
+        Command::DeleteNote(path_buf) | Command::DeleteDir(path_buf) => {
+            app.trash_in_background(&path_buf);
+        }
+        Command::CreateNoteThenSelect(path_buf) => {
+            app.create_note_in_background(&path_buf, true);
+        }
+        Command::CreateSubDir(path_buf) => {
+            app.create_dir_in_background(&path_buf);
+        }
+        Command::StartRename(path_buf) => {
+            ui_state.renaming = Some(RenameState::new(path_buf));
+        }
+        Command::Rename { from, to } => {
+            start_rename(app, ui_state, from, to);
+        }
+        Command::ApplyRenamePlan(from) => {
+            app.apply_rename_plan_in_background(&from);
+            ui_state.pending_rename = None;
+        }
+        Command::RenameWithoutLinks { from, to } => {
+            app.discard_rename_plan(&from);
+            app.rename_in_background(&from, &to);
+            ui_state.pending_rename = None;
+        }
+        Command::CancelRename(from) => {
+            app.discard_rename_plan(&from);
+            ui_state.pending_rename = None;
+        }
+        Command::UndoRelink => {
+            app.undo_relink_in_background();
+        }
+        Command::DismissRelink => {
+            app.dismiss_last_relink();
+        }
+        Command::SetIcon { path, icon } => {
+            app.pin_tab(&path);
+            app.set_note_icon(&path, icon);
+        }
+        Command::SetTitle { path, title } => {
+            app.pin_tab(&path);
+            app.set_note_title(&path, &title);
+        }
+        Command::RenameToTitle(path) => {
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let to = app
+                .get_note(&path)
+                .and_then(|note| file_name_for_title(note.data.title()?, &name))
+                .map(|name| path.with_file_name(name));
+            if let Some(to) = to {
+                start_rename(app, ui_state, path, to);
+            }
+        }
         Command::MarkChanged(path_buf) => {
+            app.pin_tab(&path_buf);
             app.set_dirty(&path_buf);
         }
         Command::SaveNote(path_buf) => {
@@ -638,5 +3538,111 @@ fn handle_command(app: &mut NonBlockingApplication, command: Command) {
                 app.save_note_in_background(&path_buf);
             }
         }
+        Command::SaveNoteNow(path_buf) => {
+            if app.note_is_dirty(&path_buf) {
+                app.save_note_now(&path_buf);
+            }
+        }
+        Command::SwitchVault(name) => {
+            let vault = ui_state.settings.notes.vault_named(&name).cloned();
+            if let Some(vault) = vault
+                && vault.name != ui_state.settings.notes.vault().name
+            {
+                open_vault(app, ui_state, &vault);
+            }
+        }
+        Command::SetVaults(drafts) => {
+            let Some(vaults) = vaults_of_drafts(&drafts) else {
+                return;
+            };
+            let current = ui_state.settings.notes.vault().clone();
+            // Current vault is followed through renames by its position
+            let current_index = ui_state
+                .settings
+                .notes
+                .vaults
+                .iter()
+                .position(|vault| *vault == current);
+            let reopened = current_index
+                .and_then(|index| vaults.get(index))
+                .unwrap_or(&vaults[0])
+                .clone();
+            let previous = std::mem::replace(&mut ui_state.settings.notes.vaults, vaults);
+            if reopened.location == current.location {
+                ui_state.settings.notes.current_vault = reopened.name;
+            } else if !open_vault(app, ui_state, &reopened) {
+                ui_state.settings.notes.vaults = previous;
+            }
+        }
+        Command::ToggleDir(path) => {
+            if !ui_state.expanded_dirs.remove(&path) {
+                ui_state.expanded_dirs.insert(path);
+            }
+        }
+        Command::ReadTrash => {
+            app.read_trash_in_background();
+        }
+        Command::RestoreFromTrash(name) => {
+            app.restore_from_trash_in_background(&name);
+        }
+        Command::DeleteFromTrash(name) => {
+            app.purge_from_trash_in_background(&name);
+        }
+        Command::EmptyTrash => {
+            app.empty_trash_in_background();
+        }
+        Command::DismissRecentlyTrashed => {
+            app.dismiss_recently_trashed();
+        }
+        Command::BuildIndex => {
+            app.build_index_in_background();
+        }
+        Command::RebuildIndex => {
+            app.rebuild_index_in_background();
+        }
+        Command::LayoutGraph => {
+            app.layout_graph_in_background();
+        }
+        Command::RenameTag { from, to } => {
+            for tag in &mut ui_state.tag_filter.selected {
+                if let Some(renamed) = tags::renamed(tag, &from, &to) {
+                    *tag = renamed;
+                }
+            }
+            app.rename_tag_in_background(&from, &to);
+        }
+        Command::ResolveConflict { path, resolution } => {
+            app.resolve_conflict(&path, resolution);
+        }
+        Command::OpenMergeView(path) => {
+            if let Some(merged) = app.merge_conflict(&path) {
+                let has_conflicts = merged.is_err();
+                ui_state.merge_view = Some(MergeView {
+                    path,
+                    text: merged.unwrap_or_else(|text| text),
+                    has_conflicts,
+                });
+            }
+        }
+        Command::ReadHistory(path) => {
+            app.read_note_history_in_background(&path);
+        }
+        Command::ReloadHistory(path) => {
+            app.refresh_note_history_in_background(&path);
+        }
+        Command::ReadSnapshots(path) => {
+            app.read_note_snapshots_in_background(&path);
+        }
+        Command::ReloadSnapshots(path) => {
+            app.refresh_note_snapshots_in_background(&path);
+        }
+        Command::RestoreRevision { path, source } => {
+            app.pin_tab(&path);
+            app.restore_revision(&path, source);
+        }
+        Command::JumpToSearchHit { path, offset } => {
+            app.open_note(path.clone(), false);
+            ui_state.pending_jump = Some((path, offset));
+        }
     }
 }
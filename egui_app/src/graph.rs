@@ -0,0 +1,180 @@
+use std::path::{Path, PathBuf};
+
+use egui::{Align2, CursorIcon, FontId, Pos2, Rect, Sense, Stroke, Ui, Vec2};
+use storage::graph::{GraphLayout, Point};
+
+const MIN_ZOOM: f32 = 0.05;
+const MAX_ZOOM: f32 = 8.;
+
+/// From that zoom on every note shown has its name under it
+const LABELS_ZOOM: f32 = 1.5;
+
+/// Camera and filters of the graph panel
+#[derive(Debug)]
+pub struct GraphView {
+    /// Graph point in the middle of the panel
+    pub center: Vec2,
+    pub zoom: f32,
+    /// Only notes below the folder, path from the vault root
+    pub folder: Option<PathBuf>,
+    /// Only notes with the tag
+    pub tag: Option<String>,
+}
+
+impl Default for GraphView {
+    fn default() -> Self {
+        Self {
+            center: Vec2::ZERO,
+            zoom: 1.,
+            folder: None,
+            tag: None,
+        }
+    }
+}
+
+impl GraphView {
+    fn to_screen(&self, rect: Rect, point: Point) -> Pos2 {
+        rect.center() + (Vec2::from(point) - self.center) * self.zoom
+    }
+
+    fn to_graph(&self, rect: Rect, pos: Pos2) -> Vec2 {
+        self.center + (pos - rect.center()) / self.zoom
+    }
+
+    /// Put the note in the middle
+    pub fn center_on(&mut self, layout: &GraphLayout, path: &Path) {
+        if let Ok(node) = layout
+            .graph
+            .nodes
+            .binary_search_by(|node| node.as_path().cmp(path))
+        {
+            self.center = Vec2::from(layout.positions[node]);
+        }
+    }
+}
+
+fn note_name(path: &Path) -> String {
+    path.file_stem()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Drag to pan, scroll or pinch to zoom. Only notes marked visible are drawn, the current one
+/// and notes linked with it stand out. Returns the note clicked
+pub fn graph_ui(
+    ui: &mut Ui,
+    view: &mut GraphView,
+    layout: &GraphLayout,
+    visible: &[bool],
+    current: &Path,
+) -> Option<PathBuf> {
+    let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
+    let rect = response.rect;
+    view.center -= response.drag_delta() / view.zoom;
+    if let Some(pointer) = response.hover_pos() {
+        let (scroll, pinch) = ui.input(|input| (input.smooth_scroll_delta.y, input.zoom_delta()));
+        let factor = pinch * (scroll / 200.).exp();
+        if factor != 1. {
+            // Point under the pointer stays under it
+            let before = view.to_graph(rect, pointer);
+            view.zoom = (view.zoom * factor).clamp(MIN_ZOOM, MAX_ZOOM);
+            view.center += before - view.to_graph(rect, pointer);
+        }
+    }
+
+    let graph = &layout.graph;
+    let current = graph
+        .nodes
+        .binary_search_by(|node| node.as_path().cmp(current))
+        .ok();
+    let neighbours = current
+        .map(|node| graph.neighbours(node))
+        .unwrap_or_default();
+    let mut degrees = vec![0usize; graph.nodes.len()];
+    for &(a, b) in &graph.edges {
+        degrees[a] += 1;
+        degrees[b] += 1;
+    }
+
+    let visuals = ui.visuals();
+    let current_color = visuals.selection.bg_fill;
+    let neighbour_color = visuals.hyperlink_color;
+    let note_color = if current.is_some() {
+        visuals.weak_text_color()
+    } else {
+        visuals.text_color()
+    };
+    let edge_stroke = Stroke::new(1., visuals.weak_text_color().gamma_multiply(0.4));
+    let font = FontId::proportional(12.);
+
+    for &(a, b) in &graph.edges {
+        if !(visible[a] && visible[b]) {
+            continue;
+        }
+        let ends = [
+            view.to_screen(rect, layout.positions[a]),
+            view.to_screen(rect, layout.positions[b]),
+        ];
+        if !Rect::from_two_pos(ends[0], ends[1]).intersects(rect) {
+            continue;
+        }
+        let stroke = if current == Some(a) || current == Some(b) {
+            Stroke::new(1.5, neighbour_color)
+        } else {
+            edge_stroke
+        };
+        painter.line_segment(ends, stroke);
+    }
+
+    let pointer = response.hover_pos();
+    let mut hovered: Option<(usize, f32)> = None;
+    for (node, path) in graph.nodes.iter().enumerate() {
+        if !visible[node] {
+            continue;
+        }
+        let center = view.to_screen(rect, layout.positions[node]);
+        // Notes with more links are bigger
+        let radius = (2.5 + (degrees[node] as f32).sqrt()) * view.zoom.sqrt();
+        if !rect.expand(radius).contains(center) {
+            continue;
+        }
+        let highlighted = current == Some(node) || neighbours.contains(&node);
+        let color = if current == Some(node) {
+            current_color
+        } else if highlighted {
+            neighbour_color
+        } else {
+            note_color
+        };
+        painter.circle_filled(center, radius, color);
+        if highlighted || view.zoom >= LABELS_ZOOM {
+            let label_pos = center + Vec2::new(0., radius + 2.);
+            painter.text(
+                label_pos,
+                Align2::CENTER_TOP,
+                note_name(path),
+                font.clone(),
+                color,
+            );
+        }
+        if let Some(pointer) = pointer {
+            let distance = pointer.distance(center);
+            if distance <= radius + 4. && hovered.is_none_or(|(_, nearest)| distance < nearest) {
+                hovered = Some((node, distance));
+            }
+        }
+    }
+
+    let (node, _) = hovered?;
+    ui.ctx().set_cursor_icon(CursorIcon::PointingHand);
+    let path = &graph.nodes[node];
+    let center = view.to_screen(rect, layout.positions[node]);
+    painter.text(
+        center + Vec2::new(0., -8.),
+        Align2::CENTER_BOTTOM,
+        note_name(path),
+        font,
+        visuals.strong_text_color(),
+    );
+    response.clicked().then(|| path.clone())
+}
@@ -1,21 +1,25 @@
-mod app;
-mod data;
+mod config;
 mod egui_app;
-mod thread_pool;
+mod graph;
+mod preview;
+mod session;
 mod util;
 
 use eframe::Renderer;
 use egui_app::NotesApp;
+use session::Session;
 
 rust_i18n::i18n!("locales", fallback = "en");
 
 fn main() -> eframe::Result {
     env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
+    let session = Session::load();
     let options = eframe::NativeOptions {
         // viewport: egui::ViewportBuilder::default()
         //     .with_titlebar_shown(false)
         //     .with_title_shown(false)
         //     .with_fullsize_content_view(true),
+        viewport: session.viewport(),
         renderer: Renderer::Wgpu,
         ..Default::default()
     };
@@ -28,7 +32,7 @@ fn main() -> eframe::Result {
             egui_extras::install_image_loaders(&cc.egui_ctx);
 
             NotesApp::setup_fonts(&cc.egui_ctx);
-            Ok(Box::new(NotesApp::init()))
+            Ok(Box::new(NotesApp::init(session, &cc.egui_ctx)?))
         }),
     )
 }
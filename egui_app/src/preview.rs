@@ -0,0 +1,474 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use egui::{
+    Align, Frame, Grid, Image, Label, Layout, Margin, OpenUrl, RichText, ScrollArea, Stroke,
+    TextStyle, Ui, Widget,
+};
+use markdown::ParseOptions;
+use markdown::mdast::{self, Node};
+use notes::links::{WikiLink, decode_local_url, is_external, wiki_links};
+use rust_i18n::t;
+use storage::links::{NoteNames, clean_path};
+
+/// Parsed note text, reparsed only when the text changes
+#[derive(Default)]
+pub struct MarkdownCache {
+    path: PathBuf,
+    text_hash: u64,
+    root: Option<Node>,
+}
+
+impl MarkdownCache {
+    pub fn get(&mut self, path: &Path, text: &str) -> &Node {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let text_hash = hasher.finish();
+
+        if self.root.is_none() || self.path != path || self.text_hash != text_hash {
+            self.path = path.to_path_buf();
+            self.text_hash = text_hash;
+            // Markdown without MDX extensions always parses
+            self.root = markdown::to_mdast(text, &ParseOptions::gfm()).ok();
+        }
+        self.root.get_or_insert_with(|| {
+            Node::Root(mdast::Root {
+                children: Vec::new(),
+                position: None,
+            })
+        })
+    }
+}
+
+/// Inline formatting inherited from enclosing nodes
+#[derive(Clone, Default)]
+struct InlineStyle {
+    strong: bool,
+    emphasis: bool,
+    strikethrough: bool,
+    heading: Option<u8>,
+    link: Option<String>,
+}
+
+impl InlineStyle {
+    fn rich_text(&self, ui: &Ui, text: &str) -> RichText {
+        let mut rich_text = RichText::new(text);
+        if let Some(depth) = self.heading {
+            let heading_size = TextStyle::Heading.resolve(ui.style()).size;
+            let body_size = TextStyle::Body.resolve(ui.style()).size;
+            // h1 gets heading size, deeper levels step down to body size
+            let step = (heading_size - body_size) / 3.;
+            let size = heading_size - step * f32::from(depth.saturating_sub(1).min(3));
+            rich_text = rich_text.size(size).strong();
+        }
+        if self.strong {
+            rich_text = rich_text.strong();
+        }
+        if self.emphasis {
+            rich_text = rich_text.italics();
+        }
+        if self.strikethrough {
+            rich_text = rich_text.strikethrough();
+        }
+        rich_text
+    }
+}
+
+/// Notes wiki links of the shown note resolve to
+pub struct WikiLinkTargets<'a> {
+    pub names: &'a NoteNames,
+    pub base_path: &'a Path,
+    pub note_path: &'a Path,
+}
+
+/// Renders markdown AST as egui widgets
+pub struct MarkdownView<'a> {
+    /// Relative links and images are resolved against it
+    note_dir: &'a Path,
+    /// Links to files outside of it are not opened
+    base_path: &'a Path,
+    /// Wiki links are plain text without it
+    wiki_links: Option<WikiLinkTargets<'a>>,
+    /// Local file clicked in a link
+    opened_link: Option<PathBuf>,
+}
+
+impl<'a> MarkdownView<'a> {
+    pub fn new(note_dir: &'a Path, base_path: &'a Path) -> Self {
+        Self {
+            note_dir,
+            base_path,
+            wiki_links: None,
+            opened_link: None,
+        }
+    }
+
+    pub fn with_wiki_links(mut self, targets: WikiLinkTargets<'a>) -> Self {
+        self.wiki_links = Some(targets);
+        self
+    }
+
+    /// Show document, returns local file when a link to it was clicked
+    pub fn show(mut self, ui: &mut Ui, root: &Node) -> Option<PathBuf> {
+        ui.with_layout(Layout::top_down(Align::LEFT), |ui| {
+            self.blocks_ui(ui, root.children().map(Vec::as_slice).unwrap_or_default());
+        });
+        self.opened_link
+    }
+
+    fn blocks_ui(&mut self, ui: &mut Ui, nodes: &[Node]) {
+        for node in nodes {
+            self.block_ui(ui, node);
+        }
+    }
+
+    fn block_ui(&mut self, ui: &mut Ui, node: &Node) {
+        match node {
+            Node::Heading(heading) => {
+                ui.add_space(ui.spacing().item_spacing.y * 2.);
+                let style = InlineStyle {
+                    heading: Some(heading.depth),
+                    ..Default::default()
+                };
+                self.paragraph_ui(ui, &heading.children, &style);
+            }
+            Node::Paragraph(paragraph) => {
+                self.paragraph_ui(ui, &paragraph.children, &InlineStyle::default());
+            }
+            Node::List(list) => self.list_ui(ui, list),
+            Node::Code(code) => code_block_ui(ui, &code.value, code.lang.as_deref()),
+            Node::Math(math) => code_block_ui(ui, &math.value, Some("math")),
+            Node::Blockquote(quote) => {
+                let response = Frame::new()
+                    .inner_margin(Margin {
+                        left: 12,
+                        ..Default::default()
+                    })
+                    .show(ui, |ui| self.blocks_ui(ui, &quote.children))
+                    .response;
+                let rect = response.rect;
+                ui.painter().vline(
+                    rect.left() + 2.,
+                    rect.y_range(),
+                    Stroke::new(3., ui.visuals().weak_text_color()),
+                );
+            }
+            Node::Table(table) => self.table_ui(ui, table),
+            Node::ThematicBreak(_) => {
+                ui.separator();
+            }
+            Node::Html(html) => {
+                ui.add(Label::new(RichText::new(&html.value).monospace().weak()));
+            }
+            Node::FootnoteDefinition(footnote) => {
+                ui.horizontal_top(|ui| {
+                    ui.weak(format!("[{}]", footnote.identifier));
+                    ui.vertical(|ui| self.blocks_ui(ui, &footnote.children));
+                });
+            }
+            // Front matter is not part of note text, definitions are used by references
+            Node::Yaml(_) | Node::Toml(_) | Node::Definition(_) => {}
+            node => match node.children() {
+                Some(children) => self.blocks_ui(ui, children),
+                None => self.paragraph_ui(ui, std::slice::from_ref(node), &InlineStyle::default()),
+            },
+        }
+    }
+
+    fn paragraph_ui(&mut self, ui: &mut Ui, nodes: &[Node], style: &InlineStyle) {
+        ui.horizontal_wrapped(|ui| {
+            ui.spacing_mut().item_spacing.x = 0.;
+            for node in nodes {
+                self.inline_ui(ui, node, style);
+            }
+        });
+    }
+
+    fn inline_ui(&mut self, ui: &mut Ui, node: &Node, style: &InlineStyle) {
+        match node {
+            Node::Text(text) => self.text_ui(ui, &text.value, style),
+            Node::InlineCode(code) => {
+                self.rich_text_ui(ui, style.rich_text(ui, &code.value).code(), style)
+            }
+            Node::InlineMath(math) => {
+                self.rich_text_ui(ui, style.rich_text(ui, &math.value).code(), style)
+            }
+            Node::Html(html) => self.rich_text_ui(ui, RichText::new(&html.value).weak(), style),
+            Node::Break(_) => ui.end_row(),
+            Node::Strong(strong) => {
+                let style = InlineStyle {
+                    strong: true,
+                    ..style.clone()
+                };
+                self.inlines_ui(ui, &strong.children, &style);
+            }
+            Node::Emphasis(emphasis) => {
+                let style = InlineStyle {
+                    emphasis: true,
+                    ..style.clone()
+                };
+                self.inlines_ui(ui, &emphasis.children, &style);
+            }
+            Node::Delete(delete) => {
+                let style = InlineStyle {
+                    strikethrough: true,
+                    ..style.clone()
+                };
+                self.inlines_ui(ui, &delete.children, &style);
+            }
+            Node::Link(link) => {
+                let style = InlineStyle {
+                    link: Some(link.url.clone()),
+                    ..style.clone()
+                };
+                self.inlines_ui(ui, &link.children, &style);
+            }
+            Node::Image(image) => self.image_ui(ui, &image.url, &image.alt),
+            Node::FootnoteReference(reference) => {
+                self.rich_text_ui(
+                    ui,
+                    RichText::new(format!("[{}]", reference.identifier)).small_raised(),
+                    style,
+                );
+            }
+            node => match node.children() {
+                Some(children) => self.inlines_ui(ui, children, style),
+                None => self.text_ui(ui, &node.to_string(), style),
+            },
+        }
+    }
+
+    fn inlines_ui(&mut self, ui: &mut Ui, nodes: &[Node], style: &InlineStyle) {
+        for node in nodes {
+            self.inline_ui(ui, node, style);
+        }
+    }
+
+    fn text_ui(&mut self, ui: &mut Ui, text: &str, style: &InlineStyle) {
+        // Markdown parser leaves `[[target]]` in text, inside a link it stays text
+        let links = match self.wiki_links {
+            Some(_) if style.link.is_none() => wiki_links(text),
+            _ => Vec::new(),
+        };
+        let mut offset = 0;
+        for link in links {
+            if offset < link.range.start {
+                let rich_text = style.rich_text(ui, &text[offset..link.range.start]);
+                self.rich_text_ui(ui, rich_text, style);
+            }
+            self.wiki_link_ui(ui, &link, style);
+            offset = link.range.end;
+        }
+        if offset < text.len() || text.is_empty() {
+            let rich_text = style.rich_text(ui, &text[offset..]);
+            self.rich_text_ui(ui, rich_text, style);
+        }
+    }
+
+    /// Link to a note of the vault, missing notes are shown dimmed
+    fn wiki_link_ui(&mut self, ui: &mut Ui, link: &WikiLink, style: &InlineStyle) {
+        let Some(targets) = &self.wiki_links else {
+            return;
+        };
+        let rich_text = style.rich_text(ui, link.label());
+        let resolved = targets
+            .names
+            .resolve(targets.base_path, targets.note_path, link.target);
+        let Some(path) = resolved else {
+            ui.label(rich_text.weak())
+                .on_hover_text(t!("note_not_found", target = link.target));
+            return;
+        };
+        let location = path.strip_prefix(targets.base_path).unwrap_or(path);
+        if ui
+            .link(rich_text)
+            .on_hover_text(location.to_string_lossy())
+            .clicked()
+        {
+            self.opened_link = Some(path.to_path_buf());
+        }
+    }
+
+    fn rich_text_ui(&mut self, ui: &mut Ui, rich_text: RichText, style: &InlineStyle) {
+        let Some(url) = &style.link else {
+            ui.label(rich_text);
+            return;
+        };
+        let response = ui.link(rich_text).on_hover_text(url);
+        if response.clicked() {
+            if is_external(url) {
+                ui.ctx().open_url(OpenUrl::new_tab(url));
+            } else if let Some(path) = local_link_target(self.base_path, self.note_dir, url) {
+                self.opened_link = Some(path);
+            }
+        }
+    }
+
+    /// Images out of the vault are not loaded, their alt text is shown instead
+    fn image_ui(&mut self, ui: &mut Ui, url: &str, alt: &str) {
+        let Some(uri) = image_uri(self.base_path, self.note_dir, url) else {
+            ui.weak(alt).on_hover_text(url);
+            return;
+        };
+        Image::new(uri)
+            .max_width(ui.available_width())
+            .show_loading_spinner(true)
+            .ui(ui)
+            .on_hover_text(alt);
+    }
+
+    fn list_ui(&mut self, ui: &mut Ui, list: &mdast::List) {
+        let start = list.start.unwrap_or(1);
+        for (index, item) in list.children.iter().enumerate() {
+            let Node::ListItem(item) = item else {
+                self.block_ui(ui, item);
+                continue;
+            };
+            let marker = match (item.checked, list.ordered) {
+                (Some(true), _) => phosphor_icons::CHECK_SQUARE.to_owned(),
+                (Some(false), _) => phosphor_icons::SQUARE.to_owned(),
+                (None, true) => format!("{}.", start as usize + index),
+                (None, false) => "•".to_owned(),
+            };
+            ui.horizontal_top(|ui| {
+                ui.label(marker);
+                ui.vertical(|ui| self.blocks_ui(ui, &item.children));
+            });
+        }
+    }
+
+    fn table_ui(&mut self, ui: &mut Ui, table: &mdast::Table) {
+        let id = ui.next_auto_id();
+        ScrollArea::horizontal().id_salt(id).show(ui, |ui| {
+            Grid::new(id).striped(true).show(ui, |ui| {
+                for (row_index, row) in table.children.iter().enumerate() {
+                    let style = InlineStyle {
+                        strong: row_index == 0,
+                        ..Default::default()
+                    };
+                    for cell in row.children().map(Vec::as_slice).unwrap_or_default() {
+                        let cell_children = cell.children().map(Vec::as_slice).unwrap_or_default();
+                        self.paragraph_ui(ui, cell_children, &style);
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    }
+}
+
+fn code_block_ui(ui: &mut Ui, code: &str, lang: Option<&str>) {
+    Frame::new()
+        .fill(ui.visuals().code_bg_color)
+        .corner_radius(ui.visuals().widgets.noninteractive.corner_radius)
+        .inner_margin(Margin::same(8))
+        .show(ui, |ui| {
+            ui.set_width(ui.available_width());
+            if let Some(lang) = lang {
+                ui.weak(lang);
+            }
+            let id = ui.next_auto_id();
+            ScrollArea::horizontal().id_salt(id).show(ui, |ui| {
+                Label::new(RichText::new(code.trim_end_matches('\n')).monospace())
+                    .extend()
+                    .ui(ui);
+            });
+        });
+}
+
+/// File a relative link points to, `None` when it leads out of the vault
+fn local_link_target(base_path: &Path, note_dir: &Path, url: &str) -> Option<PathBuf> {
+    let path = clean_path(&note_dir.join(decode_local_url(url)));
+    path.starts_with(base_path).then_some(path)
+}
+
+/// URI to load an image from, local files only inside the vault
+fn image_uri(base_path: &Path, note_dir: &Path, url: &str) -> Option<String> {
+    if url.starts_with("file:") {
+        return None;
+    }
+    if is_external(url) {
+        return Some(url.to_owned());
+    }
+    let path = local_link_target(base_path, note_dir, url)?;
+    Some(format!("file://{}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wiki_links_are_resolved() {
+        let mut names = NoteNames::default();
+        names.insert(Path::new("/notes/dir/other.md"), &[]);
+        let text = "See [[other]], [[missing|gone]] and [link [[other]]](x)";
+        let mut cache = MarkdownCache::default();
+        let root = cache.get(Path::new("/notes/note"), text);
+
+        let ctx = egui::Context::default();
+        let _ = ctx.run(Default::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let targets = WikiLinkTargets {
+                    names: &names,
+                    base_path: Path::new("/notes"),
+                    note_path: Path::new("/notes/note"),
+                };
+                let view = MarkdownView::new(Path::new("/notes"), Path::new("/notes"))
+                    .with_wiki_links(targets);
+                assert!(view.show(ui, root).is_none());
+            });
+        });
+    }
+
+    #[test]
+    fn render_all_kinds_of_nodes() {
+        let text = "# Title\n\n## Sub\n\nSome *em* **strong** ~~del~~ `code` [link](other%20note) <b>html</b>\\\nnext\n\n- item\n- [x] done\n\n3. three\n4. four\n\n> quote\n\n```rust\nfn main() {}\n```\n\n| a | b |\n|---|---|\n| 1 | 2 |\n\n---\n\n![alt](image.png) text[^1]\n\n[^1]: note\n";
+        let mut cache = MarkdownCache::default();
+        let root = cache.get(Path::new("/notes/note"), text);
+        assert!(matches!(root, Node::Root(_)));
+
+        let ctx = egui::Context::default();
+        let _ = ctx.run(Default::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let opened =
+                    MarkdownView::new(Path::new("/notes"), Path::new("/notes")).show(ui, root);
+                assert!(opened.is_none());
+            });
+        });
+    }
+
+    #[test]
+    fn links_out_of_vault_are_not_opened() {
+        let base = Path::new("/notes");
+        let dir = Path::new("/notes/dir");
+        assert_eq!(
+            local_link_target(base, dir, "../My%20note.md#part"),
+            Some(PathBuf::from("/notes/My note.md"))
+        );
+        assert_eq!(
+            local_link_target(base, dir, "./sub/./x"),
+            Some(PathBuf::from("/notes/dir/sub/x"))
+        );
+        assert_eq!(local_link_target(base, dir, "../../etc/passwd"), None);
+        assert_eq!(local_link_target(base, dir, "/etc/passwd"), None);
+        assert_eq!(local_link_target(base, dir, "../../notes2/x"), None);
+    }
+
+    #[test]
+    fn images_out_of_vault_are_not_loaded() {
+        let base = Path::new("/notes");
+        let dir = Path::new("/notes/dir");
+        assert_eq!(
+            image_uri(base, dir, "../images/cat%201.png").as_deref(),
+            Some("file:///notes/images/cat 1.png")
+        );
+        assert_eq!(
+            image_uri(base, dir, "https://example.com/cat.png").as_deref(),
+            Some("https://example.com/cat.png")
+        );
+        assert_eq!(image_uri(base, dir, "../../etc/secret.png"), None);
+        assert_eq!(image_uri(base, dir, "/etc/secret.png"), None);
+        assert_eq!(image_uri(base, dir, "file:///etc/secret.png"), None);
+    }
+}
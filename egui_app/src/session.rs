@@ -0,0 +1,244 @@
+//! Where the user left off, kept in `session.toml` of the platform state directory,
+//! e.g. `~/.local/state/questionable/session.toml` on Linux
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use egui::{Pos2, Vec2, ViewportBuilder, ViewportInfo};
+use serde::{Deserialize, Serialize};
+
+use storage::settings::{APP_DIR_NAME, read_toml, write_toml};
+use storage::tabs::Tab;
+
+const SESSION_FILE_NAME: &str = "session.toml";
+
+/// View states of that many recently shown notes are kept
+const MAX_REMEMBERED_NOTES: usize = 256;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+    pub explorer: bool,
+    pub trash: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<WindowGeometry>,
+    pub vaults: Vec<VaultState>,
+    pub notes: Vec<NoteState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WindowGeometry {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<[f32; 2]>,
+    pub size: [f32; 2],
+    #[serde(default)]
+    pub maximized: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VaultState {
+    pub base_path: PathBuf,
+    pub current_note: PathBuf,
+    #[serde(default)]
+    pub tabs: Vec<Tab>,
+    #[serde(default)]
+    pub expanded_dirs: Vec<PathBuf>,
+}
+
+/// Editor position in a note
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoteState {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub scroll: f32,
+    /// Character index
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<usize>,
+    /// Unix time the note was last shown at
+    #[serde(default)]
+    pub shown_at: i64,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            explorer: true,
+            trash: false,
+            window: None,
+            vaults: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+}
+
+impl Session {
+    pub fn path() -> Option<PathBuf> {
+        Some(
+            dirs::state_dir()
+                .or_else(dirs::data_local_dir)?
+                .join(APP_DIR_NAME)
+                .join(SESSION_FILE_NAME),
+        )
+    }
+
+    /// Fresh session when there is none yet or it cannot be read
+    pub fn load() -> Self {
+        let Some(path) = Self::path() else {
+            return Self::default();
+        };
+        match Self::read(&path) {
+            Ok(session) => session,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(err) => {
+                log::error!("Failed to read {}: {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        read_toml(path)
+    }
+
+    pub fn save(&mut self) -> io::Result<()> {
+        let path = Self::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no state directory"))?;
+        self.write(&path)
+    }
+
+    /// Only recently shown notes are written
+    pub fn write(&mut self, path: &Path) -> io::Result<()> {
+        self.notes.sort_by(|a, b| {
+            b.shown_at
+                .cmp(&a.shown_at)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        self.notes.truncate(MAX_REMEMBERED_NOTES);
+        write_toml(path, self)
+    }
+
+    pub fn viewport(&self) -> ViewportBuilder {
+        let viewport = ViewportBuilder::default();
+        let Some(window) = &self.window else {
+            return viewport;
+        };
+        let viewport = viewport
+            .with_inner_size(Vec2::from(window.size))
+            .with_maximized(window.maximized);
+        match window.position {
+            Some(position) => viewport.with_position(Pos2::from(position)),
+            None => viewport,
+        }
+    }
+}
+
+impl WindowGeometry {
+    /// Size before maximizing is kept while the window is maximized or minimized
+    pub fn update(window: &mut Option<Self>, info: &ViewportInfo) {
+        let maximized = info.maximized.unwrap_or_default();
+        if maximized || info.minimized.unwrap_or_default() {
+            if let Some(window) = window {
+                window.maximized = maximized;
+            }
+            return;
+        }
+        let Some(inner_rect) = info.inner_rect else {
+            return;
+        };
+        *window = Some(Self {
+            position: info.outer_rect.map(|rect| rect.min.into()),
+            size: inner_rect.size().into(),
+            maximized: false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SESSION_FILE_NAME);
+        let mut session = Session {
+            explorer: false,
+            trash: true,
+            window: Some(WindowGeometry {
+                position: Some([10., 20.]),
+                size: [800., 600.],
+                maximized: false,
+            }),
+            vaults: vec![VaultState {
+                base_path: PathBuf::from("/notes"),
+                current_note: PathBuf::from("/notes/a.md"),
+                tabs: vec![Tab {
+                    path: PathBuf::from("/notes/a.md"),
+                    pinned: true,
+                }],
+                expanded_dirs: vec![PathBuf::from("/notes/dir")],
+            }],
+            notes: vec![NoteState {
+                path: PathBuf::from("/notes/a.md"),
+                scroll: 120.5,
+                cursor: Some(42),
+                shown_at: 1,
+            }],
+        };
+
+        session.write(&path).unwrap();
+        assert_eq!(Session::read(&path).unwrap(), session);
+    }
+
+    #[test]
+    fn only_recent_notes_are_written() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(SESSION_FILE_NAME);
+        let mut session = Session {
+            notes: (0..MAX_REMEMBERED_NOTES as i64 + 10)
+                .map(|shown_at| NoteState {
+                    path: PathBuf::from(format!("/notes/{shown_at}.md")),
+                    scroll: 0.,
+                    cursor: None,
+                    shown_at,
+                })
+                .collect(),
+            ..Default::default()
+        };
+
+        session.write(&path).unwrap();
+        let notes = Session::read(&path).unwrap().notes;
+        assert_eq!(notes.len(), MAX_REMEMBERED_NOTES);
+        assert!(notes.iter().all(|note| note.shown_at >= 10));
+    }
+
+    #[test]
+    fn maximized_window_keeps_previous_geometry() {
+        let mut window = None;
+        let mut info = ViewportInfo {
+            inner_rect: Some(egui::Rect::from_min_size(
+                Pos2::new(10., 40.),
+                Vec2::new(800., 600.),
+            )),
+            outer_rect: Some(egui::Rect::from_min_size(
+                Pos2::new(10., 10.),
+                Vec2::new(800., 630.),
+            )),
+            maximized: Some(false),
+            ..Default::default()
+        };
+        WindowGeometry::update(&mut window, &info);
+
+        info.maximized = Some(true);
+        info.inner_rect = Some(egui::Rect::from_min_size(
+            Pos2::ZERO,
+            Vec2::new(1920., 1080.),
+        ));
+        WindowGeometry::update(&mut window, &info);
+
+        let window = window.unwrap();
+        assert!(window.maximized);
+        assert_eq!(window.size, [800., 600.]);
+        assert_eq!(window.position, Some([10., 10.]));
+    }
+}
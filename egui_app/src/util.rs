@@ -1,28 +1,10 @@
-use std::collections::HashMap;
-
-use regex::Regex;
-
 pub mod chrono {
-    use std::time::{SystemTime, UNIX_EPOCH};
-
     use chrono::{DateTime, Local, TimeZone, Utc};
 
     #[inline]
     pub fn to_local_date_time(utc_date_time: &DateTime<Utc>) -> DateTime<Local> {
         Local.from_utc_datetime(&utc_date_time.naive_utc())
     }
-
-    pub fn to_date_time_utc(sys_time: SystemTime) -> DateTime<Utc> {
-        DateTime::from_timestamp_secs(
-            sys_time
-                .duration_since(UNIX_EPOCH)
-                .unwrap()
-                .as_secs()
-                .try_into()
-                .unwrap(),
-        )
-        .expect("DateTime<Utc>")
-    }
 }
 
 pub mod egui {
@@ -38,35 +20,38 @@ pub mod egui {
     }
 }
 
-pub fn generate_unique_name<'x>(
-    existing_names: impl IntoIterator<Item = &'x str>,
-    candidate_name: String,
-) -> String {
-    let start_count = 1;
-    let counts = existing_names
-        .into_iter()
-        .filter_map(|existing_name| {
-            Regex::new(&format!(r"^{candidate_name}( #(?<count>\d+))*$"))
-                .unwrap()
-                .captures(existing_name)
-        })
-        .map(|caps| {
-            caps.name("count")
-                .and_then(|c| c.as_str().parse::<i32>().ok())
-                .unwrap_or(start_count)
-        })
-        .map(|count| (count, true))
-        .collect::<HashMap<i32, bool>>();
-    let max_count = counts.keys().max().cloned().unwrap_or(-1);
-    for i in start_count..=max_count + 1 {
-        if counts.get(&i).is_none() {
-            return if i == 0 {
-                candidate_name
-            } else {
-                format!("{candidate_name} #{i}")
-            };
-        }
+/// File name of a note with the title, extension of the current name is kept. `None` when the
+/// title makes no name
+pub fn file_name_for_title(title: &str, current_name: &str) -> Option<String> {
+    // Leading dots would hide the note
+    let stem = title.replace(['/', '\\'], "-");
+    let stem = stem.trim().trim_start_matches('.');
+    if stem.is_empty() {
+        return None;
     }
+    // `v1.2 draft` has no extension, `draft.md` has
+    let extension = std::path::Path::new(current_name)
+        .extension()
+        .map(|extension| extension.to_string_lossy())
+        .filter(|extension| {
+            extension.len() <= 8 && extension.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+    let name = match extension {
+        Some(extension) => format!("{stem}.{extension}"),
+        None => stem.to_owned(),
+    };
+    is_valid_file_name(&name).then_some(name)
+}
+
+/// Name that can be used as a single path component
+pub fn is_valid_file_name(name: &str) -> bool {
+    let name = name.trim();
+    !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\'])
+}
 
-    candidate_name
+/// Byte offset of the char index, end of text when it is past the end
+pub fn byte_offset(text: &str, char_index: usize) -> usize {
+    text.char_indices()
+        .nth(char_index)
+        .map_or(text.len(), |(offset, _)| offset)
 }
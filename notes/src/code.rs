@@ -0,0 +1,58 @@
+//! Code blocks and code spans, which have no tags or links
+
+use std::ops::Range;
+
+/// Byte ranges of text outside of fenced code blocks and code spans. Ranges never span lines,
+/// the one before a code span ends at its opening backticks
+pub(crate) fn prose_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut offset = 0;
+    let mut in_code_block = false;
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let mut start = 0;
+        while let Some(found) = line[start..].find('`') {
+            let run_start = start + found;
+            // Code span ends with a backtick run of the same length
+            let run = line[run_start..].len() - line[run_start..].trim_start_matches('`').len();
+            let fence = &line[run_start..run_start + run];
+            let after = run_start + run;
+            if run_start > start {
+                ranges.push(line_start + start..line_start + run_start);
+            }
+            start = match line[after..].find(fence) {
+                Some(end) => after + end + run,
+                None => after,
+            };
+        }
+        if start < line.len() {
+            ranges.push(line_start + start..offset);
+        }
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_is_left_out() {
+        let text = "a `b` c ``d ` e``\n```\ncode\n```\nf `g";
+        let prose = prose_ranges(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect::<Vec<_>>();
+        assert_eq!(prose, ["a ", " c ", "\n", "f ", "g"]);
+    }
+}
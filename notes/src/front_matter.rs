@@ -0,0 +1,335 @@
+//! YAML front matter block at the start of note source:
+//!
+//! ```text
+//! ---
+//! title: Meeting notes
+//! icon: note
+//! tags: [work, project/alpha]
+//! ---
+//! Note text
+//! ```
+
+use std::fmt;
+use std::io::{self, BufRead};
+
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_yaml::Mapping;
+
+pub const DELIMITER: &str = "---";
+
+/// Front matter longer than that is not looked for when only the header is read
+const MAX_HEADER_LINES: usize = 256;
+
+/// Known front matter keys, everything else is kept in `extra`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrontMatter {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "string_or_list"
+    )]
+    pub tags: Vec<String>,
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        deserialize_with = "string_or_list"
+    )]
+    pub aliases: Vec<String>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "lenient_date_time",
+        serialize_with = "rfc3339"
+    )]
+    pub created: Option<DateTime<FixedOffset>>,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "lenient_date_time",
+        serialize_with = "rfc3339"
+    )]
+    pub updated: Option<DateTime<FixedOffset>>,
+    #[serde(flatten)]
+    pub extra: Mapping,
+}
+
+#[derive(Debug)]
+pub enum FrontMatterError {
+    /// Opening delimiter without closing one
+    Unterminated,
+    /// Block is valid YAML, but not a key-value mapping
+    NotMapping,
+    Yaml(serde_yaml::Error),
+}
+
+impl fmt::Display for FrontMatterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unterminated => write!(f, "front matter is not terminated with `{DELIMITER}`"),
+            Self::NotMapping => write!(f, "front matter is not a key-value mapping"),
+            Self::Yaml(err) => write!(f, "malformed front matter: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for FrontMatterError {}
+
+/// Split source into front matter YAML and body, `None` when source has no front matter
+pub fn split(source: &str) -> Result<Option<(&str, &str)>, FrontMatterError> {
+    let Some(rest) = source.strip_prefix(DELIMITER) else {
+        return Ok(None);
+    };
+    let Some(rest) = rest
+        .strip_prefix("\r\n")
+        .or_else(|| rest.strip_prefix('\n'))
+    else {
+        // `----` or `--- text` is not an opening delimiter
+        return Ok(None);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end_matches(['\r', '\n']) == DELIMITER {
+            return Ok(Some((&rest[..offset], &rest[offset + line.len()..])));
+        }
+        offset += line.len();
+    }
+
+    Err(FrontMatterError::Unterminated)
+}
+
+/// Parse front matter and return it with the rest of source
+pub fn parse(source: &str) -> Result<Option<(FrontMatter, &str)>, FrontMatterError> {
+    let Some((yaml, body)) = split(source)? else {
+        return Ok(None);
+    };
+
+    if yaml.trim().is_empty() {
+        return Ok(Some((FrontMatter::default(), body)));
+    }
+
+    let value = serde_yaml::from_str::<serde_yaml::Value>(yaml).map_err(FrontMatterError::Yaml)?;
+    if !value.is_mapping() {
+        return Err(FrontMatterError::NotMapping);
+    }
+    let front_matter = serde_yaml::from_value(value).map_err(FrontMatterError::Yaml)?;
+
+    Ok(Some((front_matter, body)))
+}
+
+/// Read only the front matter block from the start of a file, malformed block is skipped
+pub fn read_header(mut reader: impl BufRead) -> io::Result<Option<FrontMatter>> {
+    let mut header = String::new();
+    for line_number in 0..MAX_HEADER_LINES {
+        let line_start = header.len();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let line = header[line_start..].trim_end_matches(['\r', '\n']);
+        if line == DELIMITER && line_number > 0 {
+            return Ok(parse(&header)
+                .ok()
+                .flatten()
+                .map(|(front_matter, _)| front_matter));
+        }
+        if line_number == 0 && line != DELIMITER {
+            return Ok(None);
+        }
+    }
+    Ok(None)
+}
+
+/// Serialize front matter block, including both delimiters
+pub fn serialize(front_matter: &FrontMatter) -> String {
+    let yaml = if *front_matter == FrontMatter::default() {
+        String::new()
+    } else {
+        serde_yaml::to_string(front_matter).expect("front matter is serializable")
+    };
+    format!("{DELIMITER}\n{yaml}{DELIMITER}\n")
+}
+
+fn string_or_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        String(String),
+        List(Vec<String>),
+    }
+
+    Ok(match Option::<StringOrList>::deserialize(deserializer)? {
+        Some(StringOrList::String(value)) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::to_owned)
+            .collect(),
+        Some(StringOrList::List(values)) => values,
+        None => Vec::new(),
+    })
+}
+
+/// Accept RFC 3339, `YYYY-MM-DD HH:MM[:SS]` and `YYYY-MM-DD`, local forms are taken as UTC
+fn lenient_date_time<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateTime<FixedOffset>>, D::Error> {
+    let Some(value) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+    let value = value.trim();
+
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(Some(date_time));
+    }
+    let naive = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|fmt| NaiveDateTime::parse_from_str(value, fmt).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        });
+
+    naive
+        .map(|naive| Some(naive.and_utc().fixed_offset()))
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid date `{value}`")))
+}
+
+fn rfc3339<S: Serializer>(
+    date_time: &Option<DateTime<FixedOffset>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match date_time {
+        Some(date_time) => serializer.serialize_str(&date_time.to_rfc3339()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Current time in the form stored in front matter
+pub fn now() -> DateTime<FixedOffset> {
+    Utc::now().fixed_offset()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn source_without_front_matter() {
+        let source = "# Title\n\nSome text\n";
+        assert!(parse(source).unwrap().is_none());
+    }
+
+    #[test]
+    fn thematic_break_lookalikes_are_not_front_matter() {
+        assert!(parse("----\ntext\n---\n").unwrap().is_none());
+        assert!(parse("--- title\ntext\n---\n").unwrap().is_none());
+        assert!(parse("text\n---\nicon: x\n---\n").unwrap().is_none());
+    }
+
+    #[test]
+    fn parse_known_and_extra_keys() {
+        let source = "---\ntitle: Weekly sync\nicon: note\ntags: [work, project/alpha]\naliases: meeting, sync\ncreated: 2024-03-01\nupdated: 2024-03-02T10:15:00+03:00\nauthor: me\nrating: 5\n---\nBody\n";
+        let (front_matter, body) = parse(source).unwrap().unwrap();
+
+        assert_eq!(body, "Body\n");
+        assert_eq!(front_matter.title.as_deref(), Some("Weekly sync"));
+        assert_eq!(front_matter.icon.as_deref(), Some("note"));
+        assert_eq!(front_matter.tags, ["work", "project/alpha"]);
+        assert_eq!(front_matter.aliases, ["meeting", "sync"]);
+        assert_eq!(
+            front_matter.created.unwrap().to_rfc3339(),
+            "2024-03-01T00:00:00+00:00"
+        );
+        assert_eq!(
+            front_matter.updated.unwrap().to_rfc3339(),
+            "2024-03-02T10:15:00+03:00"
+        );
+        assert_eq!(front_matter.extra.len(), 2);
+        assert_eq!(front_matter.extra["author"], "me");
+        assert_eq!(front_matter.extra["rating"], 5);
+    }
+
+    #[test]
+    fn round_trip() {
+        let source = "---\ntitle: Weekly sync\nicon: note\ntags:\n- work\naliases:\n- sync\ncreated: 2024-03-01T00:00:00+00:00\nupdated: 2024-03-02T10:15:00+03:00\nauthor: me\n---\nBody\n";
+        let (front_matter, body) = parse(source).unwrap().unwrap();
+        let serialized = format!("{}{}", serialize(&front_matter), body);
+
+        assert_eq!(serialized, source);
+    }
+
+    #[test]
+    fn empty_front_matter() {
+        let (front_matter, body) = parse("---\n---\nBody").unwrap().unwrap();
+        assert_eq!(front_matter, FrontMatter::default());
+        assert_eq!(body, "Body");
+        assert_eq!(serialize(&front_matter), "---\n---\n");
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let (front_matter, body) = parse("---\r\nicon: note\r\n---\r\nBody\r\n")
+            .unwrap()
+            .unwrap();
+        assert_eq!(front_matter.icon.as_deref(), Some("note"));
+        assert_eq!(body, "Body\r\n");
+    }
+
+    #[test]
+    fn read_only_header() {
+        let source = "---\nicon: note\n---\nBody\n---\n";
+        let front_matter = read_header(source.as_bytes()).unwrap().unwrap();
+        assert_eq!(front_matter.icon.as_deref(), Some("note"));
+
+        assert!(read_header("Body\n".as_bytes()).unwrap().is_none());
+        assert!(
+            read_header("---\nicon: note\n".as_bytes())
+                .unwrap()
+                .is_none()
+        );
+        assert!(read_header("---\n- x\n---\n".as_bytes()).unwrap().is_none());
+    }
+
+    #[test]
+    fn unterminated_header() {
+        assert!(matches!(
+            parse("---\nicon: note\nBody\n"),
+            Err(FrontMatterError::Unterminated)
+        ));
+    }
+
+    #[test]
+    fn invalid_yaml() {
+        assert!(matches!(
+            parse("---\nicon: [note\n---\nBody\n"),
+            Err(FrontMatterError::Yaml(_))
+        ));
+    }
+
+    #[test]
+    fn yaml_that_is_not_mapping() {
+        assert!(matches!(
+            parse("---\n- one\n- two\n---\nBody\n"),
+            Err(FrontMatterError::NotMapping)
+        ));
+        assert!(matches!(
+            parse("---\njust text\n---\nBody\n"),
+            Err(FrontMatterError::NotMapping)
+        ));
+    }
+
+    #[test]
+    fn invalid_field_types() {
+        assert!(matches!(
+            parse("---\ncreated: yesterday\n---\n"),
+            Err(FrontMatterError::Yaml(_))
+        ));
+        assert!(matches!(
+            parse("---\ntags: {a: b}\n---\n"),
+            Err(FrontMatterError::Yaml(_))
+        ));
+    }
+}
@@ -1,41 +1,100 @@
+mod code;
+pub mod front_matter;
+pub mod links;
+pub mod tags;
+
+use std::ops::Range;
+
+use chrono::{DateTime, FixedOffset};
+use serde_yaml::Mapping;
+
+use front_matter::FrontMatter;
+
 pub const DEFAULT_ICON: &str = phosphor_icons::NOTE;
 pub const SCRATCH_PAD_ICON: &str = phosphor_icons::PENCIL_LINE;
 
 pub const DEFAULT_FOLDER_NAME: &str = "Some folder";
 pub const DEFAULT_ROOT_NAME: &str = "notes";
 pub const DEFAULT_TRASH_NAME: &str = "trash";
+pub const DEFAULT_HISTORY_NAME: &str = "history";
 
 pub const DEFAULT_NAME: &str = "a note";
 pub const DEFAULT_TITLE: &str = "Some new note title";
 
 pub const SCRATCH_PAD_NAME: &str = "Scratch Pad";
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Note {
     pub text: String,
     pub metadata: Metadata,
 }
 
-impl Default for Note {
-    fn default() -> Self {
-        Self {
-            text: String::new(),
-            metadata: Default::default(),
-        }
-    }
-}
-
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Metadata {
+    /// Title from front matter, it takes precedence over headings
+    pub title: Option<String>,
     pub icon: String,
     pub is_scratch_pad: bool,
+    pub tags: Vec<String>,
+    pub aliases: Vec<String>,
+    pub created: Option<DateTime<FixedOffset>>,
+    pub updated: Option<DateTime<FixedOffset>>,
+    /// Front matter keys without special meaning, preserved as is
+    pub extra: Mapping,
+    /// Source had front matter block, it is written back even when empty
+    pub has_front_matter: bool,
 }
 
 impl Default for Metadata {
     fn default() -> Self {
         Self {
+            title: None,
             icon: DEFAULT_ICON.to_owned(),
             is_scratch_pad: false,
+            tags: Vec::new(),
+            aliases: Vec::new(),
+            created: None,
+            updated: None,
+            extra: Mapping::new(),
+            has_front_matter: false,
+        }
+    }
+}
+
+impl Metadata {
+    /// Icon is stored in front matter by Phosphor name, glyphs are accepted as well
+    pub fn from_front_matter(front_matter: FrontMatter) -> Self {
+        let icon = front_matter
+            .icon
+            .map(|icon| match phosphor_icons::by_name(&icon) {
+                Some(named) => named.glyph.to_owned(),
+                None => icon,
+            });
+        Self {
+            title: front_matter.title,
+            icon: icon.unwrap_or_else(|| DEFAULT_ICON.to_owned()),
+            tags: front_matter.tags,
+            aliases: front_matter.aliases,
+            created: front_matter.created,
+            updated: front_matter.updated,
+            extra: front_matter.extra,
+            has_front_matter: true,
+            ..Default::default()
+        }
+    }
+
+    fn to_front_matter(&self) -> FrontMatter {
+        FrontMatter {
+            title: self.title.clone(),
+            icon: (self.icon != DEFAULT_ICON).then(|| match phosphor_icons::by_glyph(&self.icon) {
+                Some(named) => named.name.to_owned(),
+                None => self.icon.clone(),
+            }),
+            tags: self.tags.clone(),
+            aliases: self.aliases.clone(),
+            created: self.created,
+            updated: self.updated,
+            extra: self.extra.clone(),
         }
     }
 }
@@ -58,17 +117,299 @@ impl Note {
             ..Default::default()
         }
     }
+
+    /// Parse file contents, malformed front matter is left in text so nothing is lost
+    pub fn from_source(source: String) -> Self {
+        match front_matter::parse(&source) {
+            Ok(Some((front_matter, body))) => Self {
+                text: body.to_owned(),
+                metadata: Metadata::from_front_matter(front_matter),
+            },
+            Ok(None) | Err(_) => Self::from_text(source),
+        }
+    }
+
+    /// File contents, front matter is written only when note had it or has metadata to keep
+    pub fn to_source(&self) -> String {
+        let front_matter = self.metadata.to_front_matter();
+        if !self.metadata.has_front_matter && front_matter == FrontMatter::default() {
+            return self.text.clone();
+        }
+        front_matter::serialize(&front_matter) + &self.text
+    }
+
+    /// Bump update time of notes that keep front matter
+    pub fn touch(&mut self) {
+        if self.metadata.has_front_matter {
+            self.metadata.updated = Some(front_matter::now());
+        }
+    }
+
     pub fn icon(&self) -> &str {
         &self.metadata.icon
     }
 
+    pub fn set_icon(&mut self, icon: String) {
+        self.metadata.icon = icon;
+    }
+
+    /// Front matter title, else the first heading, else the first line of text
     pub fn title(&self) -> Option<&str> {
-        self.text
-            .split_once(char::is_whitespace)
-            .map(|(head, _tail)| head)
+        self.explicit_title()
+            .or_else(|| first_text_line(&self.text))
+    }
+
+    /// Title from front matter or heading, `None` when it would be taken from the first line
+    pub fn explicit_title(&self) -> Option<&str> {
+        self.metadata
+            .title
+            .as_deref()
+            .or_else(|| heading_range(&self.text).map(|range| &self.text[range]))
+    }
+
+    /// Change the title where it comes from, a note without one gets a heading on top
+    pub fn set_title(&mut self, title: &str) {
+        let title = title.replace(['\r', '\n'], " ");
+        if let Some(front_matter_title) = &mut self.metadata.title {
+            *front_matter_title = title;
+            return;
+        }
+        match heading_range(&self.text) {
+            // `#` alone is a heading too, the text needs a space after it
+            Some(range) if range.is_empty() && self.text[..range.start].ends_with('#') => {
+                self.text.insert_str(range.start, &format!(" {title}"));
+            }
+            Some(range) => self.text.replace_range(range, &title),
+            None if title.is_empty() => {}
+            None if self.text.is_empty() => self.text = format!("# {title}\n"),
+            None => self.text.insert_str(0, &format!("# {title}\n\n")),
+        }
+    }
+
+    /// Front matter tags followed by inline ones, each once
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags = Vec::<String>::new();
+        let front_matter = self.metadata.tags.iter().map(|tag| tags::normalize(tag));
+        let inline = tags::inline_tags(&self.text)
+            .into_iter()
+            .map(|(_, tag)| tag);
+        for tag in front_matter.chain(inline) {
+            if !tag.is_empty() && !tags.iter().any(|known| known == tag) {
+                tags.push(tag.to_owned());
+            }
+        }
+        tags
+    }
+
+    /// Rename tag and tags nested in it, both in front matter and text. Returns whether the
+    /// note had any of them
+    pub fn rename_tag(&mut self, from: &str, to: &str) -> bool {
+        let mut changed = false;
+        for tag in &mut self.metadata.tags {
+            if let Some(renamed) = tags::renamed(tags::normalize(tag), from, to) {
+                *tag = renamed;
+                changed = true;
+            }
+        }
+        let inline = tags::inline_tags(&self.text)
+            .into_iter()
+            .filter_map(|(range, tag)| Some((range, tags::renamed(tag, from, to)?)))
+            .collect::<Vec<_>>();
+        for (range, renamed) in inline.into_iter().rev() {
+            self.text.replace_range(range, &renamed);
+            changed = true;
+        }
+        changed
     }
 
     pub fn is_scratch_pad(&self) -> bool {
         self.metadata.is_scratch_pad
     }
 }
+
+/// First non-empty line outside of code blocks, fences and front matter delimiters are not text
+fn first_text_line(text: &str) -> Option<&str> {
+    let mut in_code_block = false;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with("```") || line.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block || line.is_empty() || line == "---" {
+            continue;
+        }
+        return Some(line);
+    }
+    None
+}
+
+/// Byte range of the text of the first ATX heading outside of code blocks
+fn heading_range(text: &str) -> Option<Range<usize>> {
+    let mut offset = 0;
+    let mut in_code_block = false;
+    for line in text.split_inclusive('\n') {
+        let line_start = offset;
+        offset += line.len();
+        let line = line.trim_end_matches(['\r', '\n']);
+        let indented = line.trim_start_matches(' ');
+        if line.len() - indented.len() > 3 {
+            continue;
+        }
+        if indented.starts_with("```") || indented.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+        let level = indented.len() - indented.trim_start_matches('#').len();
+        let rest = &indented[level..];
+        if !(1..=6).contains(&level) || !(rest.is_empty() || rest.starts_with([' ', '\t'])) {
+            continue;
+        }
+        let content = rest.trim();
+        // Closing `#`s are not part of heading text when separated by space
+        let unclosed = content.trim_end_matches('#');
+        let content = if unclosed.is_empty() || unclosed.ends_with([' ', '\t']) {
+            unclosed.trim_end()
+        } else {
+            content
+        };
+        let rest_start = line_start + (line.len() - rest.len());
+        // Typed title goes right after the space, before any closing `#`s
+        let start = if content.is_empty() {
+            rest_start + rest.len().min(1)
+        } else {
+            rest_start + (rest.len() - rest.trim_start().len())
+        };
+        return Some(start..start + content.len());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_is_untouched() {
+        let source = "# Title\n\ntext\n".to_owned();
+        let note = Note::from_source(source.clone());
+        assert_eq!(note.text, source);
+        assert_eq!(note.to_source(), source);
+    }
+
+    #[test]
+    fn malformed_front_matter_stays_in_text() {
+        let source = "---\nicon: [note\n---\ntext\n".to_owned();
+        let note = Note::from_source(source.clone());
+        assert!(!note.metadata.has_front_matter);
+        assert_eq!(note.to_source(), source);
+    }
+
+    #[test]
+    fn front_matter_goes_to_metadata() {
+        let source = "---\nicon: x\ntags:\n- a\nkey: value\n---\ntext\n".to_owned();
+        let note = Note::from_source(source.clone());
+        assert_eq!(note.text, "text\n");
+        assert_eq!(note.icon(), phosphor_icons::X);
+        assert_eq!(note.metadata.tags, ["a"]);
+        assert_eq!(note.to_source(), source);
+    }
+
+    #[test]
+    fn icon_is_stored_by_name() {
+        let mut note = Note::from_text("text".to_owned());
+        note.set_icon(phosphor_icons::ACORN.to_owned());
+        assert_eq!(note.to_source(), "---\nicon: acorn\n---\ntext");
+        assert_eq!(
+            Note::from_source(note.to_source()).icon(),
+            phosphor_icons::ACORN
+        );
+
+        let glyph = Note::from_source(format!("---\nicon: {}\n---\n", phosphor_icons::X));
+        assert_eq!(glyph.icon(), phosphor_icons::X);
+    }
+
+    #[test]
+    fn title_precedence() {
+        let note = Note::from_source("---\ntitle: Front\n---\n# Heading\n".to_owned());
+        assert_eq!(note.title(), Some("Front"));
+        let note = Note::from_text("Intro\n\n## Meeting notes ##\n# Later\n".to_owned());
+        assert_eq!(note.title(), Some("Meeting notes"));
+        let note = Note::from_text("```\n# comment\n```\n\n  Meeting notes\n".to_owned());
+        assert_eq!(note.title(), Some("Meeting notes"));
+        assert_eq!(note.explicit_title(), None);
+        assert_eq!(
+            Note::from_text("\n  first line \nsecond".to_owned()).title(),
+            Some("first line")
+        );
+        assert_eq!(
+            Note::from_text("---\n\n~~~\ncode\n~~~\nafter code".to_owned()).title(),
+            Some("after code")
+        );
+        assert_eq!(
+            Note::from_text("```\ncode only\n```".to_owned()).title(),
+            None
+        );
+        assert_eq!(Note::default().title(), None);
+    }
+
+    #[test]
+    fn title_is_set_where_it_comes_from() {
+        let mut note = Note::from_source("---\ntitle: Front\n---\ntext".to_owned());
+        note.set_title("New");
+        assert_eq!(note.to_source(), "---\ntitle: New\n---\ntext");
+
+        let mut note = Note::from_text("text\n## Old ##\n".to_owned());
+        note.set_title("New\ntitle");
+        assert_eq!(note.text, "text\n## New title ##\n");
+        note.set_title("");
+        assert_eq!(note.explicit_title(), Some(""));
+        note.set_title("Again");
+        assert_eq!(note.text, "text\n## Again ##\n");
+
+        let mut note = Note::from_text("#\ntext".to_owned());
+        note.set_title("Bare");
+        assert_eq!(note.text, "# Bare\ntext");
+
+        let mut note = Note::from_text("text".to_owned());
+        note.set_title("Added");
+        assert_eq!(note.text, "# Added\n\ntext");
+        let mut note = Note::default();
+        note.set_title("Added");
+        assert_eq!(note.text, "# Added\n");
+    }
+
+    #[test]
+    fn tags_from_front_matter_and_text() {
+        let mut note = Note::from_source(
+            "---\ntags: [\"#project\", work]\n---\n#work on #project/alpha\n".to_owned(),
+        );
+        assert_eq!(note.tags(), ["project", "work", "project/alpha"]);
+
+        assert!(note.rename_tag("project", "client"));
+        assert!(!note.rename_tag("missing", "other"));
+        assert_eq!(
+            note.to_source(),
+            "---\ntags:\n- client\n- work\n---\n#work on #client/alpha\n"
+        );
+    }
+
+    #[test]
+    fn metadata_adds_front_matter() {
+        let mut note = Note::from_text("text".to_owned());
+        note.metadata.tags.push("a".to_owned());
+        assert_eq!(note.to_source(), "---\ntags:\n- a\n---\ntext");
+    }
+
+    #[test]
+    fn written_source_reads_back_the_same() {
+        let mut note =
+            Note::from_source("---\ncreated: 2024-03-01\nkey: [1, 2]\n---\ntext".to_owned());
+        note.touch();
+        let source = note.to_source();
+        assert_eq!(Note::from_source(source.clone()).to_source(), source);
+    }
+}
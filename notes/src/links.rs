@@ -0,0 +1,265 @@
+//! Wiki links between notes, `[[Note name]]`, `[[folder/note|alias]]` or `[[note#heading]]`.
+//! Targets are names or paths of notes, resolving them needs the vault. Markdown links to
+//! local files, `[text](../My%20note.md)`, have a URL relative to the note
+
+use std::ops::Range;
+
+use crate::code::prose_ranges;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WikiLink<'a> {
+    /// Byte range of the whole link, brackets included
+    pub range: Range<usize>,
+    /// Byte range of the target
+    pub target_range: Range<usize>,
+    pub target: &'a str,
+    pub heading: Option<&'a str>,
+    pub alias: Option<&'a str>,
+}
+
+impl WikiLink<'_> {
+    /// Text the link is shown with
+    pub fn label(&self) -> &str {
+        self.alias.unwrap_or(self.target)
+    }
+}
+
+/// Wiki links in text, code blocks and code spans have none
+pub fn wiki_links(text: &str) -> Vec<WikiLink<'_>> {
+    let mut links = Vec::new();
+    for segment in prose_ranges(text) {
+        let mut index = segment.start;
+        while let Some(found) = text[index..segment.end].find("[[") {
+            let open = index + found;
+            let inner_start = open + 2;
+            let Some(inner_len) = text[inner_start..segment.end].find("]]") else {
+                break;
+            };
+            match parse_link(text, inner_start..inner_start + inner_len) {
+                Some(link) => {
+                    index = link.range.end;
+                    links.push(link);
+                }
+                // `[[[note]]` links from the next bracket
+                None => index = open + 1,
+            }
+        }
+    }
+    links
+}
+
+fn parse_link(text: &str, inner: Range<usize>) -> Option<WikiLink<'_>> {
+    let content = &text[inner.clone()];
+    if content.contains(['[', ']']) {
+        return None;
+    }
+    let (destination, alias) = match content.split_once('|') {
+        Some((destination, alias)) => (destination, Some(alias.trim())),
+        None => (content, None),
+    };
+    let (target, heading) = match destination.split_once('#') {
+        Some((target, heading)) => (target, Some(heading.trim())),
+        None => (destination, None),
+    };
+    let target_start = inner.start + (target.len() - target.trim_start().len());
+    let target = target.trim();
+    if target.is_empty() {
+        return None;
+    }
+    Some(WikiLink {
+        range: inner.start - 2..inner.end + 2,
+        target_range: target_start..target_start + target.len(),
+        target,
+        heading: heading.filter(|heading| !heading.is_empty()),
+        alias: alias.filter(|alias| !alias.is_empty()),
+    })
+}
+
+/// Markdown link or image with a relative URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarkdownLink<'a> {
+    /// Byte range of the URL
+    pub url_range: Range<usize>,
+    pub url: &'a str,
+}
+
+/// Links and images to local files in text, `[text](url)` or `![alt](url)`. Links to
+/// websites and to headings of the same note are left out
+pub fn markdown_links(text: &str) -> Vec<MarkdownLink<'_>> {
+    let mut links = Vec::new();
+    for segment in prose_ranges(text) {
+        let mut index = segment.start;
+        while let Some(found) = text[index..segment.end].find("](") {
+            let start = index + found + 2;
+            // URL ends before the closing parenthesis or a title
+            let Some(len) = text[start..segment.end].find(|c: char| c == ')' || c.is_whitespace())
+            else {
+                break;
+            };
+            let url = &text[start..start + len];
+            index = start + len;
+            if !url.is_empty() && !url.starts_with(['#', '<']) && !is_external(url) {
+                links.push(MarkdownLink {
+                    url_range: start..start + len,
+                    url,
+                });
+            }
+        }
+    }
+    links
+}
+
+pub fn is_external(url: &str) -> bool {
+    url.contains("://") || url.starts_with("mailto:")
+}
+
+/// Path part of relative URL with `%XX` escapes decoded, e.g. `My%20note#part` is `My note`
+pub fn decode_local_url(url: &str) -> String {
+    percent_decode(url.split(['#', '?']).next().unwrap_or_default())
+}
+
+/// Text with `%XX` escapes decoded, `%` without two hex digits after it is kept
+pub fn percent_decode(text: &str) -> String {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let [byte, tail @ ..] = rest {
+        let escaped = match tail {
+            [high, low, ..] if *byte == b'%' => std::str::from_utf8(&[*high, *low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+        match escaped {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(*byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Relative path as URL, characters that end or break a link are escaped
+pub fn encode_local_path(path: &str) -> String {
+    let mut url = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            ' ' | '%' | '(' | ')' | '#' | '?' | '<' | '>' => {
+                url.push_str(&format!("%{:02X}", c as u32))
+            }
+            c => url.push(c),
+        }
+    }
+    url
+}
+
+/// Link at the byte offset
+pub fn link_at(text: &str, offset: usize) -> Option<WikiLink<'_>> {
+    wiki_links(text)
+        .into_iter()
+        .find(|link| link.range.contains(&offset))
+}
+
+/// Target of a link being typed before the cursor, after `[[` and not closed yet. Byte range
+/// of what is typed so far
+pub fn typed_target(text: &str, cursor: usize) -> Option<Range<usize>> {
+    let line_start = text[..cursor].rfind('\n').map_or(0, |index| index + 1);
+    let open = line_start + text[line_start..cursor].rfind("[[")?;
+    let start = open + 2;
+    let typed = &text[start..cursor];
+    if typed.contains(['[', ']', '|', '#']) {
+        return None;
+    }
+    let in_prose = prose_ranges(text)
+        .iter()
+        .any(|range| range.start <= open && cursor <= range.end);
+    in_prose.then_some(start..cursor)
+}
+
+/// Put the target in place of the one being typed before the cursor and close the link.
+/// Returns the byte offset after the link
+pub fn complete_target(text: &mut String, cursor: usize, target: &str) -> Option<usize> {
+    let typed = typed_target(text, cursor)?;
+    // Brackets may be closed already, `[[|]]`
+    let closing = if text[cursor..].starts_with("]]") {
+        typed.start..cursor + 2
+    } else {
+        typed.start..cursor
+    };
+    text.replace_range(closing, &format!("{target}]]"));
+    Some(typed.start + target.len() + 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links_in_text() {
+        let text =
+            "See [[Note]], [[ folder/note | alias ]] and [[other#Part]].\n`[[code]]` [[]] [[[x]]";
+        let links = wiki_links(text);
+        let targets = links.iter().map(|link| link.target).collect::<Vec<_>>();
+        assert_eq!(targets, ["Note", "folder/note", "other", "x"]);
+
+        assert_eq!(&text[links[0].range.clone()], "[[Note]]");
+        assert_eq!(&text[links[1].target_range.clone()], "folder/note");
+        assert_eq!(links[1].label(), "alias");
+        assert_eq!(links[2].heading, Some("Part"));
+        assert_eq!(links[2].label(), "other");
+        assert_eq!(link_at(text, 6).map(|link| link.target), Some("Note"));
+        assert_eq!(link_at(text, 2), None);
+    }
+
+    #[test]
+    fn target_being_typed() {
+        let text = "done [[a]] and [[fol";
+        assert_eq!(typed_target(text, text.len()), Some(17..20));
+        assert_eq!(typed_target(text, 10), None);
+        assert_eq!(typed_target("[[a|b", 5), None);
+        assert_eq!(typed_target("`[[a`", 4), None);
+        assert_eq!(typed_target("[[\nb", 4), None);
+        assert_eq!(typed_target("[[", 2), Some(2..2));
+    }
+
+    #[test]
+    fn typed_target_is_completed() {
+        let mut text = "see [[no and more".to_owned();
+        assert_eq!(complete_target(&mut text, 8, "dir/note"), Some(16));
+        assert_eq!(text, "see [[dir/note]] and more");
+
+        let mut text = "[[x]]".to_owned();
+        assert_eq!(complete_target(&mut text, 3, "xyz"), Some(7));
+        assert_eq!(text, "[[xyz]]");
+        assert_eq!(complete_target(&mut text, 7, "a"), None);
+    }
+
+    #[test]
+    fn local_markdown_links() {
+        let text = "[a](My%20note.md) ![i](img/x.png \"title\") [w](https://x.org) [h](#part)\n\
+                    `[c](code)` [d](../d.md#part)";
+        let urls = markdown_links(text)
+            .into_iter()
+            .map(|link| link.url)
+            .collect::<Vec<_>>();
+        assert_eq!(urls, ["My%20note.md", "img/x.png", "../d.md#part"]);
+        assert_eq!(
+            &text[markdown_links(text)[0].url_range.clone()],
+            "My%20note.md"
+        );
+
+        assert_eq!(decode_local_url("My%20note"), "My note");
+        assert_eq!(decode_local_url("dir/note#heading"), "dir/note");
+        assert_eq!(
+            decode_local_url("%D0%B7%D0%B0%D0%BC%D0%B5%D1%82%D0%BA%D0%B0"),
+            "заметка"
+        );
+        assert_eq!(decode_local_url("100%"), "100%");
+        assert_eq!(encode_local_path("a b/(1)%.md"), "a%20b/%281%29%25.md");
+        assert_eq!(decode_local_url(&encode_local_path("a b#?.md")), "a b#?.md");
+    }
+}
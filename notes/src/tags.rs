@@ -0,0 +1,124 @@
+//! Tags from front matter and inline `#tag` syntax. Tags nest with `/`, `#project/alpha` is
+//! within `project`
+
+use std::ops::Range;
+
+use crate::code::prose_ranges;
+
+/// Characters after `#` that start a tag, e.g. `(#tag)` or `text, #tag`
+const OPENING_PUNCTUATION: &[char] = &['(', '[', '{', ',', ';', ':', '!', '?', '"', '\''];
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '/'
+}
+
+/// Tag name without `#`, nonempty segments, not a number like `#123`
+pub fn is_valid_tag(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(is_tag_char)
+        && name.split('/').all(|segment| !segment.is_empty())
+        && !name.chars().all(|c| c.is_ascii_digit() || c == '/')
+}
+
+/// Front matter allows `#tag` as well as `tag`
+pub fn normalize(tag: &str) -> &str {
+    tag.trim().trim_start_matches('#')
+}
+
+/// Tag is the same as `parent` or nested in it
+pub fn is_within(tag: &str, parent: &str) -> bool {
+    tag.strip_prefix(parent)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Tag after renaming `from` to `to`, nested tags move along. `None` when tag is not affected
+pub fn renamed(tag: &str, from: &str, to: &str) -> Option<String> {
+    is_within(tag, from).then(|| format!("{to}{}", &tag[from.len()..]))
+}
+
+/// Inline tags with byte ranges of their names, `#` excluded. Code blocks and code spans have
+/// no tags
+pub fn inline_tags(text: &str) -> Vec<(Range<usize>, &str)> {
+    let mut tags = Vec::new();
+    for range in prose_ranges(text) {
+        segment_tags(text, range, &mut tags);
+    }
+    tags.into_iter()
+        .map(|range: Range<usize>| (range.clone(), &text[range]))
+        .collect()
+}
+
+fn segment_tags(text: &str, segment: Range<usize>, tags: &mut Vec<Range<usize>>) {
+    let mut index = segment.start;
+    // Line break before the segment counts as whitespace, closing backtick does not
+    let mut previous = text[..index].chars().next_back();
+    while let Some(c) = text[index..segment.end].chars().next() {
+        let starts_tag = c == '#'
+            && previous.is_none_or(|p| p.is_whitespace() || OPENING_PUNCTUATION.contains(&p));
+        if starts_tag {
+            let name_start = index + 1;
+            let name_len = text[name_start..segment.end]
+                .find(|c: char| !is_tag_char(c))
+                .unwrap_or(segment.end - name_start);
+            let mut name = &text[name_start..name_start + name_len];
+            // `#a//b` is `#a`, trailing `/` is punctuation
+            if let Some(end) = name.find("//") {
+                name = &name[..end];
+            }
+            name = name.trim_end_matches('/');
+            if is_valid_tag(name) {
+                tags.push(name_start..name_start + name.len());
+            }
+            index = name_start + name_len;
+            previous = text[..index].chars().next_back();
+            continue;
+        }
+        previous = Some(c);
+        index += c.len_utf8();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(text: &str) -> Vec<&str> {
+        inline_tags(text)
+            .into_iter()
+            .map(|(_, name)| name)
+            .collect()
+    }
+
+    #[test]
+    fn inline_tags_in_text() {
+        assert_eq!(
+            names("#start, text #project/alpha (#paren) and #trailing/.\n#last"),
+            ["start", "project/alpha", "paren", "trailing", "last"]
+        );
+        assert_eq!(
+            names("# Heading\nissue#1 #123 url.com/#anchor a##b &#x27; #a//b"),
+            ["a"]
+        );
+    }
+
+    #[test]
+    fn code_has_no_tags() {
+        let text = "`#span` ``a ` #span``\n```\n#block\n```\n#after `#unclosed";
+        assert_eq!(names(text), ["after"]);
+        let (range, _) = &inline_tags(text)[0];
+        assert_eq!(&text[range.clone()], "after");
+    }
+
+    #[test]
+    fn nested_tags_rename_along() {
+        assert!(is_within("project/alpha", "project"));
+        assert!(!is_within("projects", "project"));
+        assert_eq!(
+            renamed("project/alpha", "project", "work").as_deref(),
+            Some("work/alpha")
+        );
+        assert_eq!(renamed("projects", "project", "work"), None);
+        assert!(!is_valid_tag("2024/01"));
+        assert!(is_valid_tag("y2024/q1"));
+    }
+}
@@ -0,0 +1,118 @@
+//! Generate icon table from ligatures of the bundled Phosphor font.
+//!
+//! The font maps every icon name, typed as a ligature, to its glyph,
+//! so GSUB ligature substitutions give the full `name -> codepoint` table.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+
+use ttf_parser::gsub::SubstitutionSubtable;
+use ttf_parser::{Face, GlyphId};
+
+/// Font bundled with the application, relative to this crate
+const FONT_PATH: &str = "../assets/fonts/Phosphor.ttf";
+
+/// Icons with single letter names, which cannot be typed as ligatures
+const SINGLE_LETTER_ICONS: &[(&str, u32)] = &[("x", 0xE4F6)];
+
+fn main() {
+    let font_path = Path::new(env!("CARGO_MANIFEST_DIR")).join(FONT_PATH);
+    println!("cargo::rerun-if-changed={}", font_path.display());
+    println!("cargo::rerun-if-changed=build.rs");
+
+    let data = std::fs::read(&font_path).expect("Phosphor font");
+    let face = Face::parse(&data, 0).expect("valid Phosphor font");
+
+    let mut icons = ligature_icons(&face);
+    icons.extend(
+        SINGLE_LETTER_ICONS
+            .iter()
+            .map(|(name, codepoint)| (name.to_string(), *codepoint)),
+    );
+
+    let mut out = String::new();
+    for (name, codepoint) in &icons {
+        writeln!(
+            out,
+            "pub const {}: &str = \"\\u{{{codepoint:X}}}\";",
+            const_name(name)
+        )
+        .unwrap();
+    }
+    writeln!(
+        out,
+        "\n/// All icons sorted by name\npub static ICONS: &[Icon] = &["
+    )
+    .unwrap();
+    for name in icons.keys() {
+        writeln!(
+            out,
+            "    Icon {{ name: {name:?}, glyph: {} }},",
+            const_name(name)
+        )
+        .unwrap();
+    }
+    writeln!(out, "];").unwrap();
+
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    std::fs::write(Path::new(&out_dir).join("icons.rs"), out).unwrap();
+}
+
+/// Icon name to codepoint, built from ligatures spelling the name
+fn ligature_icons(face: &Face) -> BTreeMap<String, u32> {
+    let mut glyph_chars = HashMap::new();
+    for subtable in face.tables().cmap.expect("cmap table").subtables {
+        if !subtable.is_unicode() {
+            continue;
+        }
+        subtable.codepoints(|codepoint| {
+            if let Some(glyph) = subtable.glyph_index(codepoint) {
+                glyph_chars.entry(glyph).or_insert(codepoint);
+            }
+        });
+    }
+
+    let mut icons = BTreeMap::new();
+    let gsub = face.tables().gsub.expect("GSUB table");
+    for lookup in gsub.lookups {
+        for subtable in lookup.subtables.into_iter::<SubstitutionSubtable>() {
+            let SubstitutionSubtable::Ligature(ligatures) = subtable else {
+                continue;
+            };
+            let first_glyphs = (0..face.number_of_glyphs())
+                .map(GlyphId)
+                .filter_map(|glyph| Some((ligatures.coverage.get(glyph)?, glyph)))
+                .collect::<HashMap<u16, GlyphId>>();
+            for (index, set) in ligatures.ligature_sets.into_iter().enumerate() {
+                let Some(first) = first_glyphs.get(&(index as u16)) else {
+                    continue;
+                };
+                for ligature in set {
+                    let name = std::iter::once(*first)
+                        .chain(ligature.components)
+                        .map(|glyph| glyph_chars.get(&glyph).and_then(|c| char::from_u32(*c)))
+                        .collect::<Option<String>>();
+                    let codepoint = glyph_chars.get(&ligature.glyph);
+                    if let (Some(name), Some(codepoint)) = (name, codepoint)
+                        && is_icon_name(&name)
+                    {
+                        icons.insert(name, *codepoint);
+                    }
+                }
+            }
+        }
+    }
+    icons
+}
+
+fn is_icon_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+fn const_name(name: &str) -> String {
+    name.to_ascii_uppercase().replace('-', "_")
+}
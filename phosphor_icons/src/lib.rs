@@ -1,13 +1,29 @@
 /// Phoshpor icon font icons symbols
-pub const SIDEBAR: &str = "\u{EAB6}";
-pub const LIST_DASHES: &str = "\u{E2F4}";
-pub const CARDS: &str = "\u{E0F8}";
-pub const WRENCH: &str = "\u{E5D4}";
-pub const X: &str = "\u{E4F6}";
-pub const NOTE: &str = "\u{E348}";
-// pub const NOTE_PENCIL: &str = "\u{E34C}";
-pub const PENCIL_LINE: &str = "\u{E3B2}";
-pub const TRASH: &str = "\u{E4A6}";
-pub const PLUS: &str = "\u{E3D4}";
-pub const INFO: &str = "\u{E2CE}";
-pub const ARROW_CCW: &str = "\u{E038}";
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Icon {
+    /// Name as in Phosphor catalog, e.g. `arrow-counter-clockwise`
+    pub name: &'static str,
+    pub glyph: &'static str,
+}
+
+include!(concat!(env!("OUT_DIR"), "/icons.rs"));
+
+pub const ARROW_CCW: &str = ARROW_COUNTER_CLOCKWISE;
+pub const ARROW_CW: &str = ARROW_CLOCKWISE;
+
+pub fn by_name(name: &str) -> Option<&'static Icon> {
+    ICONS
+        .binary_search_by(|icon| icon.name.cmp(name))
+        .ok()
+        .map(|index| &ICONS[index])
+}
+
+pub fn by_glyph(glyph: &str) -> Option<&'static Icon> {
+    ICONS.iter().find(|icon| icon.glyph == glyph)
+}
+
+/// Icons whose name contains the query, case insensitive
+pub fn search(query: &str) -> impl Iterator<Item = &'static Icon> {
+    let query = query.trim().to_lowercase().replace(' ', "-");
+    ICONS.iter().filter(move |icon| icon.name.contains(&query))
+}
@@ -0,0 +1,2952 @@
+use std::any::Any;
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Receiver, Sender, channel};
+use std::time::{Duration, Instant};
+
+use notes::{DEFAULT_ICON, Note};
+use notify::EventKind;
+
+use crate::LocationConfig;
+use crate::data::{DataNode, Directory, FileMetadata, Trash};
+use crate::files::{self, Notes};
+use crate::fs::{self, Fs, RealFs, Wake};
+use crate::git_history;
+use crate::graph::{Graph, GraphLayout};
+use crate::history::Revision;
+use crate::index::VaultIndex;
+use crate::links::NoteNames;
+use crate::relink::{self, RelinkUndo, RenamePlan};
+use crate::snapshots::{Retention, Snapshots};
+use crate::tabs::{Tab, Tabs};
+use crate::thread_pool::ThreadPoolExecutor;
+use crate::trash;
+
+#[derive(Debug, Default)]
+pub struct FileMemory {
+    pub dirs: HashMap<PathBuf, MemoryCell<DataNode<Directory>>>,
+    pub notes: HashMap<PathBuf, MemoryCell<DataNode<Note>>>,
+    pub metadata: HashMap<PathBuf, MemoryCell<FileMetadata>>,
+    pub trash: HashMap<PathBuf, MemoryCell<Trash>>,
+    /// Search, tag and link indexes per base directory
+    pub index: HashMap<PathBuf, MemoryCell<VaultIndex>>,
+    /// Source of notes as last read from or written to disk, used to tell own writes from
+    /// external ones and as merge base
+    pub disk_sources: HashMap<PathBuf, String>,
+    /// Notes changed on disk while they had unsaved edits
+    pub conflicts: HashMap<PathBuf, Conflict>,
+    /// Previous versions of notes, newest first
+    pub history: HashMap<PathBuf, MemoryCell<Vec<Revision>>>,
+    /// Versions of notes kept by saves, newest first
+    pub snapshots: HashMap<PathBuf, MemoryCell<Vec<Revision>>>,
+    /// Links renames would change, by rename source, until applied or discarded
+    pub rename_plans: HashMap<PathBuf, MemoryCell<RenamePlan>>,
+    /// Layouts of the link graph per base directory
+    pub graphs: HashMap<PathBuf, MemoryCell<GraphLayout>>,
+}
+
+/// Note changed on disk while it had unsaved edits
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    /// Note on disk, `None` when it was deleted
+    pub theirs: Option<Note>,
+}
+
+#[derive(Debug)]
+pub enum ConflictResolution {
+    KeepMine,
+    TakeTheirs,
+    Merged(String),
+}
+
+#[derive(Debug)]
+pub enum MemoryCell<T> {
+    PendingRead,
+    ReadError(io::Error),
+    ValueWriteError(T, io::Error),
+    Value(T),
+}
+
+#[derive(Debug)]
+pub enum MemoryCellState {
+    PendingRead,
+    Ready,
+    Error,
+}
+
+impl MemoryCellState {
+    pub fn state_of_cell<T>(cell: &MemoryCell<T>) -> Self {
+        match cell {
+            MemoryCell::PendingRead => Self::PendingRead,
+            MemoryCell::ReadError(_) | MemoryCell::ValueWriteError(..) => Self::Error,
+            MemoryCell::Value(_) => Self::Ready,
+        }
+    }
+}
+
+impl<T> MemoryCell<T> {
+    pub fn value(&self) -> Option<&T> {
+        match self {
+            Self::Value(value) | Self::ValueWriteError(value, _) => Some(value),
+            Self::PendingRead | Self::ReadError(_) => None,
+        }
+    }
+
+    pub fn value_mut(&mut self) -> Option<&mut T> {
+        match self {
+            Self::Value(value) | Self::ValueWriteError(value, _) => Some(value),
+            Self::PendingRead | Self::ReadError(_) => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&io::Error> {
+        match self {
+            Self::ReadError(err) | Self::ValueWriteError(_, err) => Some(err),
+            Self::PendingRead | Self::Value(_) => None,
+        }
+    }
+
+    /// Attach write error to the value, cells without value are left as is
+    pub fn set_write_error(&mut self, err: io::Error) {
+        *self = match std::mem::replace(self, Self::PendingRead) {
+            Self::Value(value) | Self::ValueWriteError(value, _) => {
+                Self::ValueWriteError(value, err)
+            }
+            cell => cell,
+        };
+    }
+
+    pub fn clear_write_error(&mut self) {
+        *self = match std::mem::replace(self, Self::PendingRead) {
+            Self::ValueWriteError(value, _) => Self::Value(value),
+            cell => cell,
+        };
+    }
+}
+
+#[derive(Debug)]
+pub struct ApplicationState {
+    pub memory: FileMemory,
+    /// Note of the active tab
+    pub current_note_path: PathBuf,
+    pub tabs: Tabs,
+    pub config: ApplicationConfig,
+    /// Name in trash of the last trashed item, until dismissed
+    pub recently_trashed: Option<String>,
+    /// Last rename that rewrote links, until undone or dismissed
+    pub last_relink: Option<RelinkUndo>,
+    /// Failed file operations, until dismissed
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ApplicationConfig {
+    pub location: LocationConfig,
+    pub autosave: bool,
+    /// Saves wait until edits pause for that long
+    pub save_delay: Duration,
+    /// With git history, saves are committed together once they pause for that long
+    pub commit_delay: Duration,
+    /// With snapshots, which versions of a note are kept
+    pub snapshot_retention: Retention,
+}
+
+impl ApplicationConfig {
+    /// Defaults for everything but the location
+    pub fn new(location: LocationConfig) -> Self {
+        Self {
+            location,
+            autosave: true,
+            save_delay: DEFAULT_SAVE_DELAY,
+            commit_delay: DEFAULT_COMMIT_DELAY,
+            snapshot_retention: Default::default(),
+        }
+    }
+
+    /// Where saves keep prior versions of notes, `None` when snapshots are off
+    pub fn snapshots(&self) -> Option<Snapshots> {
+        self.location.snapshots.then(|| Snapshots {
+            base_path: self.location.base_path.to_path_buf(),
+            history_path: self.location.history_path.to_path_buf(),
+            retention: self.snapshot_retention,
+        })
+    }
+}
+
+/// Completed file system operation, applied to memory when polled
+#[derive(Debug)]
+pub enum FileOp {
+    NoteCreated {
+        path: PathBuf,
+        select: bool,
+    },
+    DirCreated {
+        path: PathBuf,
+    },
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+    Trashed {
+        origin: PathBuf,
+        name: String,
+    },
+    Restored {
+        path: PathBuf,
+    },
+    Purged,
+    /// Notes written with changes to their text, e.g. a renamed tag
+    Rewritten(Notes),
+    /// Rename with notes written along, each with the source it replaced
+    Relinked {
+        from: PathBuf,
+        to: PathBuf,
+        notes: Vec<(PathBuf, String, Note)>,
+        undo: Option<RelinkUndo>,
+    },
+}
+
+#[derive(Debug, Default)]
+struct BackgroundTasks {
+    notes: HashMap<PathBuf, Pipe<io::Result<DataNode<Note>>>>,
+    dirs: HashMap<PathBuf, Pipe<io::Result<DataNode<Directory>>>>,
+    saves: HashMap<PathBuf, Pipe<io::Result<SavedNote>>>,
+    file_ops: HashMap<PathBuf, Pipe<io::Result<FileOp>>>,
+    trash: HashMap<PathBuf, Pipe<io::Result<Trash>>>,
+    metadata: HashMap<PathBuf, Pipe<io::Result<FileMetadata>>>,
+    /// Notes to add to the vault index, the whole index when it is pending
+    index: HashMap<PathBuf, Pipe<io::Result<Notes>>>,
+    /// Number of saves sent to executor and not yet polled, per note path
+    pending_saves: HashMap<PathBuf, usize>,
+    /// Saves waiting for edits to settle, per note path
+    scheduled_saves: HashMap<PathBuf, ScheduledSave>,
+    /// Generation of the latest save applied to memory, per note path
+    applied_save_generations: HashMap<PathBuf, u64>,
+    /// Renames waiting for pending saves below their source path
+    deferred_renames: Vec<(PathBuf, PathBuf)>,
+    /// Moves to trash waiting for pending saves below their path
+    deferred_trash: Vec<PathBuf>,
+    rename_plans: HashMap<PathBuf, Pipe<io::Result<RenamePlan>>>,
+    /// Renames with links to rewrite, waiting for pending saves of affected notes
+    deferred_relinks: Vec<RenamePlan>,
+    /// Undo of the last relinking rename, waiting for pending saves of affected notes
+    deferred_relink_undo: Option<RelinkUndo>,
+    /// Notes rewritten by relinking renames sent to executor, by rename source. They are in
+    /// moving paths until the rename is done
+    relinking: HashMap<PathBuf, Vec<PathBuf>>,
+    /// Note on disk together with the save generation it was read at
+    disk_checks: HashMap<PathBuf, Pipe<io::Result<DiskNote>>>,
+    /// Number of saves ever sent to executor, per note path
+    save_generations: HashMap<PathBuf, u64>,
+    /// Notes to check once their pending saves finish
+    deferred_disk_checks: HashSet<PathBuf>,
+    /// Sources of renames and moves to trash sent to executor, their file events are our own
+    /// and saves below them wait for the new path
+    moving_paths: HashSet<PathBuf>,
+    /// Notes with closed tabs waiting for their saves to finish before leaving memory
+    deferred_evictions: HashSet<PathBuf>,
+    history: HashMap<PathBuf, Pipe<io::Result<Vec<Revision>>>>,
+    /// Commits of the base directory, whether there was anything to commit
+    commits: HashMap<PathBuf, Pipe<io::Result<bool>>>,
+    /// When changes are committed unless more saves come first
+    scheduled_commit: Option<Instant>,
+    commit_in_flight: bool,
+    snapshots: HashMap<PathBuf, Pipe<io::Result<Vec<Revision>>>>,
+    graphs: HashMap<PathBuf, Pipe<io::Result<GraphLayout>>>,
+    graph_in_flight: bool,
+}
+
+/// Move map entries at or below `from` to the same relative place below `to`
+fn rekey_paths<V>(map: &mut HashMap<PathBuf, V>, from: &Path, to: &Path) {
+    let keys = map
+        .keys()
+        .filter(|key| key.starts_with(from))
+        .cloned()
+        .collect::<Vec<PathBuf>>();
+    for key in keys {
+        if let (Some(value), Ok(relative)) = (map.remove(&key), key.strip_prefix(from)) {
+            map.insert(to.join(relative), value);
+        }
+    }
+}
+
+type Pipe<T> = (Sender<T>, Receiver<T>);
+
+/// Save generation and the note as it was written
+type SavedNote = (u64, DataNode<Note>);
+
+pub const DEFAULT_SAVE_DELAY: Duration = Duration::from_millis(500);
+
+pub const DEFAULT_COMMIT_DELAY: Duration = Duration::from_secs(30);
+
+/// Revisions listed in note history
+const HISTORY_LIMIT: usize = 100;
+
+/// Continuous typing delays a save at most that long after the first unsaved edit, unless
+/// save delay itself is longer
+const MAX_SAVE_DELAY: Duration = Duration::from_secs(3);
+
+/// Flush waits that long at most for writes in flight before it writes anyway
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy)]
+struct ScheduledSave {
+    first_requested: Instant,
+    due: Instant,
+}
+
+/// Save generation at the time of reading and the note on disk
+type DiskNote = (u64, Option<Note>);
+
+fn pipe_sender<T>(pipes: &mut HashMap<PathBuf, Pipe<T>>, path: &Path) -> Sender<T> {
+    match pipes.entry(path.to_owned()) {
+        Entry::Vacant(entry) => entry.insert(channel()).0.clone(),
+        Entry::Occupied(entry) => entry.into_mut().0.clone(),
+    }
+}
+
+#[derive(Debug)]
+pub struct NonBlockingApplication {
+    state: ApplicationState,
+    fs: Arc<dyn Fs>,
+    executor: ThreadPoolExecutor,
+    background_tasks: BackgroundTasks,
+    /// Watches base directory for changes made by other programs, absent if it failed to start
+    _watcher: Option<Box<dyn Any>>,
+    fs_events: FsEvents,
+    /// Called by the watcher after it sends changes
+    wake: Wake,
+    /// Bumped when location changes, reads of the previous location still queued are skipped
+    location_epoch: Arc<AtomicU64>,
+}
+
+type FsEvents = Receiver<notify::Result<notify::Event>>;
+
+impl NonBlockingApplication {
+    pub fn init(config: ApplicationConfig) -> io::Result<Self> {
+        Self::with_fs(config, Arc::new(RealFs), Default::default())
+    }
+
+    /// Application on another file system, e.g. an in-memory one with an inline executor in
+    /// tests
+    pub fn with_fs(
+        config: ApplicationConfig,
+        fs: Arc<dyn Fs>,
+        executor: ThreadPoolExecutor,
+    ) -> io::Result<Self> {
+        config.location.prepare(&*fs)?;
+        let wake = Wake::default();
+        let (watcher, fs_events) = Self::watch(&*fs, &config.location.base_path, wake.clone());
+        let scratch_pad_path = config.location.scratch_pad_path.to_path_buf();
+
+        Ok(Self {
+            state: ApplicationState {
+                memory: Default::default(),
+                tabs: Tabs::from_tabs([Tab {
+                    path: scratch_pad_path.clone(),
+                    pinned: false,
+                }]),
+                current_note_path: scratch_pad_path,
+                config,
+                recently_trashed: None,
+                last_relink: None,
+                errors: Vec::new(),
+            },
+            fs,
+            executor,
+            background_tasks: Default::default(),
+            _watcher: watcher,
+            fs_events,
+            wake,
+            location_epoch: Default::default(),
+        })
+    }
+
+    fn watch(fs: &dyn Fs, base_path: &Path, wake: Wake) -> (Option<Box<dyn Any>>, FsEvents) {
+        let (fs_events_tx, fs_events) = channel();
+        let watcher = fs
+            .watch(base_path, fs::FsEvents::new(fs_events_tx, wake))
+            .inspect_err(|err| log::warn!("External changes will not be detected: {err}"))
+            .ok();
+        (watcher, fs_events)
+    }
+
+    /// Called when other programs change files, so that changes are polled without waiting for
+    /// input. Watching starts again with it
+    pub fn set_wake(&mut self, wake: Wake) {
+        self.wake = wake;
+        let (watcher, fs_events) = Self::watch(&*self.fs, self.base_dir_path(), self.wake.clone());
+        self._watcher = watcher;
+        self.fs_events = fs_events;
+    }
+
+    /// Open another location in place of the current one. Writes in flight land and unsaved
+    /// notes are written first, whatever autosave is, then memory and background tasks are
+    /// dropped. Location stays when a note cannot be saved
+    pub fn switch_location(&mut self, location: LocationConfig) -> io::Result<()> {
+        location.prepare(&*self.fs)?;
+        self.flush_saves()
+            .map_err(|err| io::Error::new(err.kind(), format!("Notes were not saved, {err}")))?;
+        self.flush_commit();
+        self.location_epoch.fetch_add(1, Ordering::Relaxed);
+
+        let (watcher, fs_events) = Self::watch(&*self.fs, &location.base_path, self.wake.clone());
+        self._watcher = watcher;
+        self.fs_events = fs_events;
+        self.background_tasks = Default::default();
+        self.state.memory = Default::default();
+        self.state.tabs = Default::default();
+        self.state.recently_trashed = None;
+        self.state.last_relink = None;
+        self.state.config.location = location;
+        self.open_note(self.scratch_pad_path().to_path_buf(), false);
+        Ok(())
+    }
+
+    pub fn current_note_path(&self) -> &Path {
+        &self.state.current_note_path
+    }
+
+    pub fn tabs(&self) -> &Tabs {
+        &self.state.tabs
+    }
+
+    /// Show note in a tab, opened for preview it takes the place of the preview tab
+    pub fn open_note(&mut self, path: PathBuf, pinned: bool) {
+        let replaced = self
+            .state
+            .tabs
+            .open(&path, pinned, Some(&self.state.current_note_path));
+        self.read_note_in_background(&path);
+        self.state.current_note_path = path;
+        if let Some(replaced) = replaced {
+            self.evict_note(&replaced);
+        }
+    }
+
+    /// Replace tabs, e.g. with the ones of a previous session, tabs of notes deleted since
+    /// close once their read fails
+    pub fn restore_tabs(&mut self, tabs: Vec<Tab>, current_note_path: PathBuf) {
+        let previous = std::mem::replace(&mut self.state.tabs, Tabs::from_tabs(tabs));
+        self.open_note(current_note_path, false);
+        for tab in previous.iter() {
+            self.evict_note(&tab.path);
+        }
+    }
+
+    pub fn pin_tab(&mut self, path: &Path) {
+        self.state.tabs.pin(path);
+    }
+
+    pub fn move_tab(&mut self, from: usize, to: usize) {
+        self.state.tabs.move_tab(from, to);
+    }
+
+    /// Unsaved edits are saved first. Closing the last tab shows scratch pad
+    pub fn close_tab(&mut self, path: &Path) {
+        let neighbor = self.state.tabs.neighbor(path).map(Path::to_path_buf);
+        if !self.state.tabs.close(path) {
+            return;
+        }
+        if self.note_is_dirty(path) {
+            self.save_note_now(path);
+        }
+        if self.state.current_note_path == path {
+            match neighbor {
+                Some(neighbor) => self.open_note(neighbor, false),
+                None => self.open_note(self.scratch_pad_path().to_path_buf(), false),
+            }
+        }
+        self.evict_note(path);
+    }
+
+    /// Close tabs of notes gone from disk, without saving them
+    fn close_tabs_below(&mut self, path: &Path) {
+        for tab_path in self.state.tabs.below(path) {
+            self.forget_path(&tab_path);
+            self.close_tab(&tab_path);
+        }
+    }
+
+    /// Drop note without a tab from memory, once its edits are saved
+    fn evict_note(&mut self, path: &Path) {
+        if self.state.tabs.contains(path) {
+            return;
+        }
+        let tasks = &self.background_tasks;
+        if self.note_is_dirty(path)
+            || self.conflict(path).is_some()
+            || tasks.scheduled_saves.contains_key(path)
+            || tasks.pending_saves.contains_key(path)
+        {
+            self.background_tasks
+                .deferred_evictions
+                .insert(path.to_path_buf());
+            return;
+        }
+        let memory = &mut self.state.memory;
+        memory.notes.remove(path);
+        memory.metadata.remove(path);
+        memory.disk_sources.remove(path);
+        memory.history.remove(path);
+        memory.snapshots.remove(path);
+        self.background_tasks.notes.remove(path);
+        self.background_tasks.metadata.remove(path);
+        self.background_tasks.disk_checks.remove(path);
+        self.background_tasks.history.remove(path);
+        self.background_tasks.snapshots.remove(path);
+    }
+
+    fn run_deferred_evictions(&mut self) {
+        if self.background_tasks.deferred_evictions.is_empty() {
+            return;
+        }
+        for path in std::mem::take(&mut self.background_tasks.deferred_evictions) {
+            self.evict_note(&path);
+        }
+    }
+
+    pub fn autosave(&self) -> bool {
+        self.state.config.autosave
+    }
+
+    pub fn set_autosave(&mut self, autosave: bool, save_delay: Duration) {
+        self.state.config.autosave = autosave;
+        self.state.config.save_delay = save_delay;
+    }
+
+    pub fn set_snapshot_retention(&mut self, retention: Retention) {
+        self.state.config.snapshot_retention = retention;
+    }
+
+    pub fn base_dir_path(&self) -> &Path {
+        &self.state.config.location.base_path
+    }
+
+    pub fn scratch_pad_path(&self) -> &Path {
+        &self.state.config.location.scratch_pad_path
+    }
+
+    pub fn trash_path(&self) -> &Path {
+        &self.state.config.location.trash_path
+    }
+
+    pub fn base_dir(&self) -> Option<&DataNode<Directory>> {
+        self.get_dir(self.base_dir_path())
+    }
+
+    pub fn get_note(&self, path: &Path) -> Option<&DataNode<Note>> {
+        self.get_note_cell(path).and_then(MemoryCell::value)
+    }
+
+    pub fn get_note_cell(&self, path: &Path) -> Option<&MemoryCell<DataNode<Note>>> {
+        self.state.memory.notes.get(path)
+    }
+
+    pub fn note_state(&self, path: &Path) -> Option<MemoryCellState> {
+        self.state
+            .memory
+            .notes
+            .get(path)
+            .map(MemoryCellState::state_of_cell)
+    }
+
+    pub fn scratch_pad(&self) -> Option<&DataNode<Note>> {
+        self.get_note(self.scratch_pad_path())
+    }
+
+    pub fn scratch_pad_mut(&mut self) -> Option<&mut DataNode<Note>> {
+        let path = Rc::clone(&self.state.config.location.scratch_pad_path);
+        self.get_note_mut(&path)
+    }
+
+    pub fn note_is_pending(&self, path: &Path) -> bool {
+        self.state
+            .memory
+            .notes
+            .get(path)
+            .and_then(MemoryCell::value)
+            .is_none()
+    }
+
+    pub fn get_note_mut(&mut self, path: &Path) -> Option<&mut DataNode<Note>> {
+        self.state
+            .memory
+            .notes
+            .get_mut(path)
+            .and_then(MemoryCell::value_mut)
+    }
+
+    pub fn get_dir(&self, path: &Path) -> Option<&DataNode<Directory>> {
+        self.state.memory.dirs.get(path).and_then(MemoryCell::value)
+    }
+
+    pub fn poll_background_tasks(&mut self) {
+        self.poll_dir_tasks();
+        self.poll_notes_tasks();
+        self.poll_metadata_tasks();
+        self.poll_save_tasks();
+        self.poll_file_ops();
+        self.poll_trash_tasks();
+        self.poll_index_tasks();
+        self.poll_fs_events();
+        self.poll_disk_checks();
+        self.poll_history_tasks();
+        self.poll_commit_tasks();
+        self.poll_snapshot_tasks();
+        self.poll_rename_plan_tasks();
+        self.poll_graph_tasks();
+        self.run_scheduled_saves();
+        self.run_deferred_renames();
+        self.run_deferred_disk_checks();
+        self.run_deferred_evictions();
+        self.run_scheduled_commit();
+    }
+
+    pub fn poll_save_tasks(&mut self) {
+        let results = self
+            .background_tasks
+            .saves
+            .iter()
+            .flat_map(|(path, (_tx, rx))| rx.try_iter().map(|result| (path.clone(), result)))
+            .collect::<Vec<_>>();
+        for (path, result) in results {
+            if let Entry::Occupied(mut entry) =
+                self.background_tasks.pending_saves.entry(path.clone())
+            {
+                *entry.get_mut() -= 1;
+                if *entry.get() == 0 {
+                    entry.remove();
+                }
+            }
+            let Some(cell) = self.state.memory.notes.get_mut(&path) else {
+                continue;
+            };
+            match result {
+                Ok((generation, saved)) => {
+                    let applied_generation = self
+                        .background_tasks
+                        .applied_save_generations
+                        .entry(path.clone())
+                        .or_default();
+                    if generation < *applied_generation {
+                        continue;
+                    }
+                    *applied_generation = generation;
+                    cell.clear_write_error();
+                    self.state
+                        .memory
+                        .disk_sources
+                        .insert(path.clone(), saved.data.to_source());
+                    let icon = saved.data.icon().to_owned();
+                    // Keep edits made while the save was in flight
+                    if let Some(node) = cell.value_mut()
+                        && node.data == saved.data
+                    {
+                        node.dirty = false;
+                    }
+                    self.update_dir_note_icon(&path, icon);
+                    if let Some(index) = self.index_mut() {
+                        index.update(&path, &saved.data);
+                    }
+                    self.read_metadata_in_background(&path);
+                    self.schedule_commit();
+                    if self.state.memory.snapshots.contains_key(&path) {
+                        self.refresh_note_snapshots_in_background(&path);
+                    }
+                }
+                Err(err) => {
+                    log::error!("Failed to save {}: {err}", path.display());
+                    cell.set_write_error(err);
+                }
+            }
+        }
+    }
+
+    /// Keep icon in parent listing in sync without rereading the directory
+    fn update_dir_note_icon(&mut self, path: &Path, icon: String) {
+        let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+            return;
+        };
+        let Some(dir) = self
+            .state
+            .memory
+            .dirs
+            .get_mut(parent)
+            .and_then(MemoryCell::value_mut)
+        else {
+            return;
+        };
+        let name = name.to_string_lossy().into_owned();
+        if icon == DEFAULT_ICON {
+            dir.data.note_icons.remove(&name);
+        } else {
+            dir.data.note_icons.insert(name, icon);
+        }
+    }
+
+    fn has_pending_saves_below(&self, path: &Path) -> bool {
+        self.background_tasks
+            .pending_saves
+            .keys()
+            .any(|key| key.starts_with(path))
+    }
+
+    fn run_deferred_renames(&mut self) {
+        let (ready, deferred) = std::mem::take(&mut self.background_tasks.deferred_renames)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(from, _to)| !self.has_pending_saves_below(from));
+        self.background_tasks.deferred_renames = deferred;
+        for (from, to) in ready {
+            self.rename_in_background(&from, &to);
+        }
+
+        let (ready, deferred) = std::mem::take(&mut self.background_tasks.deferred_trash)
+            .into_iter()
+            .partition::<Vec<_>, _>(|path| !self.has_pending_saves_below(path));
+        self.background_tasks.deferred_trash = deferred;
+        for path in ready {
+            self.trash_in_background(&path);
+        }
+
+        let (ready, deferred) = std::mem::take(&mut self.background_tasks.deferred_relinks)
+            .into_iter()
+            .partition::<Vec<_>, _>(|plan| {
+                !self.relink_is_blocked(&plan.from, plan.notes.iter().map(|note| &note.path))
+            });
+        self.background_tasks.deferred_relinks = deferred;
+        for plan in ready {
+            self.rename_and_relink_in_background(plan);
+        }
+
+        if let Some(undo) = self.background_tasks.deferred_relink_undo.take() {
+            self.state.last_relink = Some(undo);
+            self.undo_relink_in_background();
+        }
+    }
+
+    /// Saves in flight below the renamed path or of notes with links to rewrite
+    fn relink_is_blocked<'a>(
+        &self,
+        from: &Path,
+        mut paths: impl Iterator<Item = &'a PathBuf>,
+    ) -> bool {
+        self.has_pending_saves_below(from)
+            || paths.any(|path| self.background_tasks.pending_saves.contains_key(path))
+    }
+
+    /// Invalidate cached directories and check cached notes touched by other programs
+    pub fn poll_fs_events(&mut self) {
+        let mut changed_paths = HashSet::new();
+        let mut structure_changed_paths = HashSet::new();
+        for result in self.fs_events.try_iter() {
+            let event = match result {
+                Ok(event) => event,
+                Err(err) => {
+                    log::warn!("File watcher error: {err}");
+                    continue;
+                }
+            };
+            let structure_changed = match event.kind {
+                EventKind::Access(_) => continue,
+                EventKind::Modify(
+                    notify::event::ModifyKind::Data(_) | notify::event::ModifyKind::Metadata(_),
+                ) => false,
+                _ => true,
+            };
+            for path in event.paths {
+                let moving = self
+                    .background_tasks
+                    .moving_paths
+                    .iter()
+                    .any(|moving_path| path.starts_with(moving_path));
+                if moving || !self.is_watched_path(&path) {
+                    continue;
+                }
+                if structure_changed {
+                    structure_changed_paths.insert(path.clone());
+                }
+                changed_paths.insert(path);
+            }
+        }
+
+        for path in changed_paths {
+            let structure_changed = structure_changed_paths.contains(&path);
+            let note_loaded = self.get_note(&path).is_some();
+            if let Some(parent) = path.parent()
+                && (structure_changed || !note_loaded)
+            {
+                self.refresh_dir_in_background(parent);
+            }
+            if structure_changed && !self.fs.exists(&path) {
+                // Removed directory, its listing and listings below are stale
+                let memory = &mut self.state.memory;
+                memory.dirs.retain(|key, _| !key.starts_with(&path));
+                if let Some(index) = self.index_mut() {
+                    index.remove(&path);
+                }
+            } else if !note_loaded && self.fs.is_file(&path) {
+                self.index_in_background(&path);
+            }
+            if note_loaded {
+                self.check_disk_note_in_background(&path);
+            }
+        }
+    }
+
+    /// Hidden entries, including trash, are not notes, except the scratch pad
+    fn is_watched_path(&self, path: &Path) -> bool {
+        if path == self.scratch_pad_path() {
+            return true;
+        }
+        path.strip_prefix(self.base_dir_path())
+            .is_ok_and(|relative| {
+                relative
+                    .components()
+                    .all(|component| !component.as_os_str().to_string_lossy().starts_with('.'))
+            })
+    }
+
+    fn check_disk_note_in_background(&mut self, path: &Path) {
+        let generation = self.save_generation(path);
+        let result_pipe = pipe_sender(&mut self.background_tasks.disk_checks, path);
+        self.async_execute_read_task(path, result_pipe, move |fs, path| {
+            files::read_disk_note(fs, path).map(|note| (generation, note))
+        });
+    }
+
+    fn save_generation(&self, path: &Path) -> u64 {
+        self.background_tasks
+            .save_generations
+            .get(path)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn poll_disk_checks(&mut self) {
+        let results = self
+            .background_tasks
+            .disk_checks
+            .iter()
+            .flat_map(|(path, (_tx, rx))| rx.try_iter().map(|result| (path.clone(), result)))
+            .collect::<Vec<_>>();
+        for (path, result) in results {
+            match result {
+                // Read could see a save in progress or precede a newer save
+                Ok((generation, _)) if generation != self.save_generation(&path) => {
+                    self.background_tasks.deferred_disk_checks.insert(path);
+                }
+                Ok(_) if self.background_tasks.pending_saves.contains_key(&path) => {
+                    self.background_tasks.deferred_disk_checks.insert(path);
+                }
+                Ok((_, theirs)) => self.apply_disk_note(path, theirs),
+                Err(err) => log::warn!("Failed to check {}: {err}", path.display()),
+            }
+        }
+    }
+
+    fn run_deferred_disk_checks(&mut self) {
+        let (ready, deferred) = std::mem::take(&mut self.background_tasks.deferred_disk_checks)
+            .into_iter()
+            .partition::<HashSet<_>, _>(|path| {
+                !self.background_tasks.pending_saves.contains_key(path)
+            });
+        self.background_tasks.deferred_disk_checks = deferred;
+        for path in ready {
+            self.check_disk_note_in_background(&path);
+        }
+    }
+
+    /// Take note changed on disk, or record conflict when it has unsaved edits
+    fn apply_disk_note(&mut self, path: PathBuf, theirs: Option<Note>) {
+        let memory = &mut self.state.memory;
+        let theirs_source = theirs.as_ref().map(Note::to_source);
+        if memory.disk_sources.get(&path) == theirs_source.as_ref() {
+            return;
+        }
+        let Some(node) = memory.notes.get(&path).and_then(MemoryCell::value) else {
+            return;
+        };
+
+        if node.dirty {
+            memory.conflicts.insert(path, Conflict { theirs });
+            return;
+        }
+        self.take_disk_note(path, theirs);
+    }
+
+    fn take_disk_note(&mut self, path: PathBuf, theirs: Option<Note>) {
+        self.state.memory.conflicts.remove(&path);
+        let Some(note) = theirs else {
+            self.forget_path(&path);
+            if let Some(index) = self.index_mut() {
+                index.remove(&path);
+            }
+            self.close_tabs_below(&path);
+            return;
+        };
+
+        self.state
+            .memory
+            .disk_sources
+            .insert(path.clone(), note.to_source());
+        if let Some(index) = self.index_mut() {
+            index.update(&path, &note);
+        }
+        self.update_dir_note_icon(&path, note.icon().to_owned());
+        self.read_metadata_in_background(&path);
+        self.state
+            .memory
+            .notes
+            .insert(path, MemoryCell::Value(DataNode::new(note)));
+    }
+
+    pub fn conflict(&self, path: &Path) -> Option<&Conflict> {
+        self.state.memory.conflicts.get(path)
+    }
+
+    /// Three-way merge of the note on disk into unsaved edits, conflicting lines are marked
+    /// with `<<<<<<<` and `>>>>>>>` in `Err`
+    pub fn merge_conflict(&self, path: &Path) -> Option<Result<String, String>> {
+        let theirs = self.conflict(path)?.theirs.as_ref()?.to_source();
+        let mine = self.get_note(path)?.data.to_source();
+        let base = self
+            .state
+            .memory
+            .disk_sources
+            .get(path)
+            .cloned()
+            .unwrap_or_default();
+        Some(diffy::merge(&base, &mine, &theirs))
+    }
+
+    pub fn resolve_conflict(&mut self, path: &Path, resolution: ConflictResolution) {
+        let Some(conflict) = self.state.memory.conflicts.remove(path) else {
+            return;
+        };
+        let theirs_source = conflict.theirs.as_ref().map(Note::to_source);
+        let mine = match resolution {
+            ConflictResolution::TakeTheirs => {
+                self.take_disk_note(path.to_path_buf(), conflict.theirs);
+                return;
+            }
+            ConflictResolution::KeepMine => None,
+            ConflictResolution::Merged(source) => Some(Note::from_source(source)),
+        };
+
+        // Disk now holds their version, next save replaces it
+        match theirs_source {
+            Some(source) => self
+                .state
+                .memory
+                .disk_sources
+                .insert(path.to_path_buf(), source),
+            None => self.state.memory.disk_sources.remove(path),
+        };
+        if let Some(node) = self.get_note_mut(path) {
+            if let Some(note) = mine {
+                node.data = note;
+            }
+            node.dirty = true;
+        }
+        self.save_note_now(path);
+    }
+
+    pub fn poll_trash_tasks(&mut self) {
+        self.background_tasks
+            .trash
+            .iter_mut()
+            .for_each(|(path, (_tx, rx))| {
+                rx.try_iter().for_each(|result| {
+                    let cell = match result {
+                        Ok(trash) => MemoryCell::Value(trash),
+                        Err(err) => MemoryCell::ReadError(err),
+                    };
+                    self.state.memory.trash.insert(path.to_path_buf(), cell);
+                })
+            });
+    }
+
+    pub fn poll_index_tasks(&mut self) {
+        self.background_tasks
+            .index
+            .iter_mut()
+            .for_each(|(path, (_tx, rx))| {
+                rx.try_iter().for_each(|result| {
+                    let cell = self
+                        .state
+                        .memory
+                        .index
+                        .entry(path.to_path_buf())
+                        .or_insert(MemoryCell::PendingRead);
+                    match (result, cell.value_mut()) {
+                        (Ok(notes), Some(index)) => notes
+                            .into_iter()
+                            .for_each(|(path, note)| index.update(&path, &note)),
+                        (Ok(notes), None) => {
+                            let mut index = VaultIndex::default();
+                            notes
+                                .into_iter()
+                                .for_each(|(path, note)| index.update(&path, &note));
+                            *cell = MemoryCell::Value(index);
+                        }
+                        (Err(err), _) => {
+                            log::error!("Failed to index {}: {err}", path.display());
+                            *cell = MemoryCell::ReadError(err);
+                        }
+                    }
+                })
+            });
+    }
+
+    pub fn poll_file_ops(&mut self) {
+        let results = self
+            .background_tasks
+            .file_ops
+            .iter()
+            .flat_map(|(path, (_tx, rx))| rx.try_iter().map(|result| (path.clone(), result)))
+            .collect::<Vec<_>>();
+        results.into_iter().for_each(|(path, result)| {
+            let mut released = Vec::new();
+            if self.background_tasks.moving_paths.remove(&path) {
+                released.push(path.clone());
+            }
+            for rewritten in self
+                .background_tasks
+                .relinking
+                .remove(&path)
+                .unwrap_or_default()
+            {
+                if self.background_tasks.moving_paths.remove(&rewritten) {
+                    released.push(rewritten);
+                }
+            }
+            match result {
+                Ok(op) => self.apply_file_op(op),
+                Err(err) => {
+                    log::error!("File operation failed: {err}");
+                    self.state.errors.push(err.to_string());
+                    // Saves held back for the rename go to the old paths after all
+                    for path in released {
+                        self.save_dirty_notes_below(&path);
+                    }
+                }
+            }
+        });
+    }
+
+    fn apply_file_op(&mut self, op: FileOp) {
+        self.schedule_commit();
+        match op {
+            FileOp::NoteCreated { path, select } => {
+                self.state
+                    .memory
+                    .disk_sources
+                    .insert(path.clone(), String::new());
+                if let Some(index) = self.index_mut() {
+                    index.update(&path, &Note::default());
+                }
+                self.state.memory.notes.insert(
+                    path.clone(),
+                    MemoryCell::Value(DataNode::new(Note::default())),
+                );
+                self.read_metadata_in_background(&path);
+                if let Some(parent) = path.parent() {
+                    self.refresh_dir_in_background(parent);
+                }
+                if select {
+                    self.open_note(path, true);
+                }
+            }
+            FileOp::DirCreated { path } => {
+                if let Some(parent) = path.parent() {
+                    self.refresh_dir_in_background(parent);
+                }
+            }
+            FileOp::Renamed { from, to } => {
+                if let Some(index) = self.index_mut() {
+                    index.rename(&from, &to);
+                }
+                self.rekey_renamed(&from, &to);
+                for parent in [from.parent(), to.parent()].into_iter().flatten() {
+                    self.refresh_dir_in_background(parent);
+                }
+            }
+            FileOp::Trashed { origin, name } => {
+                if let Some(index) = self.index_mut() {
+                    index.remove(&origin);
+                }
+                self.forget_path(&origin);
+                self.close_tabs_below(&origin);
+                if let Some(parent) = origin.parent() {
+                    self.refresh_dir_in_background(parent);
+                }
+                self.state.recently_trashed = Some(name);
+                self.refresh_trash_in_background();
+            }
+            FileOp::Rewritten(notes) => {
+                if let Some(index) = self.index_mut() {
+                    for (path, note) in &notes {
+                        index.update(path, note);
+                    }
+                }
+            }
+            FileOp::Relinked {
+                from,
+                to,
+                notes,
+                undo,
+            } => {
+                if let Some(index) = self.index_mut() {
+                    index.rename(&from, &to);
+                }
+                self.rekey_renamed(&from, &to);
+                for parent in [from.parent(), to.parent()].into_iter().flatten() {
+                    self.refresh_dir_in_background(parent);
+                }
+                for (path, replaced, note) in notes {
+                    self.apply_relinked_note(path, &replaced, note);
+                }
+                self.state.last_relink = undo;
+            }
+            FileOp::Restored { path } => {
+                self.index_in_background(&path);
+                if let Some(parent) = path.parent() {
+                    self.refresh_dir_in_background(parent);
+                }
+                self.refresh_trash_in_background();
+            }
+            FileOp::Purged => {
+                self.refresh_trash_in_background();
+            }
+        }
+    }
+
+    /// Take note written by a relinking rename, unless it was edited meanwhile, then it is a
+    /// conflict
+    fn apply_relinked_note(&mut self, path: PathBuf, replaced: &str, note: Note) {
+        if let Some(index) = self.index_mut() {
+            index.update(&path, &note);
+        }
+        let source = note.to_source();
+        let memory = &mut self.state.memory;
+        memory.disk_sources.insert(path.clone(), source.clone());
+        let Some(node) = memory.notes.get_mut(&path).and_then(MemoryCell::value_mut) else {
+            return;
+        };
+        let memory_source = node.data.to_source();
+        if memory_source == replaced || memory_source == source {
+            *node = DataNode::new(note);
+            self.background_tasks.scheduled_saves.remove(&path);
+            self.read_metadata_in_background(&path);
+        } else {
+            memory
+                .conflicts
+                .insert(path, Conflict { theirs: Some(note) });
+        }
+    }
+
+    /// Follow renamed path in memory, keeping loaded notes with their dirty state
+    fn rekey_renamed(&mut self, from: &Path, to: &Path) {
+        let memory = &mut self.state.memory;
+        // Directory listings hold paths of their entries, so they are reread instead
+        memory.dirs.retain(|key, _| !key.starts_with(from));
+        self.background_tasks
+            .dirs
+            .retain(|key, _| !key.starts_with(from));
+        // Reads in flight target the old path
+        memory
+            .notes
+            .retain(|key, cell| !key.starts_with(from) || cell.value().is_some());
+        self.background_tasks
+            .notes
+            .retain(|key, _| !key.starts_with(from));
+        self.background_tasks
+            .metadata
+            .retain(|key, _| !key.starts_with(from));
+
+        rekey_paths(&mut memory.notes, from, to);
+        rekey_paths(&mut memory.metadata, from, to);
+        rekey_paths(&mut memory.disk_sources, from, to);
+        rekey_paths(&mut memory.conflicts, from, to);
+        // History is kept by path, the note has none under the new one yet
+        memory.history.retain(|key, _| !key.starts_with(from));
+        self.background_tasks
+            .history
+            .retain(|key, _| !key.starts_with(from));
+        // Snapshots moved along with the note, reread under the new path when needed
+        memory.snapshots.retain(|key, _| !key.starts_with(from));
+        // Links they found are stale
+        memory.rename_plans.retain(|key, _| !key.starts_with(from));
+        self.background_tasks
+            .rename_plans
+            .retain(|key, _| !key.starts_with(from));
+        self.background_tasks
+            .snapshots
+            .retain(|key, _| !key.starts_with(from));
+        rekey_paths(&mut self.background_tasks.saves, from, to);
+        rekey_paths(&mut self.background_tasks.scheduled_saves, from, to);
+        rekey_paths(&mut self.background_tasks.pending_saves, from, to);
+
+        if let Ok(relative) = self.state.current_note_path.strip_prefix(from) {
+            self.state.current_note_path = to.join(relative);
+        }
+        self.state.tabs.rename(from, to);
+        let evictions = &mut self.background_tasks.deferred_evictions;
+        *evictions = std::mem::take(evictions)
+            .into_iter()
+            .map(|path| match path.strip_prefix(from) {
+                Ok(relative) => to.join(relative),
+                Err(_) => path,
+            })
+            .collect();
+
+        self.save_dirty_notes_below(to);
+    }
+
+    fn save_dirty_notes_below(&mut self, path: &Path) {
+        let dirty_notes = self
+            .state
+            .memory
+            .notes
+            .iter()
+            .filter(|(key, cell)| {
+                key.starts_with(path) && cell.value().is_some_and(|node| node.dirty)
+            })
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<PathBuf>>();
+        for path in dirty_notes {
+            self.save_note_in_background(&path);
+        }
+    }
+
+    /// Drop everything cached at or below the path, results of in-flight reads are discarded
+    fn forget_path(&mut self, path: &Path) {
+        let memory = &mut self.state.memory;
+        memory.notes.retain(|key, _| !key.starts_with(path));
+        memory.dirs.retain(|key, _| !key.starts_with(path));
+        memory.metadata.retain(|key, _| !key.starts_with(path));
+        memory.disk_sources.retain(|key, _| !key.starts_with(path));
+        memory.conflicts.retain(|key, _| !key.starts_with(path));
+        memory.history.retain(|key, _| !key.starts_with(path));
+        memory.snapshots.retain(|key, _| !key.starts_with(path));
+        memory.rename_plans.retain(|key, _| !key.starts_with(path));
+        let tasks = &mut self.background_tasks;
+        tasks.notes.retain(|key, _| !key.starts_with(path));
+        tasks.dirs.retain(|key, _| !key.starts_with(path));
+        tasks.metadata.retain(|key, _| !key.starts_with(path));
+        tasks.saves.retain(|key, _| !key.starts_with(path));
+        tasks.pending_saves.retain(|key, _| !key.starts_with(path));
+        tasks
+            .scheduled_saves
+            .retain(|key, _| !key.starts_with(path));
+        tasks.disk_checks.retain(|key, _| !key.starts_with(path));
+        tasks.history.retain(|key, _| !key.starts_with(path));
+        tasks.snapshots.retain(|key, _| !key.starts_with(path));
+        tasks.rename_plans.retain(|key, _| !key.starts_with(path));
+        tasks
+            .deferred_disk_checks
+            .retain(|key| !key.starts_with(path));
+        tasks
+            .deferred_evictions
+            .retain(|key| !key.starts_with(path));
+    }
+
+    pub fn poll_notes_tasks(&mut self) {
+        let mut gone = Vec::new();
+        self.background_tasks
+            .notes
+            .iter_mut()
+            .for_each(|(path, (_tx, rx))| {
+                rx.try_iter().for_each(|result| match result {
+                    Ok(note) => {
+                        self.state
+                            .memory
+                            .disk_sources
+                            .insert(path.to_path_buf(), note.data.to_source());
+                        self.state
+                            .memory
+                            .notes
+                            .insert(path.to_path_buf(), MemoryCell::Value(note));
+                    }
+                    // Note remembered from the last session may be gone by now
+                    Err(err)
+                        if err.kind() == io::ErrorKind::NotFound
+                            && *path != *self.state.config.location.scratch_pad_path =>
+                    {
+                        log::warn!("{} is gone, its tab is closed", path.display());
+                        gone.push(path.to_path_buf());
+                    }
+                    Err(err) => {
+                        log::error!("Failed to read {}: {err}", path.display());
+                        self.state
+                            .memory
+                            .notes
+                            .insert(path.to_path_buf(), MemoryCell::ReadError(err));
+                    }
+                })
+            });
+        for path in gone {
+            self.close_tabs_below(&path);
+            if let Some(parent) = path.parent() {
+                self.refresh_dir_in_background(parent);
+            }
+        }
+    }
+
+    pub fn poll_metadata_tasks(&mut self) {
+        self.background_tasks
+            .metadata
+            .iter_mut()
+            .for_each(|(path, (_tx, rx))| {
+                rx.try_iter().for_each(|result| {
+                    let cell = match result {
+                        Ok(metadata) => MemoryCell::Value(metadata),
+                        Err(err) => {
+                            log::warn!("Failed to read metadata of {}: {err}", path.display());
+                            MemoryCell::ReadError(err)
+                        }
+                    };
+                    self.state.memory.metadata.insert(path.to_path_buf(), cell);
+                })
+            });
+    }
+
+    pub fn poll_dir_tasks(&mut self) {
+        self.background_tasks
+            .dirs
+            .iter_mut()
+            .for_each(|(path, (_tx, rx))| {
+                rx.try_iter().for_each(|result| match result {
+                    Ok(dir) => {
+                        self.state
+                            .memory
+                            .dirs
+                            .insert(path.to_path_buf(), MemoryCell::Value(dir));
+                    }
+                    Err(err) => {
+                        log::error!("Failed to read {}: {err}", path.display());
+                        self.state
+                            .memory
+                            .dirs
+                            .insert(path.to_path_buf(), MemoryCell::ReadError(err));
+                    }
+                })
+            });
+    }
+
+    pub fn read_note_in_background(&mut self, path: &Path) {
+        if self.note_in_memory(path) {
+            return;
+        }
+
+        self.state
+            .memory
+            .notes
+            .insert(path.to_path_buf(), MemoryCell::PendingRead);
+
+        let result_pipe = match self.background_tasks.notes.entry(path.to_owned()) {
+            Entry::Vacant(entry) => entry.insert(channel()).0.clone(),
+            Entry::Occupied(entry) => entry.into_mut().0.clone(),
+        };
+
+        self.async_execute_read_task(path, result_pipe, files::load_note);
+        self.read_metadata_in_background(path);
+    }
+
+    /// Previous metadata stays available until the fresh one arrives
+    pub fn read_metadata_in_background(&mut self, path: &Path) {
+        self.state
+            .memory
+            .metadata
+            .entry(path.to_path_buf())
+            .or_insert(MemoryCell::PendingRead);
+        let result_pipe = pipe_sender(&mut self.background_tasks.metadata, path);
+        self.async_execute_read_task(path, result_pipe, files::load_metadata);
+    }
+
+    pub fn get_metadata(&self, path: &Path) -> Option<&FileMetadata> {
+        self.state.memory.metadata.get(path)?.value()
+    }
+
+    /// Drop cached note, including read error, and read it again
+    pub fn reload_note_in_background(&mut self, path: &Path) {
+        self.state.memory.notes.remove(path);
+        self.read_note_in_background(path);
+    }
+
+    /// Icon of loaded note, or the one found when its directory was read
+    pub fn note_icon(&self, path: &Path) -> &str {
+        if let Some(note) = self.get_note(path) {
+            return note.data.icon();
+        }
+        path.parent()
+            .and_then(|parent| self.get_dir(parent))
+            .zip(path.file_name())
+            .and_then(|(dir, name)| dir.data.note_icons.get(name.to_str()?))
+            .map_or(DEFAULT_ICON, String::as_str)
+    }
+
+    pub fn set_note_icon(&mut self, path: &Path, icon: String) {
+        let Some(node) = self.get_note_mut(path) else {
+            return;
+        };
+        node.data.set_icon(icon);
+        node.dirty = true;
+        self.save_note_now(path);
+    }
+
+    /// Title edits are saved like edits of text
+    pub fn set_note_title(&mut self, path: &Path, title: &str) {
+        let Some(node) = self.get_note_mut(path) else {
+            return;
+        };
+        node.data.set_title(title);
+        node.dirty = true;
+    }
+
+    pub fn note_is_dirty(&self, path: &Path) -> bool {
+        self.state
+            .memory
+            .notes
+            .get(path)
+            .and_then(MemoryCell::value)
+            .is_some_and(|node| node.dirty)
+    }
+
+    pub fn note_in_memory(&self, path: &Path) -> bool {
+        self.state.memory.notes.contains_key(path)
+    }
+
+    pub fn set_dirty(&mut self, path: &Path) {
+        if let Some(node) = self
+            .state
+            .memory
+            .notes
+            .get_mut(path)
+            .and_then(MemoryCell::value_mut)
+        {
+            node.dirty = true;
+        }
+    }
+
+    /// Path is about to be renamed or moved to trash
+    pub fn rename_is_pending(&self, path: &Path) -> bool {
+        let tasks = &self.background_tasks;
+        tasks
+            .deferred_renames
+            .iter()
+            .map(|(from, _to)| from)
+            .chain(&tasks.deferred_trash)
+            .chain(&tasks.moving_paths)
+            .any(|from| path.starts_with(from))
+    }
+
+    /// Save once edits pause, repeated calls are coalesced into one write
+    pub fn save_note_in_background(&mut self, path: &Path) {
+        self.schedule_save(path, self.state.config.save_delay);
+    }
+
+    pub fn save_note_now(&mut self, path: &Path) {
+        self.schedule_save(path, Duration::ZERO);
+        self.run_scheduled_saves();
+    }
+
+    fn schedule_save(&mut self, path: &Path, delay: Duration) {
+        let now = Instant::now();
+        let scheduled = self
+            .background_tasks
+            .scheduled_saves
+            .entry(path.to_path_buf())
+            .or_insert(ScheduledSave {
+                first_requested: now,
+                due: now,
+            });
+        let max_delay = MAX_SAVE_DELAY.max(self.state.config.save_delay);
+        scheduled.due = (now + delay).min(scheduled.first_requested + max_delay);
+    }
+
+    /// When the earliest scheduled save is due, to wake up UI for it
+    pub fn next_save_due(&self) -> Option<Instant> {
+        self.background_tasks
+            .scheduled_saves
+            .values()
+            .map(|scheduled| scheduled.due)
+            .min()
+    }
+
+    /// Start due saves, one write per note at a time so they land in order
+    fn run_scheduled_saves(&mut self) {
+        let now = Instant::now();
+        let due_paths = self
+            .background_tasks
+            .scheduled_saves
+            .iter()
+            .filter(|(path, scheduled)| {
+                scheduled.due <= now
+                    && !self.background_tasks.pending_saves.contains_key(*path)
+                    // Saved after the rename under the new path
+                    && !self.rename_is_pending(path)
+            })
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<PathBuf>>();
+        for path in due_paths {
+            self.background_tasks.scheduled_saves.remove(&path);
+            self.start_save(&path);
+        }
+    }
+
+    /// Loaded notes with edits not written to disk, sorted
+    pub fn unsaved_notes(&self) -> Vec<PathBuf> {
+        let mut paths = self
+            .state
+            .memory
+            .notes
+            .iter()
+            .filter(|(_, cell)| cell.value().is_some_and(|node| node.dirty))
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    pub fn has_unsaved_notes(&self) -> bool {
+        self.state
+            .memory
+            .notes
+            .values()
+            .any(|cell| cell.value().is_some_and(|node| node.dirty))
+    }
+
+    /// Block until saves, renames and commit sent to executor land, so that nothing writes the
+    /// same files at the same time
+    fn wait_for_writes(&mut self) {
+        let deadline = Instant::now() + FLUSH_TIMEOUT;
+        loop {
+            self.poll_save_tasks();
+            self.poll_file_ops();
+            self.poll_commit_tasks();
+            let tasks = &self.background_tasks;
+            if tasks.pending_saves.is_empty()
+                && tasks.moving_paths.is_empty()
+                && !tasks.commit_in_flight
+            {
+                return;
+            }
+            if Instant::now() >= deadline {
+                log::warn!("Writes in flight did not finish in {FLUSH_TIMEOUT:?}");
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Write every note with unsaved edits right away, blocking the caller, e.g. before exit.
+    /// Saves in flight land first. Notes in conflict with disk, or still being written or moved
+    /// when waiting times out, are not written and, like failed writes, make it an error once
+    /// the others are written
+    pub fn flush_saves(&mut self) -> io::Result<()> {
+        self.wait_for_writes();
+        self.background_tasks.scheduled_saves.clear();
+        let snapshots = self.snapshots();
+        let mut result = Ok(());
+        for path in self.unsaved_notes() {
+            if self.background_tasks.pending_saves.contains_key(&path)
+                || self.rename_is_pending(&path)
+            {
+                result = Err(io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("{} is still being written", path.display()),
+                ));
+                continue;
+            }
+            if self.conflict(&path).is_some() {
+                result = Err(io::Error::other(format!(
+                    "{} was changed by another program",
+                    path.display()
+                )));
+                continue;
+            }
+            let Some(cell) = self.state.memory.notes.get_mut(&path) else {
+                continue;
+            };
+            let Some(node) = cell.value_mut() else {
+                continue;
+            };
+            node.data.touch();
+            match files::save_note(&*self.fs, &path, node, snapshots.as_ref()) {
+                Ok(saved) => {
+                    node.dirty = false;
+                    cell.clear_write_error();
+                    self.state
+                        .memory
+                        .disk_sources
+                        .insert(path, saved.data.to_source());
+                    self.schedule_commit();
+                }
+                Err(err) => {
+                    log::error!("Failed to save {}: {err}", path.display());
+                    result = Err(io::Error::new(
+                        err.kind(),
+                        format!("{}: {err}", path.display()),
+                    ));
+                    cell.set_write_error(err);
+                }
+            }
+        }
+        result
+    }
+
+    fn start_save(&mut self, path: &Path) {
+        // Saving would overwrite the version on disk before user picks one
+        if self.conflict(path).is_some() {
+            return;
+        }
+
+        let Some(node) = self.get_note_mut(path) else {
+            return;
+        };
+        node.data.touch();
+        let note = node.clone();
+        let snapshots = self.snapshots();
+
+        let result_pipe = pipe_sender(&mut self.background_tasks.saves, path);
+        *self
+            .background_tasks
+            .pending_saves
+            .entry(path.to_path_buf())
+            .or_default() += 1;
+        let generation = self
+            .background_tasks
+            .save_generations
+            .entry(path.to_path_buf())
+            .or_default();
+        *generation += 1;
+        let generation = *generation;
+
+        self.async_execute_file_task(path, result_pipe, move |fs, path| {
+            files::save_note(fs, path, &note, snapshots.as_ref()).map(|note| (generation, note))
+        });
+    }
+
+    fn async_execute_file_task<T: Send + 'static>(
+        &self,
+        path: &Path,
+        result_pipe: Sender<io::Result<T>>,
+        task_fn: impl Fn(&dyn Fs, &Path) -> io::Result<T> + Send + 'static,
+    ) {
+        let fs = Arc::clone(&self.fs);
+        let path_clone = path.to_path_buf();
+        self.executor.execute(move || {
+            let parse_result = task_fn(&*fs, &path_clone);
+            // Receiver is gone when the result is no longer awaited
+            let _ = result_pipe.send(parse_result);
+        });
+    }
+
+    /// Read that is skipped if location changes before it starts
+    fn async_execute_read_task<T: Send + 'static>(
+        &self,
+        path: &Path,
+        result_pipe: Sender<io::Result<T>>,
+        task_fn: impl Fn(&dyn Fs, &Path) -> io::Result<T> + Send + 'static,
+    ) {
+        let location_epoch = Arc::clone(&self.location_epoch);
+        let epoch = location_epoch.load(Ordering::Relaxed);
+        self.async_execute_file_task(path, result_pipe, move |fs, path| {
+            if location_epoch.load(Ordering::Relaxed) != epoch {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            task_fn(fs, path)
+        });
+    }
+
+    pub fn read_dir_in_background(&mut self, path: &Path) {
+        if self.dir_in_memory(path) {
+            return;
+        }
+
+        self.state
+            .memory
+            .dirs
+            .insert(path.to_path_buf(), MemoryCell::PendingRead);
+
+        let result_pipe = match self.background_tasks.dirs.entry(path.to_owned()) {
+            Entry::Vacant(entry) => entry.insert(channel()).0.clone(),
+            Entry::Occupied(entry) => entry.into_mut().0.clone(),
+        };
+
+        self.async_execute_read_task(path, result_pipe, files::load_dir);
+    }
+
+    /// Reload directory that is already in memory, keeping the stale value until the new one arrives
+    pub fn refresh_dir_in_background(&mut self, path: &Path) {
+        if !self.dir_in_memory(path) {
+            return;
+        }
+
+        let result_pipe = match self.background_tasks.dirs.entry(path.to_owned()) {
+            Entry::Vacant(entry) => entry.insert(channel()).0.clone(),
+            Entry::Occupied(entry) => entry.into_mut().0.clone(),
+        };
+
+        self.async_execute_read_task(path, result_pipe, files::load_dir);
+    }
+
+    pub fn create_note_in_background(&mut self, dir_path: &Path, select: bool) {
+        let result_pipe = pipe_sender(&mut self.background_tasks.file_ops, dir_path);
+
+        self.async_execute_file_task(dir_path, result_pipe, move |fs, dir_path| {
+            files::create_note(fs, dir_path).map(|path| FileOp::NoteCreated { path, select })
+        });
+    }
+
+    pub fn create_dir_in_background(&mut self, parent_path: &Path) {
+        let result_pipe = pipe_sender(&mut self.background_tasks.file_ops, parent_path);
+        self.async_execute_file_task(parent_path, result_pipe, |fs, parent_path| {
+            files::create_dir(fs, parent_path).map(|path| FileOp::DirCreated { path })
+        });
+    }
+
+    /// Rename or move note or directory, waits for pending saves of affected notes first
+    pub fn rename_in_background(&mut self, from: &Path, to: &Path) {
+        if from == to {
+            return;
+        }
+
+        if self.has_pending_saves_below(from) {
+            self.background_tasks
+                .deferred_renames
+                .push((from.to_path_buf(), to.to_path_buf()));
+            return;
+        }
+
+        let to = to.to_path_buf();
+        let snapshots = self.snapshots();
+        self.background_tasks
+            .moving_paths
+            .insert(from.to_path_buf());
+        let result_pipe = pipe_sender(&mut self.background_tasks.file_ops, from);
+        self.async_execute_file_task(from, result_pipe, move |fs, from| {
+            files::rename(fs, from, &to)?;
+            if let Some(snapshots) = &snapshots
+                && let Err(err) = snapshots.rename(fs, from, &to)
+            {
+                log::warn!("Failed to move snapshots of {}: {err}", from.display());
+            }
+            Ok(FileOp::Renamed {
+                from: from.to_path_buf(),
+                to: to.clone(),
+            })
+        });
+    }
+
+    /// Find links that a rename or move would change, for a preview before it is done. Needs
+    /// the vault index, nothing happens until it is built
+    pub fn plan_rename_in_background(&mut self, from: &Path, to: &Path) {
+        let base_path = self.base_dir_path().to_path_buf();
+        let Some(MemoryCell::Value(index)) = self.get_index() else {
+            return;
+        };
+        let names = index.links.names.clone();
+        let mut loaded = Vec::new();
+        let mut unloaded = Vec::new();
+        for path in index.links.affected_by_rename(&base_path, from) {
+            match self.get_note(&path) {
+                Some(node) => loaded.push((path, node.data.clone())),
+                None => unloaded.push(path),
+            }
+        }
+
+        self.state
+            .memory
+            .rename_plans
+            .insert(from.to_path_buf(), MemoryCell::PendingRead);
+        let to = to.to_path_buf();
+        let result_pipe = pipe_sender(&mut self.background_tasks.rename_plans, from);
+        self.async_execute_read_task(from, result_pipe, move |fs, from| {
+            let mut notes = loaded.clone();
+            for path in &unloaded {
+                if let Some(note) = files::read_disk_note(fs, path)? {
+                    notes.push((path.clone(), note));
+                }
+            }
+            Ok(RenamePlan::new(&base_path, &names, from, &to, &notes))
+        });
+    }
+
+    pub fn get_rename_plan(&self, from: &Path) -> Option<&MemoryCell<RenamePlan>> {
+        self.state.memory.rename_plans.get(from)
+    }
+
+    pub fn discard_rename_plan(&mut self, from: &Path) {
+        self.state.memory.rename_plans.remove(from);
+        self.background_tasks.rename_plans.remove(from);
+    }
+
+    pub fn poll_rename_plan_tasks(&mut self) {
+        self.background_tasks
+            .rename_plans
+            .iter_mut()
+            .for_each(|(path, (_tx, rx))| {
+                rx.try_iter().for_each(|result| {
+                    let cell = match result {
+                        Ok(plan) => MemoryCell::Value(plan),
+                        Err(err) => MemoryCell::ReadError(err),
+                    };
+                    self.state
+                        .memory
+                        .rename_plans
+                        .insert(path.to_path_buf(), cell);
+                })
+            });
+    }
+
+    /// Do the planned rename and rewrite the links it changes, all at once: when any write
+    /// fails, nothing is changed. Loaded notes are written with their unsaved edits
+    pub fn apply_rename_plan_in_background(&mut self, from: &Path) {
+        self.background_tasks.rename_plans.remove(from);
+        let Some(MemoryCell::Value(plan)) = self.state.memory.rename_plans.remove(from) else {
+            return;
+        };
+        self.rename_and_relink_in_background(plan);
+    }
+
+    fn rename_and_relink_in_background(&mut self, plan: RenamePlan) {
+        let paths = plan.notes.iter().map(|note| &note.path);
+        if self.relink_is_blocked(&plan.from, paths) {
+            self.background_tasks.deferred_relinks.push(plan);
+            return;
+        }
+
+        let loaded = plan
+            .notes
+            .iter()
+            .filter_map(|note| Some((note.path.clone(), self.get_note(&note.path)?.data.clone())))
+            .collect::<Notes>();
+        let rewritten = plan
+            .notes
+            .iter()
+            .map(|note| note.path.clone())
+            .collect::<Vec<_>>();
+        let tasks = &mut self.background_tasks;
+        tasks.moving_paths.insert(plan.from.clone());
+        tasks.moving_paths.extend(rewritten.iter().cloned());
+        tasks.relinking.insert(plan.from.clone(), rewritten);
+        let snapshots = self.snapshots();
+        let result_pipe = pipe_sender(&mut self.background_tasks.file_ops, &plan.from);
+        self.async_execute_file_task(&plan.from.clone(), result_pipe, move |fs, _from| {
+            let (rewritten, undo) =
+                relink::rename_and_relink(fs, &plan, &loaded, snapshots.as_ref())?;
+            let notes = undo
+                .sources
+                .iter()
+                .zip(rewritten)
+                .map(|((_, original, _), (path, note))| (path, original.clone(), note))
+                .collect();
+            Ok(FileOp::Relinked {
+                from: plan.from.clone(),
+                to: plan.to.clone(),
+                notes,
+                undo: Some(undo),
+            })
+        });
+    }
+
+    pub fn last_relink(&self) -> Option<&RelinkUndo> {
+        self.state.last_relink.as_ref()
+    }
+
+    pub fn dismiss_last_relink(&mut self) {
+        self.state.last_relink = None;
+    }
+
+    /// Rename back and restore the notes the last relinking rename rewrote, unless they
+    /// changed since
+    pub fn undo_relink_in_background(&mut self) {
+        let Some(undo) = self.state.last_relink.take() else {
+            return;
+        };
+        let paths = undo.sources.iter().map(|(path, _, _)| path);
+        if self.relink_is_blocked(&undo.to, paths) {
+            self.background_tasks.deferred_relink_undo = Some(undo);
+            return;
+        }
+
+        let rewritten = undo
+            .sources
+            .iter()
+            .map(|(path, _, _)| path.clone())
+            .collect::<Vec<_>>();
+        let tasks = &mut self.background_tasks;
+        tasks.moving_paths.insert(undo.to.clone());
+        tasks.moving_paths.extend(rewritten.iter().cloned());
+        tasks.relinking.insert(undo.to.clone(), rewritten);
+        let snapshots = self.snapshots();
+        let result_pipe = pipe_sender(&mut self.background_tasks.file_ops, &undo.to);
+        self.async_execute_file_task(&undo.to.clone(), result_pipe, move |fs, _to| {
+            let restored = relink::undo_relink(fs, &undo, snapshots.as_ref())?;
+            let notes = undo
+                .sources
+                .iter()
+                .zip(restored)
+                .map(|((_, _, rewritten), (path, note))| (path, rewritten.clone(), note))
+                .collect();
+            Ok(FileOp::Relinked {
+                from: undo.to.clone(),
+                to: undo.from.clone(),
+                notes,
+                undo: None,
+            })
+        });
+    }
+
+    pub fn get_trash(&self) -> Option<&MemoryCell<Trash>> {
+        self.state.memory.trash.get(self.trash_path())
+    }
+
+    pub fn recently_trashed(&self) -> Option<&str> {
+        self.state.recently_trashed.as_deref()
+    }
+
+    pub fn dismiss_recently_trashed(&mut self) {
+        self.state.recently_trashed = None;
+    }
+
+    pub fn read_trash_in_background(&mut self) {
+        if self.state.memory.trash.contains_key(self.trash_path()) {
+            return;
+        }
+        self.refresh_trash_in_background();
+    }
+
+    pub fn refresh_trash_in_background(&mut self) {
+        let trash_path = self.trash_path().to_path_buf();
+        self.state
+            .memory
+            .trash
+            .entry(trash_path.clone())
+            .or_insert(MemoryCell::PendingRead);
+
+        let result_pipe = pipe_sender(&mut self.background_tasks.trash, &trash_path);
+        self.async_execute_read_task(&trash_path, result_pipe, trash::load_trash);
+    }
+
+    pub fn trash_in_background(&mut self, path: &Path) {
+        if path == self.scratch_pad_path() || path == self.base_dir_path() {
+            log::warn!("Refuse to trash {}", path.display());
+            return;
+        }
+
+        // Write in flight would recreate the note after it is moved
+        if self.has_pending_saves_below(path) {
+            self.background_tasks
+                .deferred_trash
+                .push(path.to_path_buf());
+            return;
+        }
+
+        let trash_path = self.trash_path().to_path_buf();
+        self.background_tasks
+            .moving_paths
+            .insert(path.to_path_buf());
+        let result_pipe = pipe_sender(&mut self.background_tasks.file_ops, path);
+        self.async_execute_file_task(path, result_pipe, move |fs, path| {
+            trash::move_to_trash(fs, path, &trash_path).map(|name| FileOp::Trashed {
+                origin: path.to_path_buf(),
+                name,
+            })
+        });
+    }
+
+    pub fn restore_from_trash_in_background(&mut self, name: &str) {
+        let trash_path = self.trash_path().to_path_buf();
+        let result_pipe = pipe_sender(&mut self.background_tasks.file_ops, &trash_path);
+        let name = name.to_owned();
+        self.async_execute_file_task(&trash_path, result_pipe, move |fs, trash_path| {
+            trash::restore_from_trash(fs, &name, trash_path).map(|path| FileOp::Restored { path })
+        });
+    }
+
+    pub fn purge_from_trash_in_background(&mut self, name: &str) {
+        let trash_path = self.trash_path().to_path_buf();
+        let result_pipe = pipe_sender(&mut self.background_tasks.file_ops, &trash_path);
+        let name = name.to_owned();
+        self.async_execute_file_task(&trash_path, result_pipe, move |fs, trash_path| {
+            trash::purge_from_trash(fs, &name, trash_path).map(|()| FileOp::Purged)
+        });
+    }
+
+    pub fn empty_trash_in_background(&mut self) {
+        let trash_path = self.trash_path().to_path_buf();
+        let result_pipe = pipe_sender(&mut self.background_tasks.file_ops, &trash_path);
+        self.async_execute_file_task(&trash_path, result_pipe, |fs, trash_path| {
+            trash::empty_trash(fs, trash_path).map(|()| FileOp::Purged)
+        });
+    }
+
+    pub fn get_index(&self) -> Option<&MemoryCell<VaultIndex>> {
+        self.state.memory.index.get(self.base_dir_path())
+    }
+
+    fn index_mut(&mut self) -> Option<&mut VaultIndex> {
+        let base_path = Rc::clone(&self.state.config.location.base_path);
+        self.state
+            .memory
+            .index
+            .get_mut(&*base_path)
+            .and_then(MemoryCell::value_mut)
+    }
+
+    /// Build index of the base directory, unless it is built or being built
+    pub fn build_index_in_background(&mut self) {
+        let base_path = self.base_dir_path().to_path_buf();
+        if self.state.memory.index.contains_key(&base_path) {
+            return;
+        }
+        self.state
+            .memory
+            .index
+            .insert(base_path.clone(), MemoryCell::PendingRead);
+        self.index_in_background(&base_path);
+    }
+
+    /// Drop index, including read error, and build it again
+    pub fn rebuild_index_in_background(&mut self) {
+        let base_path = Rc::clone(&self.state.config.location.base_path);
+        self.state.memory.index.remove(&*base_path);
+        self.build_index_in_background();
+    }
+
+    /// Add notes at or below the path to the index
+    fn index_in_background(&mut self, path: &Path) {
+        let base_path = self.base_dir_path().to_path_buf();
+        if !self.state.memory.index.contains_key(&base_path) {
+            return;
+        }
+        let scratch_pad_path = self.scratch_pad_path().to_path_buf();
+        let result_pipe = pipe_sender(&mut self.background_tasks.index, &base_path);
+        self.async_execute_read_task(path, result_pipe, move |fs, path| {
+            files::read_notes(fs, path, &scratch_pad_path)
+        });
+    }
+
+    /// Rename tag and tags nested in it in every note of the index. Notes in memory are changed
+    /// and saved there, the rest are rewritten in background
+    pub fn rename_tag_in_background(&mut self, from: &str, to: &str) {
+        let Some(index) = self.index_mut() else {
+            return;
+        };
+        let paths = index.tags.notes_with_all(&[from.to_owned()]);
+        let (loaded, unloaded): (Vec<PathBuf>, Vec<PathBuf>) = paths
+            .into_iter()
+            .partition(|path| self.get_note(path).is_some());
+
+        for path in loaded {
+            let Some(node) = self.get_note_mut(&path) else {
+                continue;
+            };
+            if node.data.rename_tag(from, to) {
+                node.dirty = true;
+                self.save_note_now(&path);
+            }
+        }
+
+        if unloaded.is_empty() {
+            return;
+        }
+        let (from, to) = (from.to_owned(), to.to_owned());
+        let snapshots = self.snapshots();
+        let base_path = self.base_dir_path().to_path_buf();
+        let result_pipe = pipe_sender(&mut self.background_tasks.file_ops, &base_path);
+        self.async_execute_file_task(&base_path, result_pipe, move |fs, _base_path| {
+            let mut rewritten = Vec::new();
+            for path in &unloaded {
+                let mut note = Note::from_source(fs.read_to_string(path)?);
+                if !note.rename_tag(&from, &to) {
+                    continue;
+                }
+                note.touch();
+                let saved = files::save_note(fs, path, &DataNode::new(note), snapshots.as_ref())?;
+                rewritten.push((path.clone(), saved.data));
+            }
+            Ok(FileOp::Rewritten(rewritten))
+        });
+    }
+
+    /// Names of notes wiki links resolve against, from the index when it is built and from
+    /// directories read so far until then
+    pub fn note_names(&self) -> Cow<'_, NoteNames> {
+        if let Some(MemoryCell::Value(index)) = self.get_index() {
+            return Cow::Borrowed(&index.links.names);
+        }
+        let base_path = self.base_dir_path();
+        Cow::Owned(NoteNames::from_dirs(
+            self.state
+                .memory
+                .dirs
+                .iter()
+                .filter(|(path, _)| path.starts_with(base_path))
+                .filter_map(|(_, cell)| cell.value())
+                .map(|node| &node.data),
+        ))
+    }
+
+    pub fn get_graph(&self) -> Option<&MemoryCell<GraphLayout>> {
+        self.state.memory.graphs.get(self.base_dir_path())
+    }
+
+    /// Lay out the link graph in background when links changed since the last layout. Needs
+    /// the vault index. The previous layout is kept until the new one is done
+    pub fn layout_graph_in_background(&mut self) {
+        if self.background_tasks.graph_in_flight {
+            return;
+        }
+        let base_path = self.base_dir_path().to_path_buf();
+        let Some(MemoryCell::Value(index)) = self.get_index() else {
+            return;
+        };
+        let generation = index.links.generation();
+        let previous = match self.state.memory.graphs.get(&base_path) {
+            Some(MemoryCell::Value(layout)) if layout.generation == generation => return,
+            Some(MemoryCell::Value(layout)) => Some(layout),
+            _ => None,
+        };
+        let nodes = index.links.names.paths().map(Path::to_path_buf).collect();
+        let graph = Graph::new(nodes, &index.links.edges(&base_path));
+        let unchanged = previous.is_some_and(|previous| previous.graph == graph);
+        let previous = previous.cloned();
+        let memory = &mut self.state.memory;
+        if unchanged {
+            // Links changed only in note text
+            if let Some(layout) = memory
+                .graphs
+                .get_mut(&base_path)
+                .and_then(MemoryCell::value_mut)
+            {
+                layout.generation = generation;
+            }
+            return;
+        }
+
+        memory
+            .graphs
+            .entry(base_path.clone())
+            .or_insert(MemoryCell::PendingRead);
+        self.background_tasks.graph_in_flight = true;
+        let result_pipe = pipe_sender(&mut self.background_tasks.graphs, &base_path);
+        self.async_execute_read_task(&base_path, result_pipe, move |_fs, _base_path| {
+            Ok(GraphLayout::new(
+                graph.clone(),
+                generation,
+                previous.as_ref(),
+            ))
+        });
+    }
+
+    pub fn poll_graph_tasks(&mut self) {
+        self.background_tasks
+            .graphs
+            .iter_mut()
+            .for_each(|(path, (_tx, rx))| {
+                rx.try_iter().for_each(|result| {
+                    self.background_tasks.graph_in_flight = false;
+                    let cell = match result {
+                        Ok(layout) => MemoryCell::Value(layout),
+                        Err(err) => MemoryCell::ReadError(err),
+                    };
+                    self.state.memory.graphs.insert(path.to_path_buf(), cell);
+                })
+            });
+    }
+
+    pub fn git_history(&self) -> bool {
+        self.state.config.location.git_history
+    }
+
+    /// Commit once saves pause, later saves push the commit back
+    fn schedule_commit(&mut self) {
+        if self.git_history() {
+            self.background_tasks.scheduled_commit =
+                Some(Instant::now() + self.state.config.commit_delay);
+        }
+    }
+
+    /// When the scheduled commit is due, to wake up UI for it
+    pub fn next_commit_due(&self) -> Option<Instant> {
+        self.background_tasks.scheduled_commit
+    }
+
+    /// Start due commit after saves in flight land, one commit at a time
+    fn run_scheduled_commit(&mut self) {
+        let tasks = &self.background_tasks;
+        let due = tasks
+            .scheduled_commit
+            .is_some_and(|due| due <= Instant::now());
+        if !due
+            || tasks.commit_in_flight
+            || !tasks.pending_saves.is_empty()
+            || !tasks.scheduled_saves.is_empty()
+        {
+            return;
+        }
+        self.background_tasks.scheduled_commit = None;
+        self.background_tasks.commit_in_flight = true;
+
+        let base_path = self.base_dir_path().to_path_buf();
+        let scratch_pad_path = self.scratch_pad_path().to_path_buf();
+        let result_pipe = pipe_sender(&mut self.background_tasks.commits, &base_path);
+        // Repository is on the real disk whatever the file system of the notes
+        self.async_execute_file_task(&base_path, result_pipe, move |_fs, base_path| {
+            git_history::commit_all(base_path, &scratch_pad_path)
+        });
+    }
+
+    /// Commit scheduled changes right away, blocking the caller, e.g. before exit. Saves and
+    /// commit in flight land first. A commit still running keeps the repository locked, then
+    /// the changes go into the next commit, which takes every change below the base directory
+    pub fn flush_commit(&mut self) {
+        self.wait_for_writes();
+        if self.background_tasks.commit_in_flight {
+            log::warn!("Commit in flight did not finish, changes go into the next one");
+            return;
+        }
+        if self.background_tasks.scheduled_commit.take().is_none() {
+            return;
+        }
+        if let Err(err) = git_history::commit_all(self.base_dir_path(), self.scratch_pad_path()) {
+            log::error!("Failed to commit {}: {err}", self.base_dir_path().display());
+        }
+    }
+
+    pub fn poll_commit_tasks(&mut self) {
+        let results = self
+            .background_tasks
+            .commits
+            .values()
+            .flat_map(|(_tx, rx)| rx.try_iter())
+            .collect::<Vec<_>>();
+        for result in results {
+            self.background_tasks.commit_in_flight = false;
+            match result {
+                Ok(true) => {
+                    let paths = self
+                        .state
+                        .memory
+                        .history
+                        .keys()
+                        .cloned()
+                        .collect::<Vec<PathBuf>>();
+                    for path in paths {
+                        self.refresh_note_history_in_background(&path);
+                    }
+                }
+                Ok(false) => {}
+                Err(err) => {
+                    log::error!("Failed to commit: {err}");
+                    self.state.errors.push(err.to_string());
+                }
+            }
+        }
+    }
+
+    pub fn get_note_history(&self, path: &Path) -> Option<&MemoryCell<Vec<Revision>>> {
+        self.state.memory.history.get(path)
+    }
+
+    /// Read previous versions of the note, unless they are read or being read
+    pub fn read_note_history_in_background(&mut self, path: &Path) {
+        if self.state.memory.history.contains_key(path) {
+            return;
+        }
+        self.refresh_note_history_in_background(path);
+    }
+
+    /// Stale history stays available until the fresh one arrives
+    pub fn refresh_note_history_in_background(&mut self, path: &Path) {
+        if !self.git_history() {
+            return;
+        }
+        self.state
+            .memory
+            .history
+            .entry(path.to_path_buf())
+            .or_insert(MemoryCell::PendingRead);
+        let base_path = self.base_dir_path().to_path_buf();
+        let result_pipe = pipe_sender(&mut self.background_tasks.history, path);
+        self.async_execute_read_task(path, result_pipe, move |_fs, path| {
+            git_history::note_revisions(&base_path, path, HISTORY_LIMIT)
+        });
+    }
+
+    pub fn poll_history_tasks(&mut self) {
+        self.background_tasks
+            .history
+            .iter_mut()
+            .for_each(|(path, (_tx, rx))| {
+                rx.try_iter().for_each(|result| {
+                    let cell = match result {
+                        Ok(revisions) => MemoryCell::Value(revisions),
+                        Err(err) => {
+                            log::error!("Failed to read history of {}: {err}", path.display());
+                            MemoryCell::ReadError(err)
+                        }
+                    };
+                    self.state.memory.history.insert(path.to_path_buf(), cell);
+                })
+            });
+    }
+
+    /// Replace note with a previous version of it, saved right away
+    pub fn restore_revision(&mut self, path: &Path, source: String) {
+        let Some(node) = self.get_note_mut(path) else {
+            return;
+        };
+        node.data = Note::from_source(source);
+        node.dirty = true;
+        self.save_note_now(path);
+    }
+
+    fn snapshots(&self) -> Option<Snapshots> {
+        self.state.config.snapshots()
+    }
+
+    pub fn snapshots_enabled(&self) -> bool {
+        self.state.config.location.snapshots
+    }
+
+    pub fn get_note_snapshots(&self, path: &Path) -> Option<&MemoryCell<Vec<Revision>>> {
+        self.state.memory.snapshots.get(path)
+    }
+
+    /// Read kept versions of the note, unless they are read or being read
+    pub fn read_note_snapshots_in_background(&mut self, path: &Path) {
+        if self.state.memory.snapshots.contains_key(path) {
+            return;
+        }
+        self.refresh_note_snapshots_in_background(path);
+    }
+
+    /// Stale snapshots stay available until the fresh ones arrive
+    pub fn refresh_note_snapshots_in_background(&mut self, path: &Path) {
+        let Some(snapshots) = self.snapshots() else {
+            return;
+        };
+        self.state
+            .memory
+            .snapshots
+            .entry(path.to_path_buf())
+            .or_insert(MemoryCell::PendingRead);
+        let result_pipe = pipe_sender(&mut self.background_tasks.snapshots, path);
+        self.async_execute_read_task(path, result_pipe, move |fs, path| snapshots.list(fs, path));
+    }
+
+    pub fn poll_snapshot_tasks(&mut self) {
+        self.background_tasks
+            .snapshots
+            .iter_mut()
+            .for_each(|(path, (_tx, rx))| {
+                rx.try_iter().for_each(|result| {
+                    let cell = match result {
+                        Ok(snapshots) => MemoryCell::Value(snapshots),
+                        Err(err) => {
+                            log::error!("Failed to read snapshots of {}: {err}", path.display());
+                            MemoryCell::ReadError(err)
+                        }
+                    };
+                    self.state.memory.snapshots.insert(path.to_path_buf(), cell);
+                })
+            });
+    }
+
+    pub fn dir_state(&self, path: &Path) -> Option<MemoryCellState> {
+        self.state
+            .memory
+            .dirs
+            .get(path)
+            .map(MemoryCellState::state_of_cell)
+    }
+
+    pub fn get_dir_cell(&self, path: &Path) -> Option<&MemoryCell<DataNode<Directory>>> {
+        self.state.memory.dirs.get(path)
+    }
+
+    /// Drop cached directory, including read error, and read it again
+    pub fn reload_dir_in_background(&mut self, path: &Path) {
+        self.state.memory.dirs.remove(path);
+        self.read_dir_in_background(path);
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.state.errors
+    }
+
+    pub fn report_error(&mut self, message: String) {
+        self.state.errors.push(message);
+    }
+
+    pub fn dismiss_errors(&mut self) {
+        self.state.errors.clear();
+    }
+
+    pub fn dir_in_memory(&self, path: &Path) -> bool {
+        self.state.memory.dirs.contains_key(path)
+    }
+
+    pub fn is_selected(&self, path: &Path) -> bool {
+        self.state.current_note_path == path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{Fault, MemFs};
+
+    fn app_on(fs: &MemFs, executor: ThreadPoolExecutor) -> NonBlockingApplication {
+        let config = ApplicationConfig::new(LocationConfig::new(Path::new("/notes"), None));
+        NonBlockingApplication::with_fs(config, Arc::new(fs.clone()), executor).unwrap()
+    }
+
+    /// Application with inline executor and a note open in it
+    fn open(fs: &MemFs, path: &Path, source: &str) -> NonBlockingApplication {
+        let mut app = app_on(fs, ThreadPoolExecutor::inline());
+        fs.write(path, source.as_bytes()).unwrap();
+        app.open_note(path.to_path_buf(), true);
+        app.poll_background_tasks();
+        app
+    }
+
+    fn edit(app: &mut NonBlockingApplication, path: &Path, text: &str) {
+        app.get_note_mut(path).unwrap().data.text = text.to_owned();
+        app.set_dirty(path);
+    }
+
+    fn disk_text(fs: &MemFs, path: &Path) -> String {
+        Note::from_source(fs.read_to_string(path).unwrap()).text
+    }
+
+    #[test]
+    fn load_is_applied_when_polled() {
+        let fs = MemFs::new();
+        let mut app = app_on(&fs, ThreadPoolExecutor::inline());
+        let path = Path::new("/notes/a");
+        fs.write(path, b"text").unwrap();
+
+        app.open_note(path.to_path_buf(), false);
+        assert!(matches!(
+            app.note_state(path),
+            Some(MemoryCellState::PendingRead)
+        ));
+        app.poll_background_tasks();
+        assert_eq!(app.get_note(path).unwrap().data.text, "text");
+        assert!(app.get_metadata(path).is_some());
+
+        app.read_dir_in_background(Path::new("/notes"));
+        app.poll_background_tasks();
+        let dir = app.base_dir().unwrap();
+        assert!(dir.data.entries.contains_key("a"));
+        assert!(dir.data.entries.contains_key(".scratchpad"));
+    }
+
+    #[test]
+    fn save_writes_edits() {
+        let fs = MemFs::new();
+        let path = Path::new("/notes/a");
+        let mut app = open(&fs, path, "text");
+
+        edit(&mut app, path, "edited");
+        app.save_note_now(path);
+        assert!(app.note_is_dirty(path));
+        app.poll_background_tasks();
+
+        assert!(!app.note_is_dirty(path));
+        assert_eq!(disk_text(&fs, path), "edited");
+    }
+
+    #[test]
+    fn flush_waits_for_saves_in_flight_and_saves_every_edit() {
+        let fs = MemFs::new();
+        let mut app = app_on(&fs, ThreadPoolExecutor::with_threads_cnt(1));
+        let (a, b) = (Path::new("/notes/a"), Path::new("/notes/b"));
+        fs.write(a, b"text").unwrap();
+        fs.write(b, b"text").unwrap();
+        app.open_note(a.to_path_buf(), true);
+        app.open_note(b.to_path_buf(), true);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while (app.get_note(a).is_none() || app.get_note(b).is_none()) && Instant::now() < deadline
+        {
+            std::thread::sleep(Duration::from_millis(10));
+            app.poll_background_tasks();
+        }
+
+        fs.inject(a, Fault::Slow(Duration::from_millis(100)));
+        edit(&mut app, a, "first");
+        app.save_note_now(a);
+        edit(&mut app, a, "second");
+        // Never scheduled, e.g. without autosave
+        edit(&mut app, b, "edited");
+        app.flush_saves().unwrap();
+        fs.clear_faults();
+
+        assert!(!app.has_unsaved_notes());
+        assert_eq!(disk_text(&fs, a), "second");
+        assert_eq!(disk_text(&fs, b), "edited");
+    }
+
+    #[test]
+    fn switch_location_saves_edits_without_autosave() {
+        let fs = MemFs::new();
+        let config = ApplicationConfig {
+            autosave: false,
+            ..ApplicationConfig::new(LocationConfig::new(Path::new("/notes"), None))
+        };
+        let mut app = NonBlockingApplication::with_fs(
+            config,
+            Arc::new(fs.clone()),
+            ThreadPoolExecutor::inline(),
+        )
+        .unwrap();
+        let path = Path::new("/notes/a");
+        fs.write(path, b"text").unwrap();
+        app.open_note(path.to_path_buf(), true);
+        app.poll_background_tasks();
+        edit(&mut app, path, "edited");
+
+        fs.inject(path, Fault::DiskFull);
+        let other = LocationConfig::new(Path::new("/other"), None);
+        assert!(app.switch_location(other.clone()).is_err());
+        assert_eq!(app.base_dir_path(), Path::new("/notes"));
+        assert!(app.note_is_dirty(path));
+
+        fs.clear_faults();
+        app.switch_location(other).unwrap();
+        assert_eq!(app.base_dir_path(), Path::new("/other"));
+        assert_eq!(disk_text(&fs, path), "edited");
+    }
+
+    #[test]
+    fn rename_follows_note() {
+        let fs = MemFs::new();
+        let (from, to) = (Path::new("/notes/a"), Path::new("/notes/b"));
+        let mut app = open(&fs, from, "text");
+        edit(&mut app, from, "edited");
+
+        app.rename_in_background(from, to);
+        app.poll_background_tasks();
+        assert_eq!(app.current_note_path(), to);
+        assert!(app.get_note(from).is_none());
+        // Unsaved edits are saved under the new path
+        app.save_note_now(to);
+        app.poll_background_tasks();
+        assert!(!fs.exists(from));
+        assert_eq!(disk_text(&fs, to), "edited");
+
+        fs.write(from, b"").unwrap();
+        app.rename_in_background(to, from);
+        app.poll_background_tasks();
+        assert!(!app.errors().is_empty());
+        assert_eq!(app.current_note_path(), to);
+    }
+
+    #[test]
+    fn trash_closes_tab_and_restore_brings_note_back() {
+        let fs = MemFs::new();
+        let path = Path::new("/notes/a");
+        let mut app = open(&fs, path, "text");
+
+        app.trash_in_background(path);
+        app.poll_background_tasks();
+        assert!(!fs.exists(path));
+        assert!(!app.tabs().contains(path));
+        assert_eq!(app.current_note_path(), app.scratch_pad_path());
+        let name = app.recently_trashed().unwrap().to_owned();
+        app.poll_background_tasks();
+        let trash = app.get_trash().and_then(MemoryCell::value).unwrap();
+        assert_eq!(trash.entries.len(), 1);
+
+        app.restore_from_trash_in_background(&name);
+        app.poll_background_tasks();
+        assert_eq!(disk_text(&fs, path), "text");
+        app.poll_background_tasks();
+        let trash = app.get_trash().and_then(MemoryCell::value).unwrap();
+        assert!(trash.entries.is_empty());
+    }
+
+    #[test]
+    fn external_changes_are_polled() {
+        let fs = MemFs::new();
+        let path = Path::new("/notes/a");
+        let mut app = open(&fs, path, "text");
+
+        fs.write(path, b"theirs").unwrap();
+        app.poll_background_tasks();
+        assert_eq!(app.get_note(path).unwrap().data.text, "theirs");
+        assert!(app.conflict(path).is_none());
+
+        edit(&mut app, path, "mine");
+        fs.write(path, b"theirs again").unwrap();
+        app.poll_background_tasks();
+        assert_eq!(app.get_note(path).unwrap().data.text, "mine");
+        assert!(app.conflict(path).is_some());
+
+        fs.remove_file(path).unwrap();
+        app.poll_background_tasks();
+        let conflict = app.conflict(path).unwrap();
+        assert!(conflict.theirs.is_none());
+    }
+
+    #[test]
+    fn permission_denied_read_is_read_error() {
+        let fs = MemFs::new();
+        let mut app = app_on(&fs, ThreadPoolExecutor::inline());
+        let path = Path::new("/notes/secret");
+        fs.write(path, b"text").unwrap();
+        fs.inject(path, Fault::PermissionDenied);
+
+        app.open_note(path.to_path_buf(), false);
+        app.poll_background_tasks();
+        let cell = app.get_note_cell(path).unwrap();
+        assert!(matches!(cell, MemoryCell::ReadError(_)));
+        assert_eq!(
+            cell.error().unwrap().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+
+        fs.clear_faults();
+        app.reload_note_in_background(path);
+        app.poll_background_tasks();
+        assert_eq!(app.get_note(path).unwrap().data.text, "text");
+    }
+
+    #[test]
+    fn disk_full_save_keeps_edits() {
+        let fs = MemFs::new();
+        let path = Path::new("/notes/a");
+        let mut app = open(&fs, path, "text");
+        fs.inject(Path::new("/notes"), Fault::DiskFull);
+
+        edit(&mut app, path, "edited");
+        app.save_note_now(path);
+        app.poll_background_tasks();
+        let cell = app.get_note_cell(path).unwrap();
+        assert!(matches!(cell, MemoryCell::ValueWriteError(..)));
+        assert_eq!(cell.error().unwrap().kind(), io::ErrorKind::StorageFull);
+        assert!(app.note_is_dirty(path));
+        assert_eq!(disk_text(&fs, path), "text");
+
+        fs.clear_faults();
+        app.save_note_now(path);
+        app.poll_background_tasks();
+        assert!(matches!(
+            app.get_note_cell(path),
+            Some(MemoryCell::Value(_))
+        ));
+        assert_eq!(disk_text(&fs, path), "edited");
+    }
+
+    #[test]
+    fn saves_are_committed_to_git_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ApplicationConfig {
+            commit_delay: Duration::ZERO,
+            ..ApplicationConfig::new(LocationConfig::new(dir.path(), None).with_git_history(true))
+        };
+        let mut app =
+            NonBlockingApplication::with_fs(config, Arc::new(RealFs), ThreadPoolExecutor::inline())
+                .unwrap();
+        let path = dir.path().join("a");
+        std::fs::write(&path, "one").unwrap();
+        app.open_note(path.clone(), true);
+        app.poll_background_tasks();
+
+        edit(&mut app, &path, "two");
+        app.save_note_now(&path);
+        app.poll_background_tasks();
+        app.poll_background_tasks();
+        app.read_note_history_in_background(&path);
+        app.poll_background_tasks();
+        let history = app.get_note_history(&path).and_then(MemoryCell::value);
+        let sources = history
+            .unwrap()
+            .iter()
+            .map(|revision| revision.source.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(sources, ["two"]);
+
+        app.restore_revision(&path, "one".to_owned());
+        app.poll_background_tasks();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "one");
+    }
+
+    #[test]
+    fn flush_commit_waits_for_commit_in_flight() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = ApplicationConfig {
+            commit_delay: Duration::ZERO,
+            ..ApplicationConfig::new(LocationConfig::new(dir.path(), None).with_git_history(true))
+        };
+        let executor = ThreadPoolExecutor::with_threads_cnt(2);
+        let mut app = NonBlockingApplication::with_fs(config, Arc::new(RealFs), executor).unwrap();
+        let path = dir.path().join("a");
+        std::fs::write(&path, "one").unwrap();
+        app.open_note(path.clone(), true);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while app.get_note(&path).is_none() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+            app.poll_background_tasks();
+        }
+
+        edit(&mut app, &path, "two");
+        app.save_note_now(&path);
+        // Save lands and its commit starts in the same poll
+        while app.note_is_dirty(&path) && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+            app.poll_background_tasks();
+        }
+        edit(&mut app, &path, "three");
+        app.save_note_now(&path);
+        app.flush_commit();
+
+        let revisions = git_history::note_revisions(dir.path(), &path, 10).unwrap();
+        assert_eq!(revisions[0].source, "three");
+        assert!(app.errors().is_empty());
+    }
+
+    #[test]
+    fn saves_keep_snapshots_of_prior_versions() {
+        let fs = MemFs::new();
+        let config = ApplicationConfig::new(
+            LocationConfig::new(Path::new("/notes"), None).with_snapshots(true),
+        );
+        let mut app = NonBlockingApplication::with_fs(
+            config,
+            Arc::new(fs.clone()),
+            ThreadPoolExecutor::inline(),
+        )
+        .unwrap();
+        let path = Path::new("/notes/a");
+        fs.write(path, b"one").unwrap();
+        app.open_note(path.to_path_buf(), true);
+        app.poll_background_tasks();
+        app.read_note_snapshots_in_background(path);
+        app.poll_background_tasks();
+        assert!(
+            app.get_note_snapshots(path)
+                .and_then(MemoryCell::value)
+                .unwrap()
+                .is_empty()
+        );
+
+        edit(&mut app, path, "two");
+        app.save_note_now(path);
+        app.poll_background_tasks();
+        app.poll_background_tasks();
+        let snapshots = app.get_note_snapshots(path).and_then(MemoryCell::value);
+        let sources = snapshots
+            .unwrap()
+            .iter()
+            .map(|snapshot| snapshot.source.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(sources, ["one"]);
+        assert!(fs.is_dir(Path::new("/notes/.history/a")));
+    }
+
+    #[test]
+    fn tag_rename_rewrites_loaded_and_unloaded_notes() {
+        let fs = MemFs::new();
+        let loaded = Path::new("/notes/loaded");
+        let mut app = open(&fs, loaded, "#old and #other");
+        let unloaded = Path::new("/notes/dir/unloaded");
+        fs.create_dir_all(Path::new("/notes/dir")).unwrap();
+        fs.write(unloaded, b"---\ntags: [old/nested]\n---\ntext #old\n")
+            .unwrap();
+        app.build_index_in_background();
+        app.poll_background_tasks();
+
+        app.rename_tag_in_background("old", "new");
+        app.poll_background_tasks();
+        app.poll_background_tasks();
+
+        assert_eq!(disk_text(&fs, loaded), "#new and #other");
+        let unloaded_note = Note::from_source(fs.read_to_string(unloaded).unwrap());
+        assert_eq!(unloaded_note.tags(), ["new/nested", "new"]);
+        let index = app.get_index().and_then(MemoryCell::value).unwrap();
+        let tags = index
+            .tags
+            .tree()
+            .into_iter()
+            .map(|tree| tree.tag)
+            .collect::<Vec<String>>();
+        assert_eq!(tags, ["new", "other"]);
+    }
+
+    #[test]
+    fn links_resolve_before_and_after_indexing() {
+        let fs = MemFs::new();
+        let from = Path::new("/notes/from");
+        let mut app = open(&fs, from, "[[Target]] [[dir/deep]]");
+        fs.create_dir_all(Path::new("/notes/dir")).unwrap();
+        fs.write(Path::new("/notes/target.md"), b"").unwrap();
+        fs.write(Path::new("/notes/dir/deep"), b"").unwrap();
+        let resolve = |app: &NonBlockingApplication, target: &str| {
+            let names = app.note_names();
+            let resolved = names.resolve(app.base_dir_path(), from, target);
+            resolved.map(Path::to_path_buf)
+        };
+
+        app.read_dir_in_background(Path::new("/notes"));
+        app.poll_background_tasks();
+        assert_eq!(resolve(&app, "target"), Some("/notes/target.md".into()));
+        assert_eq!(resolve(&app, "dir/deep"), None);
+
+        app.build_index_in_background();
+        app.poll_background_tasks();
+        assert_eq!(resolve(&app, "dir/deep"), Some("/notes/dir/deep".into()));
+        let index = app.get_index().and_then(MemoryCell::value).unwrap();
+        let backlinks = index
+            .links
+            .backlinks(app.base_dir_path(), Path::new("/notes/dir/deep"));
+        assert_eq!(backlinks[0].path, from);
+    }
+
+    #[test]
+    fn rename_rewrites_links_and_is_undone_at_once() {
+        let fs = MemFs::new();
+        let linking = Path::new("/notes/linking");
+        let mut app = open(&fs, linking, "[[target]] and [t](target.md)");
+        edit(&mut app, linking, "unsaved [[target]] and [t](target.md)");
+        fs.create_dir_all(Path::new("/notes/dir")).unwrap();
+        fs.write(Path::new("/notes/target.md"), b"[u](dir/unloaded)")
+            .unwrap();
+        let unloaded = Path::new("/notes/dir/unloaded");
+        fs.write(unloaded, b"[[../target]]").unwrap();
+        app.build_index_in_background();
+        app.poll_background_tasks();
+        let (from, to) = (
+            Path::new("/notes/target.md"),
+            Path::new("/notes/dir/moved.md"),
+        );
+
+        app.plan_rename_in_background(from, to);
+        app.poll_background_tasks();
+        let plan = app
+            .get_rename_plan(from)
+            .and_then(MemoryCell::value)
+            .unwrap();
+        assert_eq!(plan.notes.len(), 3);
+        assert_eq!(plan.link_count(), 4);
+        app.apply_rename_plan_in_background(from);
+        app.poll_background_tasks();
+
+        assert!(app.errors().is_empty());
+        assert!(!fs.exists(from));
+        assert_eq!(disk_text(&fs, to), "[u](unloaded)");
+        assert_eq!(disk_text(&fs, unloaded), "[[moved]]");
+        let expected = "unsaved [[moved]] and [t](dir/moved.md)";
+        assert_eq!(disk_text(&fs, linking), expected);
+        assert_eq!(app.get_note(linking).unwrap().data.text, expected);
+        assert!(!app.note_is_dirty(linking));
+        assert_eq!(app.last_relink().unwrap().link_count, 4);
+
+        app.undo_relink_in_background();
+        app.poll_background_tasks();
+        // Index reads started by file events of the rename land one poll later
+        app.poll_background_tasks();
+        assert!(app.last_relink().is_none());
+        assert!(!fs.exists(to));
+        assert_eq!(disk_text(&fs, from), "[u](dir/unloaded)");
+        assert_eq!(disk_text(&fs, unloaded), "[[../target]]");
+        let original = "unsaved [[target]] and [t](target.md)";
+        assert_eq!(disk_text(&fs, linking), original);
+        assert_eq!(app.get_note(linking).unwrap().data.text, original);
+        let index = app.get_index().and_then(MemoryCell::value).unwrap();
+        let hits = index.links.backlinks(app.base_dir_path(), from);
+        assert_eq!(hits.len(), 3);
+    }
+
+    #[test]
+    fn failed_relink_changes_nothing() {
+        let fs = MemFs::new();
+        let linking = Path::new("/notes/linking");
+        let mut app = open(&fs, linking, "[[target]]");
+        fs.write(Path::new("/notes/target"), b"").unwrap();
+        app.build_index_in_background();
+        app.poll_background_tasks();
+        let (from, to) = (Path::new("/notes/target"), Path::new("/notes/moved"));
+
+        app.plan_rename_in_background(from, to);
+        app.poll_background_tasks();
+        fs.inject(linking, Fault::PermissionDenied);
+        app.apply_rename_plan_in_background(from);
+        app.poll_background_tasks();
+
+        assert_eq!(app.errors().len(), 1);
+        assert!(fs.exists(from));
+        assert!(!fs.exists(to));
+        assert!(app.last_relink().is_none());
+    }
+
+    #[test]
+    fn graph_is_laid_out_again_when_links_change() {
+        let fs = MemFs::new();
+        let a = Path::new("/notes/a");
+        let mut app = open(&fs, a, "[[b]]");
+        fs.write(Path::new("/notes/b"), b"").unwrap();
+        app.build_index_in_background();
+        app.poll_background_tasks();
+
+        app.layout_graph_in_background();
+        app.poll_background_tasks();
+        let layout = app.get_graph().and_then(MemoryCell::value).unwrap();
+        assert_eq!(layout.graph.nodes.len(), 3);
+        assert_eq!(layout.graph.edges.len(), 1);
+        assert_eq!(layout.positions.len(), 3);
+
+        // Same links, only the generation follows
+        edit(&mut app, a, "[[b]] more");
+        app.save_note_now(a);
+        app.poll_background_tasks();
+        app.layout_graph_in_background();
+        let layout = app.get_graph().and_then(MemoryCell::value).unwrap();
+        let index = app.get_index().and_then(MemoryCell::value).unwrap();
+        assert_eq!(layout.generation, index.links.generation());
+
+        edit(&mut app, a, "no links");
+        app.save_note_now(a);
+        app.poll_background_tasks();
+        app.layout_graph_in_background();
+        app.poll_background_tasks();
+        let layout = app.get_graph().and_then(MemoryCell::value).unwrap();
+        assert!(layout.graph.edges.is_empty());
+    }
+
+    #[test]
+    fn slow_read_stays_pending_until_done() {
+        let fs = MemFs::new();
+        let mut app = app_on(&fs, ThreadPoolExecutor::with_threads_cnt(1));
+        let path = Path::new("/notes/a");
+        fs.write(path, b"text").unwrap();
+        fs.inject(path, Fault::Slow(Duration::from_millis(200)));
+
+        app.open_note(path.to_path_buf(), false);
+        app.poll_background_tasks();
+        assert!(matches!(
+            app.note_state(path),
+            Some(MemoryCellState::PendingRead)
+        ));
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while app.get_note(path).is_none() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+            app.poll_background_tasks();
+        }
+        assert_eq!(app.get_note(path).unwrap().data.text, "text");
+    }
+}
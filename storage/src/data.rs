@@ -0,0 +1,185 @@
+use std::{
+    collections::HashMap,
+    fs::Metadata,
+    io,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use notes::links::percent_decode;
+
+use crate::fs::FsEntry;
+use crate::util::to_date_time_utc;
+
+#[derive(Debug, Clone)]
+pub struct DataNode<Value> {
+    pub data: Value,
+    pub dirty: bool,
+}
+
+impl<T> DataNode<T> {
+    pub fn new(data: T) -> Self {
+        Self { data, dirty: false }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileMetadata {
+    pub creation_time: DateTime<Utc>,
+    pub modification_time: DateTime<Utc>,
+}
+
+impl FileMetadata {
+    /// Platforms and file systems without birth time get modification time as creation time
+    pub fn from_path_metadata(metadata: Metadata) -> Self {
+        let modification_time = metadata
+            .modified()
+            .map(to_date_time_utc)
+            .unwrap_or_default();
+        let creation_time = metadata
+            .created()
+            .map(to_date_time_utc)
+            .unwrap_or(modification_time);
+        Self {
+            creation_time,
+            modification_time,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum DirEntry {
+    Dir(PathBuf),
+    File(PathBuf),
+}
+
+impl DirEntry {
+    pub fn path(&self) -> &Path {
+        match self {
+            DirEntry::Dir(path) => path,
+            DirEntry::File(path) => path,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Directory {
+    pub entries: HashMap<String, DirEntry>,
+    /// Icons of notes from their front matter, notes with default icon are absent
+    pub note_icons: HashMap<String, String>,
+    /// Timestamps of entries, absent when they could not be read
+    pub metadata: HashMap<String, FileMetadata>,
+}
+
+impl Directory {
+    /// Entries without a UTF-8 name are skipped
+    pub fn from_entries(fs_entries: Vec<FsEntry>) -> Self {
+        let mut dir = Directory::default();
+        for fs_entry in fs_entries {
+            let Some(name) = fs_entry
+                .path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map(str::to_owned)
+            else {
+                continue;
+            };
+            let entry = if fs_entry.metadata.is_dir {
+                DirEntry::Dir(fs_entry.path)
+            } else {
+                DirEntry::File(fs_entry.path)
+            };
+            dir.metadata.insert(name.clone(), fs_entry.metadata.times);
+            dir.entries.insert(name, entry);
+        }
+        dir
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeletedMetadata {
+    pub deletion_time: DateTime<Utc>,
+    pub origin_path: PathBuf,
+}
+
+impl DeletedMetadata {
+    const HEADER: &str = "[Trash Info]";
+    const PATH_KEY: &str = "Path";
+    const DELETION_DATE_KEY: &str = "DeletionDate";
+
+    pub fn deleted_now(origin_path: PathBuf) -> Self {
+        DeletedMetadata {
+            deletion_time: Utc::now(),
+            origin_path,
+        }
+    }
+
+    /// Serialize into sidecar record stored next to trashed item. Path is URL escaped as the
+    /// freedesktop trash spec asks, so spaces and line breaks in names survive
+    pub fn to_trash_info(&self) -> String {
+        format!(
+            "{}\n{}={}\n{}={}\n",
+            Self::HEADER,
+            Self::PATH_KEY,
+            encode_trash_path(&self.origin_path),
+            Self::DELETION_DATE_KEY,
+            self.deletion_time.to_rfc3339(),
+        )
+    }
+
+    pub fn from_trash_info(trash_info: &str) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_owned());
+
+        let mut lines = trash_info.lines();
+        if lines.next().map(str::trim) != Some(Self::HEADER) {
+            return Err(invalid("missing trash info header"));
+        }
+
+        let mut origin_path = None;
+        let mut deletion_time = None;
+        for (key, value) in lines.filter_map(|line| line.split_once('=')) {
+            match key.trim() {
+                Self::PATH_KEY => origin_path = Some(PathBuf::from(percent_decode(value))),
+                Self::DELETION_DATE_KEY => {
+                    deletion_time = DateTime::parse_from_rfc3339(value.trim())
+                        .ok()
+                        .map(|date_time| date_time.to_utc())
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            origin_path: origin_path.ok_or_else(|| invalid("missing origin path"))?,
+            deletion_time: deletion_time.ok_or_else(|| invalid("missing deletion date"))?,
+        })
+    }
+}
+
+/// Bytes other than unreserved URL characters and `/` as `%XX`
+fn encode_trash_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            byte => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+#[derive(Debug, Clone)]
+pub struct TrashEntry {
+    /// Unique name of item inside trash
+    pub name: String,
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub deleted: DeletedMetadata,
+}
+
+#[derive(Debug, Default)]
+pub struct Trash {
+    pub entries: Vec<TrashEntry>,
+}
@@ -0,0 +1,150 @@
+//! Notes and directories below the base directory
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use notes::front_matter;
+use notes::{DEFAULT_FOLDER_NAME, DEFAULT_ICON, DEFAULT_NAME, Metadata, Note};
+
+use crate::data::{DataNode, DirEntry, Directory, FileMetadata};
+use crate::fs::Fs;
+use crate::snapshots::Snapshots;
+use crate::util::generate_unique_name;
+
+/// Notes with their paths
+pub type Notes = Vec<(PathBuf, Note)>;
+
+pub fn load_dir(fs: &dyn Fs, path: &Path) -> io::Result<DataNode<Directory>> {
+    let mut dir = Directory::from_entries(fs.read_dir(path)?);
+    dir.note_icons = dir
+        .entries
+        .iter()
+        .filter_map(|(name, entry)| match entry {
+            DirEntry::File(path) => Some((name.clone(), read_note_icon(fs, path)?)),
+            DirEntry::Dir(_) => None,
+        })
+        .collect();
+    Ok(DataNode::new(dir))
+}
+
+/// Icon from front matter, without reading the whole note
+pub fn read_note_icon(fs: &dyn Fs, path: &Path) -> Option<String> {
+    let front_matter = front_matter::read_header(fs.open(path).ok()?)
+        .ok()
+        .flatten()?;
+    let icon = Metadata::from_front_matter(front_matter).icon;
+    (icon != DEFAULT_ICON).then_some(icon)
+}
+
+pub fn load_metadata(fs: &dyn Fs, path: &Path) -> io::Result<FileMetadata> {
+    Ok(fs.metadata(path)?.times)
+}
+
+pub fn load_note(fs: &dyn Fs, path: &Path) -> io::Result<DataNode<Note>> {
+    Ok(DataNode::new(Note::from_source(fs.read_to_string(path)?)))
+}
+
+/// Note as it is on disk, `None` when it does not exist
+pub fn read_disk_note(fs: &dyn Fs, path: &Path) -> io::Result<Option<Note>> {
+    match fs.read_to_string(path) {
+        Ok(source) => Ok(Some(Note::from_source(source))),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Write note, keeping the version it replaces when snapshots are given. Failed snapshot does
+/// not stop the save
+pub fn save_note(
+    fs: &dyn Fs,
+    path: &Path,
+    note: &DataNode<Note>,
+    snapshots: Option<&Snapshots>,
+) -> io::Result<DataNode<Note>> {
+    let source = note.data.to_source();
+    if let Some(snapshots) = snapshots
+        && let Err(err) = snapshots.keep_prior(fs, path, &source)
+    {
+        log::warn!("Failed to keep snapshot of {}: {err}", path.display());
+    }
+    fs.write(path, source.as_bytes())?;
+    let mut note = note.clone();
+    note.dirty = false;
+    Ok(note)
+}
+
+pub fn create_note(fs: &dyn Fs, dir_path: &Path) -> io::Result<PathBuf> {
+    let existing_names = fs.names_in(dir_path)?;
+    let name = generate_unique_name(
+        existing_names.iter().map(String::as_str),
+        DEFAULT_NAME.to_owned(),
+    );
+    let path = dir_path.join(name);
+    create_note_at(fs, &path)?;
+    Ok(path)
+}
+
+/// Empty note, fails when the path is taken
+pub fn create_note_at(fs: &dyn Fs, path: &Path) -> io::Result<()> {
+    fs.create_new(path)
+}
+
+pub fn create_dir(fs: &dyn Fs, parent_path: &Path) -> io::Result<PathBuf> {
+    let existing_names = fs.names_in(parent_path)?;
+    let name = generate_unique_name(
+        existing_names.iter().map(String::as_str),
+        DEFAULT_FOLDER_NAME.to_owned(),
+    );
+    let path = parent_path.join(name);
+    fs.create_dir(&path)?;
+    Ok(path)
+}
+
+/// Rename or move note or directory, never replacing an existing one
+pub fn rename(fs: &dyn Fs, from: &Path, to: &Path) -> io::Result<()> {
+    if to.starts_with(from) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Cannot move {} into itself", from.display()),
+        ));
+    }
+    // `Fs::rename` silently replaces existing files
+    if fs.try_exists(to)? {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} already exists", to.display()),
+        ));
+    }
+    fs.rename(from, to)
+}
+
+/// All notes at or below the path, hidden entries except the scratch pad are skipped
+pub fn read_notes(fs: &dyn Fs, path: &Path, scratch_pad_path: &Path) -> io::Result<Notes> {
+    if fs.is_file(path) {
+        let note = Note::from_source(fs.read_to_string(path)?);
+        return Ok(vec![(path.to_path_buf(), note)]);
+    }
+
+    let mut notes = Vec::new();
+    let mut dirs = vec![path.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        for entry in fs.read_dir(&dir)? {
+            let path = entry.path;
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            if hidden && path != scratch_pad_path {
+                continue;
+            }
+            if entry.metadata.is_dir {
+                dirs.push(path);
+                continue;
+            }
+            match fs.read_to_string(&path) {
+                Ok(source) => notes.push((path, Note::from_source(source))),
+                Err(err) => log::warn!("Skip {} in index: {err}", path.display()),
+            }
+        }
+    }
+    Ok(notes)
+}
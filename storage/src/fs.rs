@@ -0,0 +1,709 @@
+//! File system access of the storage, the real disk or an in-memory one for tests
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt::{self, Debug};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Cursor};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::Duration;
+
+use chrono::Utc;
+use notify::event::{CreateKind, ModifyKind, RemoveKind, RenameMode};
+use notify::{Event, EventHandler, EventKind, RecursiveMode, Watcher};
+
+use crate::data::FileMetadata;
+use crate::util::write_atomically;
+
+/// Called from the watcher thread after changes are sent, e.g. to repaint a UI that waits
+/// for input
+#[derive(Clone)]
+pub struct Wake(Arc<dyn Fn() + Send + Sync>);
+
+impl Wake {
+    pub fn new(wake: impl Fn() + Send + Sync + 'static) -> Self {
+        Self(Arc::new(wake))
+    }
+}
+
+impl Default for Wake {
+    fn default() -> Self {
+        Self::new(|| {})
+    }
+}
+
+impl Debug for Wake {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Wake")
+    }
+}
+
+/// Where a watcher sends changes
+#[derive(Debug, Clone)]
+pub struct FsEvents {
+    sender: Sender<notify::Result<Event>>,
+    wake: Wake,
+}
+
+impl FsEvents {
+    pub fn new(sender: Sender<notify::Result<Event>>, wake: Wake) -> Self {
+        Self { sender, wake }
+    }
+
+    fn send(&self, event: notify::Result<Event>) {
+        // Nobody listens once the application switched location
+        if self.sender.send(event).is_ok() {
+            (self.wake.0)();
+        }
+    }
+}
+
+impl EventHandler for FsEvents {
+    fn handle_event(&mut self, event: notify::Result<Event>) {
+        self.send(event);
+    }
+}
+
+/// Operations of the storage on files and directories. Paths are absolute
+pub trait Fs: Debug + Send + Sync {
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+    /// Reader for the beginning of a file, e.g. front matter, without reading it whole
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + Send>>;
+    /// Replace contents so that after a crash the file has either old or new contents
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+    /// Empty file, fails when the path is taken
+    fn create_new(&self, path: &Path) -> io::Result<()>;
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Files and directories with readable metadata, in no particular order
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<FsEntry>>;
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata>;
+    /// Replaces an existing file, like `std::fs::rename`
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Send changes at or below the path until the returned handle is dropped
+    fn watch(&self, path: &Path, events: FsEvents) -> io::Result<Box<dyn Any>>;
+
+    fn try_exists(&self, path: &Path) -> io::Result<bool> {
+        match self.metadata(path) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok_and(|metadata| metadata.is_dir)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        self.metadata(path).is_ok_and(|metadata| !metadata.is_dir)
+    }
+
+    /// Names of entries in directory
+    fn names_in(&self, path: &Path) -> io::Result<Vec<String>> {
+        Ok(self
+            .read_dir(path)?
+            .into_iter()
+            .filter_map(|entry| Some(entry.path.file_name()?.to_str()?.to_owned()))
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FsEntry {
+    pub path: PathBuf,
+    pub metadata: FsMetadata,
+}
+
+#[derive(Debug, Clone)]
+pub struct FsMetadata {
+    pub is_dir: bool,
+    pub times: FileMetadata,
+}
+
+/// Disk through `std::fs`, changes are watched with `notify`
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealFs;
+
+impl Fs for RealFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + Send>> {
+        Ok(Box::new(BufReader::new(File::open(path)?)))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        write_atomically(path, contents)
+    }
+
+    fn create_new(&self, path: &Path) -> io::Result<()> {
+        OpenOptions::new().write(true).create_new(true).open(path)?;
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<FsEntry>> {
+        Ok(fs::read_dir(path)?
+            .flatten()
+            .filter_map(|dirent| {
+                let path = dirent.path();
+                // Symbolic links are followed, broken ones and special files are skipped
+                let metadata = fs::metadata(&path).ok()?;
+                if !metadata.is_dir() && !metadata.is_file() {
+                    return None;
+                }
+                Some(FsEntry {
+                    path,
+                    metadata: FsMetadata::of(metadata),
+                })
+            })
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        fs::metadata(path).map(FsMetadata::of)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn watch(&self, path: &Path, events: FsEvents) -> io::Result<Box<dyn Any>> {
+        let mut watcher = notify::recommended_watcher(events).map_err(io::Error::other)?;
+        watcher
+            .watch(path, RecursiveMode::Recursive)
+            .map_err(io::Error::other)?;
+        Ok(Box::new(watcher))
+    }
+}
+
+impl FsMetadata {
+    fn of(metadata: fs::Metadata) -> Self {
+        Self {
+            is_dir: metadata.is_dir(),
+            times: FileMetadata::from_path_metadata(metadata),
+        }
+    }
+}
+
+/// Injected failure of [`MemFs`] operations at or below a path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Every operation fails with `PermissionDenied`
+    PermissionDenied,
+    /// Operations that take space, writes and creation, fail with `StorageFull`
+    DiskFull,
+    /// Every operation waits that long first
+    Slow(Duration),
+}
+
+/// In-memory file system for deterministic tests, clones share contents. Every change is
+/// reported to watchers, the way a real watcher reports own writes too
+#[derive(Debug, Default, Clone)]
+pub struct MemFs {
+    state: Arc<Mutex<MemFsState>>,
+}
+
+#[derive(Debug, Default)]
+struct MemFsState {
+    nodes: BTreeMap<PathBuf, MemNode>,
+    faults: Vec<(PathBuf, Fault)>,
+    /// By id, so a watch removes only its own watcher
+    watchers: Vec<(u64, PathBuf, FsEvents)>,
+    next_watcher_id: u64,
+}
+
+/// Removes its watcher from [`MemFs`] when dropped
+struct MemWatch {
+    state: Weak<Mutex<MemFsState>>,
+    id: u64,
+}
+
+impl Drop for MemWatch {
+    fn drop(&mut self) {
+        if let Some(state) = self.state.upgrade()
+            && let Ok(mut state) = state.lock()
+        {
+            state.watchers.retain(|(id, _, _)| *id != self.id);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum MemNode {
+    File {
+        contents: Vec<u8>,
+        times: FileMetadata,
+    },
+    Dir {
+        times: FileMetadata,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    /// Takes space
+    Write,
+    Remove,
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} not found", path.display()),
+    )
+}
+
+fn already_exists(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("{} already exists", path.display()),
+    )
+}
+
+impl MemNode {
+    fn times(&self) -> &FileMetadata {
+        match self {
+            MemNode::File { times, .. } | MemNode::Dir { times } => times,
+        }
+    }
+
+    fn new_dir() -> Self {
+        let now = Utc::now();
+        MemNode::Dir {
+            times: FileMetadata {
+                creation_time: now,
+                modification_time: now,
+            },
+        }
+    }
+}
+
+impl MemFs {
+    /// Empty file system with root directory
+    pub fn new() -> Self {
+        let fs = Self::default();
+        fs.lock()
+            .nodes
+            .insert(PathBuf::from("/"), MemNode::new_dir());
+        fs
+    }
+
+    /// Operations at or below the path fail or slow down until faults are cleared
+    pub fn inject(&self, path: &Path, fault: Fault) {
+        self.lock().faults.push((path.to_path_buf(), fault));
+    }
+
+    pub fn clear_faults(&self) {
+        self.lock().faults.clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemFsState> {
+        self.state.lock().unwrap()
+    }
+
+    /// Apply faults of the paths, waiting for slow ones outside of the lock
+    fn check(&self, paths: &[&Path], access: Access) -> io::Result<MutexGuard<'_, MemFsState>> {
+        let delay = {
+            let state = self.lock();
+            let faults = state
+                .faults
+                .iter()
+                .filter(|(fault_path, _)| paths.iter().any(|path| path.starts_with(fault_path)))
+                .map(|(_, fault)| *fault)
+                .collect::<Vec<Fault>>();
+            for fault in &faults {
+                match fault {
+                    Fault::PermissionDenied => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::PermissionDenied,
+                            format!("{} permission denied", paths[0].display()),
+                        ));
+                    }
+                    Fault::DiskFull if access == Access::Write => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::StorageFull,
+                            "no space left on device",
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+            faults
+                .iter()
+                .filter_map(|fault| match fault {
+                    Fault::Slow(delay) => Some(*delay),
+                    _ => None,
+                })
+                .sum::<Duration>()
+        };
+        if !delay.is_zero() {
+            std::thread::sleep(delay);
+        }
+        Ok(self.lock())
+    }
+}
+
+impl MemFsState {
+    fn node(&self, path: &Path) -> io::Result<&MemNode> {
+        self.nodes.get(path).ok_or_else(|| not_found(path))
+    }
+
+    fn file(&self, path: &Path) -> io::Result<&[u8]> {
+        match self.node(path)? {
+            MemNode::File { contents, .. } => Ok(contents),
+            MemNode::Dir { .. } => Err(io::Error::new(
+                io::ErrorKind::IsADirectory,
+                format!("{} is a directory", path.display()),
+            )),
+        }
+    }
+
+    fn check_parent(&self, path: &Path) -> io::Result<()> {
+        let parent = path.parent().ok_or_else(|| not_found(path))?;
+        match self.node(parent)? {
+            MemNode::Dir { .. } => Ok(()),
+            MemNode::File { .. } => Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("{} is not a directory", parent.display()),
+            )),
+        }
+    }
+
+    fn below(&self, path: &Path) -> Vec<PathBuf> {
+        self.nodes
+            .range(path.to_path_buf()..)
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(path))
+            .cloned()
+            .collect()
+    }
+
+    fn notify(&self, kind: EventKind, paths: &[&Path]) {
+        for (_, watched, events) in &self.watchers {
+            if paths.iter().any(|path| path.starts_with(watched)) {
+                let mut event = Event::new(kind);
+                for path in paths {
+                    event = event.add_path(path.to_path_buf());
+                }
+                events.send(Ok(event));
+            }
+        }
+    }
+}
+
+impl Fs for MemFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        let state = self.check(&[path], Access::Read)?;
+        String::from_utf8(state.file(path)?.to_vec())
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn BufRead + Send>> {
+        let state = self.check(&[path], Access::Read)?;
+        Ok(Box::new(Cursor::new(state.file(path)?.to_vec())))
+    }
+
+    fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let mut state = self.check(&[path], Access::Write)?;
+        state.check_parent(path)?;
+        let now = Utc::now();
+        let (creation_time, kind) = match state.nodes.get(path) {
+            Some(MemNode::Dir { .. }) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::IsADirectory,
+                    format!("{} is a directory", path.display()),
+                ));
+            }
+            Some(MemNode::File { times, .. }) => (
+                times.creation_time,
+                EventKind::Modify(ModifyKind::Data(notify::event::DataChange::Content)),
+            ),
+            None => (now, EventKind::Create(CreateKind::File)),
+        };
+        state.nodes.insert(
+            path.to_path_buf(),
+            MemNode::File {
+                contents: contents.to_vec(),
+                times: FileMetadata {
+                    creation_time,
+                    modification_time: now,
+                },
+            },
+        );
+        state.notify(kind, &[path]);
+        Ok(())
+    }
+
+    fn create_new(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.check(&[path], Access::Write)?;
+        state.check_parent(path)?;
+        if state.nodes.contains_key(path) {
+            return Err(already_exists(path));
+        }
+        let now = Utc::now();
+        state.nodes.insert(
+            path.to_path_buf(),
+            MemNode::File {
+                contents: Vec::new(),
+                times: FileMetadata {
+                    creation_time: now,
+                    modification_time: now,
+                },
+            },
+        );
+        state.notify(EventKind::Create(CreateKind::File), &[path]);
+        Ok(())
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.check(&[path], Access::Write)?;
+        state.check_parent(path)?;
+        if state.nodes.contains_key(path) {
+            return Err(already_exists(path));
+        }
+        state.nodes.insert(path.to_path_buf(), MemNode::new_dir());
+        state.notify(EventKind::Create(CreateKind::Folder), &[path]);
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut missing = path
+            .ancestors()
+            .take_while(|ancestor| !self.lock().nodes.contains_key(*ancestor))
+            .collect::<Vec<&Path>>();
+        missing.reverse();
+        for dir in missing {
+            self.create_dir(dir)?;
+        }
+        if self.is_dir(path) {
+            Ok(())
+        } else {
+            Err(already_exists(path))
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<FsEntry>> {
+        let state = self.check(&[path], Access::Read)?;
+        if let MemNode::File { .. } = state.node(path)? {
+            return Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("{} is not a directory", path.display()),
+            ));
+        }
+        Ok(state
+            .below(path)
+            .into_iter()
+            .filter(|key| key.parent() == Some(path))
+            .map(|key| {
+                let node = &state.nodes[&key];
+                FsEntry {
+                    metadata: FsMetadata {
+                        is_dir: matches!(node, MemNode::Dir { .. }),
+                        times: node.times().clone(),
+                    },
+                    path: key,
+                }
+            })
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FsMetadata> {
+        let state = self.check(&[path], Access::Read)?;
+        let node = state.node(path)?;
+        Ok(FsMetadata {
+            is_dir: matches!(node, MemNode::Dir { .. }),
+            times: node.times().clone(),
+        })
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut state = self.check(&[from, to], Access::Remove)?;
+        state.node(from)?;
+        state.check_parent(to)?;
+        if to.starts_with(from) && to != from {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot move {} into itself", from.display()),
+            ));
+        }
+        if let Some(MemNode::Dir { .. }) = state.nodes.get(to) {
+            return Err(already_exists(to));
+        }
+        for key in state.below(from) {
+            if let (Some(node), Ok(relative)) = (state.nodes.remove(&key), key.strip_prefix(from)) {
+                state.nodes.insert(to.join(relative), node);
+            }
+        }
+        state.notify(
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)),
+            &[from, to],
+        );
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.check(&[path], Access::Remove)?;
+        state.file(path)?;
+        state.nodes.remove(path);
+        state.notify(EventKind::Remove(RemoveKind::File), &[path]);
+        Ok(())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut state = self.check(&[path], Access::Remove)?;
+        state.node(path)?;
+        for key in state.below(path) {
+            state.nodes.remove(&key);
+        }
+        state.notify(EventKind::Remove(RemoveKind::Folder), &[path]);
+        Ok(())
+    }
+
+    fn watch(&self, path: &Path, events: FsEvents) -> io::Result<Box<dyn Any>> {
+        let mut state = self.lock();
+        let id = state.next_watcher_id;
+        state.next_watcher_id += 1;
+        state.watchers.push((id, path.to_path_buf(), events));
+        Ok(Box::new(MemWatch {
+            state: Arc::downgrade(&self.state),
+            id,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mem_fs_files_and_dirs() {
+        let fs = MemFs::new();
+        fs.create_dir_all(Path::new("/notes/dir")).unwrap();
+        fs.write(Path::new("/notes/dir/a"), b"text").unwrap();
+        fs.create_new(Path::new("/notes/b")).unwrap();
+
+        assert_eq!(
+            fs.read_to_string(Path::new("/notes/dir/a")).unwrap(),
+            "text"
+        );
+        assert_eq!(
+            fs.create_new(Path::new("/notes/b")).unwrap_err().kind(),
+            io::ErrorKind::AlreadyExists
+        );
+        assert_eq!(
+            fs.write(Path::new("/missing/a"), b"").unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        let mut names = fs.names_in(Path::new("/notes")).unwrap();
+        names.sort();
+        assert_eq!(names, ["b", "dir"]);
+
+        fs.rename(Path::new("/notes/dir"), Path::new("/notes/moved"))
+            .unwrap();
+        assert!(fs.is_file(Path::new("/notes/moved/a")));
+        assert!(!fs.try_exists(Path::new("/notes/dir")).unwrap());
+
+        fs.remove_dir_all(Path::new("/notes/moved")).unwrap();
+        assert_eq!(fs.names_in(Path::new("/notes")).unwrap(), ["b"]);
+    }
+
+    #[test]
+    fn mem_fs_faults() {
+        let fs = MemFs::new();
+        fs.create_dir(Path::new("/notes")).unwrap();
+        fs.write(Path::new("/notes/a"), b"text").unwrap();
+
+        fs.inject(Path::new("/notes"), Fault::DiskFull);
+        assert_eq!(
+            fs.write(Path::new("/notes/a"), b"new").unwrap_err().kind(),
+            io::ErrorKind::StorageFull
+        );
+        assert_eq!(fs.read_to_string(Path::new("/notes/a")).unwrap(), "text");
+        fs.remove_file(Path::new("/notes/a")).unwrap();
+
+        fs.clear_faults();
+        fs.inject(Path::new("/notes/secret"), Fault::PermissionDenied);
+        assert_eq!(
+            fs.create_new(Path::new("/notes/secret"))
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::PermissionDenied
+        );
+        fs.create_new(Path::new("/notes/public")).unwrap();
+    }
+
+    #[test]
+    fn mem_fs_reports_changes_to_watchers() {
+        let fs = MemFs::new();
+        let (events_tx, events) = std::sync::mpsc::channel();
+        let woken = Arc::new(Mutex::new(0));
+        let wake = Wake::new({
+            let woken = Arc::clone(&woken);
+            move || *woken.lock().unwrap() += 1
+        });
+        let watch = fs
+            .watch(Path::new("/notes"), FsEvents::new(events_tx.clone(), wake))
+            .unwrap();
+        let _other = fs
+            .watch(
+                Path::new("/other"),
+                FsEvents::new(events_tx, Wake::default()),
+            )
+            .unwrap();
+
+        fs.create_dir(Path::new("/notes")).unwrap();
+        fs.write(Path::new("/other"), b"").unwrap();
+        fs.write(Path::new("/notes/a"), b"").unwrap();
+
+        let paths = events
+            .try_iter()
+            .flat_map(|event| event.unwrap().paths)
+            .collect::<Vec<PathBuf>>();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("/notes"),
+                PathBuf::from("/other"),
+                PathBuf::from("/notes/a")
+            ]
+        );
+        assert_eq!(*woken.lock().unwrap(), 2);
+
+        drop(watch);
+        fs.write(Path::new("/notes/b"), b"").unwrap();
+        fs.write(Path::new("/other"), b"changed").unwrap();
+        let paths = events
+            .try_iter()
+            .flat_map(|event| event.unwrap().paths)
+            .collect::<Vec<PathBuf>>();
+        assert_eq!(paths, [PathBuf::from("/other")]);
+        assert_eq!(*woken.lock().unwrap(), 2);
+    }
+}
@@ -0,0 +1,239 @@
+//! Version history of a vault kept in a local git repository at its base directory. Commits
+//! are made on the real disk with `git2`, without remotes
+
+use std::io;
+use std::path::Path;
+
+use chrono::DateTime;
+use git2::{ErrorCode, IndexAddOption, Oid, Repository, Signature, Sort};
+
+use crate::history::Revision;
+
+const AUTHOR_NAME: &str = "questionable";
+const AUTHOR_EMAIL: &str = "questionable@localhost";
+/// Changed notes named in commit message, the rest are counted
+const MESSAGE_NAMES: usize = 3;
+
+fn to_io(err: git2::Error) -> io::Error {
+    let kind = match err.code() {
+        ErrorCode::NotFound => io::ErrorKind::NotFound,
+        ErrorCode::Locked => io::ErrorKind::ResourceBusy,
+        _ => io::ErrorKind::Other,
+    };
+    io::Error::new(kind, err.message().to_owned())
+}
+
+/// Hidden entries, like trash and temporary files of atomic writes, are not notes
+fn is_hidden(relative: &Path) -> bool {
+    relative
+        .components()
+        .any(|component| component.as_os_str().to_string_lossy().starts_with('.'))
+}
+
+fn open_or_init(base_path: &Path) -> io::Result<Repository> {
+    match Repository::open(base_path) {
+        Ok(repo) => Ok(repo),
+        Err(err) if err.code() == ErrorCode::NotFound => {
+            log::info!("Start git history in {}", base_path.display());
+            Repository::init(base_path).map_err(to_io)
+        }
+        Err(err) => Err(to_io(err)),
+    }
+}
+
+/// User identity from git config, or a fixed one when there is none
+fn signature(repo: &Repository) -> io::Result<Signature<'static>> {
+    repo.signature()
+        .or_else(|_| Signature::now(AUTHOR_NAME, AUTHOR_EMAIL))
+        .map_err(to_io)
+}
+
+/// Commit every changed note below the base directory, the scratch pad included. Repository is
+/// created on first commit. Returns whether there was anything to commit
+pub fn commit_all(base_path: &Path, scratch_pad_path: &Path) -> io::Result<bool> {
+    let repo = open_or_init(base_path)?;
+    let scratch_pad = scratch_pad_path.strip_prefix(base_path).ok();
+    let mut index = repo.index().map_err(to_io)?;
+    let mut skip_hidden =
+        |path: &Path, _: &[u8]| -> i32 { (is_hidden(path) && Some(path) != scratch_pad).into() };
+    index
+        .add_all(["*"], IndexAddOption::DEFAULT, Some(&mut skip_hidden))
+        .map_err(to_io)?;
+    index.update_all(["*"], None).map_err(to_io)?;
+    index.write().map_err(to_io)?;
+    let tree = repo
+        .find_tree(index.write_tree().map_err(to_io)?)
+        .map_err(to_io)?;
+
+    let parent = match repo.head() {
+        Ok(head) => Some(head.peel_to_commit().map_err(to_io)?),
+        Err(err) if err.code() == ErrorCode::UnbornBranch => None,
+        Err(err) => return Err(to_io(err)),
+    };
+    let parent_tree = parent
+        .as_ref()
+        .map(|parent| parent.tree())
+        .transpose()
+        .map_err(to_io)?;
+    let changes = repo
+        .diff_tree_to_tree(parent_tree.as_ref(), Some(&tree), None)
+        .map_err(to_io)?;
+    let changed_names = changes
+        .deltas()
+        .filter_map(|delta| delta.new_file().path().or(delta.old_file().path()))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect::<Vec<String>>();
+    if changed_names.is_empty() {
+        return Ok(false);
+    }
+
+    let signature = signature(&repo)?;
+    let parents = parent.iter().collect::<Vec<_>>();
+    repo.commit(
+        Some("HEAD"),
+        &signature,
+        &signature,
+        &commit_message(&changed_names),
+        &tree,
+        &parents,
+    )
+    .map_err(to_io)?;
+    Ok(true)
+}
+
+fn commit_message(changed_names: &[String]) -> String {
+    let named = changed_names
+        .iter()
+        .take(MESSAGE_NAMES)
+        .map(String::as_str)
+        .collect::<Vec<&str>>()
+        .join(", ");
+    match changed_names.len().saturating_sub(MESSAGE_NAMES) {
+        0 => format!("Update {named}"),
+        more => format!("Update {named} and {more} more"),
+    }
+}
+
+/// Committed versions of the note, newest first, at most `limit` of them. Empty when the base
+/// directory is not a repository yet
+pub fn note_revisions(
+    base_path: &Path,
+    note_path: &Path,
+    limit: usize,
+) -> io::Result<Vec<Revision>> {
+    let repo = match Repository::open(base_path) {
+        Ok(repo) => repo,
+        Err(err) if err.code() == ErrorCode::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(to_io(err)),
+    };
+    let relative = note_path.strip_prefix(base_path).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "{} is outside of {}",
+                note_path.display(),
+                base_path.display()
+            ),
+        )
+    })?;
+
+    let mut revwalk = repo.revwalk().map_err(to_io)?;
+    match revwalk.push_head() {
+        Ok(()) => {}
+        Err(err) if err.code() == ErrorCode::UnbornBranch => return Ok(Vec::new()),
+        Err(err) => return Err(to_io(err)),
+    }
+    revwalk
+        .set_sorting(Sort::TOPOLOGICAL | Sort::TIME)
+        .map_err(to_io)?;
+
+    let blob_at = |commit: &git2::Commit| -> Option<Oid> {
+        Some(commit.tree().ok()?.get_path(relative).ok()?.id())
+    };
+    let mut revisions = Vec::new();
+    for oid in revwalk {
+        let commit = repo.find_commit(oid.map_err(to_io)?).map_err(to_io)?;
+        let Some(blob_id) = blob_at(&commit) else {
+            continue;
+        };
+        // Commits that left the note as it was are not its revisions
+        if commit
+            .parents()
+            .next()
+            .is_some_and(|parent| blob_at(&parent) == Some(blob_id))
+        {
+            continue;
+        }
+        let blob = repo.find_blob(blob_id).map_err(to_io)?;
+        revisions.push(Revision {
+            id: commit.id().to_string(),
+            time: DateTime::from_timestamp(commit.time().seconds(), 0).unwrap_or_default(),
+            message: commit.summary().unwrap_or_default().to_owned(),
+            source: String::from_utf8_lossy(blob.content()).into_owned(),
+        });
+        if revisions.len() == limit {
+            break;
+        }
+    }
+    Ok(revisions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn commits_only_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (base, scratch_pad) = (dir.path(), dir.path().join(".scratchpad"));
+        fs::write(base.join("a"), "one").unwrap();
+        fs::write(&scratch_pad, "scratch").unwrap();
+        fs::create_dir(base.join(".trash")).unwrap();
+        fs::write(base.join(".trash").join("b"), "trashed").unwrap();
+
+        assert!(commit_all(base, &scratch_pad).unwrap());
+        assert!(!commit_all(base, &scratch_pad).unwrap());
+        let repo = Repository::open(base).unwrap();
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(tree.get_path(Path::new("a")).is_ok());
+        assert!(tree.get_path(Path::new(".scratchpad")).is_ok());
+        assert!(tree.get_path(Path::new(".trash/b")).is_err());
+
+        fs::remove_file(base.join("a")).unwrap();
+        assert!(commit_all(base, &scratch_pad).unwrap());
+        let tree = repo.head().unwrap().peel_to_tree().unwrap();
+        assert!(tree.get_path(Path::new("a")).is_err());
+    }
+
+    #[test]
+    fn revisions_of_note_newest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let (base, scratch_pad) = (dir.path(), dir.path().join(".scratchpad"));
+        let path = base.join("a");
+        assert!(note_revisions(base, &path, 10).unwrap().is_empty());
+
+        fs::write(&path, "one").unwrap();
+        commit_all(base, &scratch_pad).unwrap();
+        fs::write(base.join("b"), "other").unwrap();
+        commit_all(base, &scratch_pad).unwrap();
+        fs::write(&path, "two").unwrap();
+        commit_all(base, &scratch_pad).unwrap();
+
+        let revisions = note_revisions(base, &path, 10).unwrap();
+        let sources = revisions
+            .iter()
+            .map(|revision| revision.source.as_str())
+            .collect::<Vec<&str>>();
+        assert_eq!(sources, ["two", "one"]);
+        assert_eq!(revisions[1].message, "Update a");
+        assert_eq!(note_revisions(base, &path, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn message_counts_names_beyond_limit() {
+        let names = ["a", "b", "c", "d", "e"].map(str::to_owned);
+        assert_eq!(commit_message(&names[..1]), "Update a");
+        assert_eq!(commit_message(&names), "Update a, b, c and 2 more");
+    }
+}
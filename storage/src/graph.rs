@@ -0,0 +1,271 @@
+//! Layout of the link graph: linked notes pull together, notes push apart from their
+//! neighbours on the plane. Repulsion is only counted between nearby notes, on a grid, so a
+//! vault of thousands of notes lays out in a fraction of a second
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+pub type Point = [f32; 2];
+
+/// Distance linked notes settle at
+pub const LINK_LENGTH: f32 = 40.;
+
+/// Iterations of a layout from scratch, a layout that starts from a previous one needs fewer
+const ITERATIONS: usize = 300;
+const REFINE_ITERATIONS: usize = 60;
+
+/// Pull towards the center, keeps unlinked notes and separate groups together
+const GRAVITY: f32 = 0.02;
+
+/// Notes and links between them, links by note index
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graph {
+    pub nodes: Vec<PathBuf>,
+    pub edges: Vec<(usize, usize)>,
+}
+
+impl Graph {
+    /// Links given by note paths, in either direction, each pair once. Links to notes that
+    /// are not in the list are left out
+    pub fn new(mut nodes: Vec<PathBuf>, links: &[(PathBuf, PathBuf)]) -> Self {
+        nodes.sort();
+        nodes.dedup();
+        let index = nodes
+            .iter()
+            .enumerate()
+            .map(|(index, path)| (path, index))
+            .collect::<HashMap<_, _>>();
+        let mut edges = links
+            .iter()
+            .filter_map(|(from, to)| {
+                let (from, to) = (*index.get(from)?, *index.get(to)?);
+                (from != to).then_some((from.min(to), from.max(to)))
+            })
+            .collect::<Vec<_>>();
+        edges.sort();
+        edges.dedup();
+        Self { nodes, edges }
+    }
+
+    /// Notes linked to or from the note
+    pub fn neighbours(&self, node: usize) -> HashSet<usize> {
+        self.edges
+            .iter()
+            .filter_map(|&(a, b)| {
+                if a == node {
+                    Some(b)
+                } else if b == node {
+                    Some(a)
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+/// Graph with a position for each note
+#[derive(Debug, Clone, Default)]
+pub struct GraphLayout {
+    pub graph: Graph,
+    pub positions: Vec<Point>,
+    /// Generation of the link index the graph was taken from
+    pub generation: u64,
+}
+
+impl GraphLayout {
+    /// Lay out the graph, notes of the previous layout start where they were so the picture
+    /// stays familiar as the vault changes
+    pub fn new(graph: Graph, generation: u64, previous: Option<&GraphLayout>) -> Self {
+        let known = previous
+            .map(|previous| {
+                previous
+                    .graph
+                    .nodes
+                    .iter()
+                    .cloned()
+                    .zip(previous.positions.iter().copied())
+                    .collect::<HashMap<_, _>>()
+            })
+            .unwrap_or_default();
+        let mut positions = initial_positions(&graph, &known);
+        let refine = !known.is_empty() && known.len() * 2 >= graph.nodes.len();
+        let (iterations, temperature) = if refine {
+            (REFINE_ITERATIONS, LINK_LENGTH / 4.)
+        } else {
+            (ITERATIONS, LINK_LENGTH * (graph.nodes.len() as f32).sqrt())
+        };
+        lay_out(&graph, &mut positions, iterations, temperature);
+        Self {
+            graph,
+            positions,
+            generation,
+        }
+    }
+}
+
+/// Known notes where they were, new ones next to a known neighbour or on a spiral around the
+/// center
+fn initial_positions(graph: &Graph, known: &HashMap<PathBuf, Point>) -> Vec<Point> {
+    let spiral = |index: usize| {
+        let radius = LINK_LENGTH * (index as f32).sqrt();
+        // Golden angle spreads points evenly
+        let angle = index as f32 * 2.399_963;
+        [radius * angle.cos(), radius * angle.sin()]
+    };
+    let mut positions = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(index, path)| known.get(path).copied().unwrap_or_else(|| spiral(index)))
+        .collect::<Vec<_>>();
+    if known.is_empty() {
+        return positions;
+    }
+    for &(a, b) in &graph.edges {
+        let (new, old) = match (
+            known.contains_key(&graph.nodes[a]),
+            known.contains_key(&graph.nodes[b]),
+        ) {
+            (false, true) => (a, b),
+            (true, false) => (b, a),
+            _ => continue,
+        };
+        let offset = spiral(new + 1);
+        let scale = 0.5 / (new as f32 + 1.).sqrt();
+        positions[new] = [
+            positions[old][0] + offset[0] * scale,
+            positions[old][1] + offset[1] * scale,
+        ];
+    }
+    positions
+}
+
+type Cell = (i32, i32);
+
+fn cell_of(point: Point, size: f32) -> Cell {
+    (
+        (point[0] / size).floor() as i32,
+        (point[1] / size).floor() as i32,
+    )
+}
+
+/// Force-directed layout after Fruchterman and Reingold, with the grid variant of repulsion.
+/// Temperature caps how far a note moves in one step and cools down to zero
+fn lay_out(graph: &Graph, positions: &mut [Point], iterations: usize, temperature: f32) {
+    let count = positions.len();
+    if count < 2 {
+        return;
+    }
+    let k = LINK_LENGTH;
+    let cell_size = 2. * k;
+    let mut displacement = vec![[0f32; 2]; count];
+    let mut grid = HashMap::<Cell, Vec<usize>>::new();
+    for iteration in 0..iterations {
+        grid.values_mut().for_each(Vec::clear);
+        for (index, point) in positions.iter().enumerate() {
+            grid.entry(cell_of(*point, cell_size))
+                .or_default()
+                .push(index);
+        }
+
+        for (index, point) in positions.iter().enumerate() {
+            let (x, y) = cell_of(*point, cell_size);
+            let mut force = [-point[0] * GRAVITY, -point[1] * GRAVITY];
+            for cell in (x - 1..=x + 1).flat_map(|x| (y - 1..=y + 1).map(move |y| (x, y))) {
+                for &other in grid.get(&cell).into_iter().flatten() {
+                    if other == index {
+                        continue;
+                    }
+                    let mut delta = [
+                        point[0] - positions[other][0],
+                        point[1] - positions[other][1],
+                    ];
+                    let mut distance = delta[0].hypot(delta[1]);
+                    if distance < 0.01 {
+                        // Notes on the same spot part in a direction of their own
+                        let angle = index as f32;
+                        delta = [angle.cos(), angle.sin()];
+                        distance = 0.01;
+                    }
+                    if distance < cell_size {
+                        let push = k * k / distance / distance;
+                        force[0] += delta[0] * push;
+                        force[1] += delta[1] * push;
+                    }
+                }
+            }
+            displacement[index] = force;
+        }
+
+        for &(a, b) in &graph.edges {
+            let delta = [
+                positions[a][0] - positions[b][0],
+                positions[a][1] - positions[b][1],
+            ];
+            let pull = delta[0].hypot(delta[1]) / k;
+            for axis in 0..2 {
+                displacement[a][axis] -= delta[axis] * pull;
+                displacement[b][axis] += delta[axis] * pull;
+            }
+        }
+
+        let step_limit = temperature * (1. - iteration as f32 / iterations as f32);
+        for (point, force) in positions.iter_mut().zip(&displacement) {
+            let length = force[0].hypot(force[1]);
+            if length > 0. {
+                let step = length.min(step_limit) / length;
+                point[0] += force[0] * step;
+                point[1] += force[1] * step;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn distance(layout: &GraphLayout, a: usize, b: usize) -> f32 {
+        let (a, b) = (layout.positions[a], layout.positions[b]);
+        (a[0] - b[0]).hypot(a[1] - b[1])
+    }
+
+    #[test]
+    fn linked_notes_are_close() {
+        let nodes = (0..40)
+            .map(|index| PathBuf::from(format!("/v/{index:02}")))
+            .collect();
+        let links = [
+            ("/v/00", "/v/01"),
+            ("/v/01", "/v/00"),
+            ("/v/02", "/v/03"),
+            ("/v/02", "/v/x"),
+        ]
+        .map(|(from, to)| (PathBuf::from(from), PathBuf::from(to)));
+        let graph = Graph::new(nodes, &links);
+        assert_eq!(graph.edges, [(0, 1), (2, 3)]);
+        assert_eq!(graph.neighbours(1), HashSet::from([0]));
+
+        let layout = GraphLayout::new(graph.clone(), 1, None);
+        assert!(
+            layout
+                .positions
+                .iter()
+                .all(|point| point[0].is_finite() && point[1].is_finite())
+        );
+        let unlinked = (4..40)
+            .map(|other| distance(&layout, 0, other))
+            .fold(f32::INFINITY, f32::min);
+        assert!(distance(&layout, 0, 1) < unlinked);
+        assert!(distance(&layout, 0, 1) > LINK_LENGTH / 4.);
+
+        // Relayout keeps notes about where they were
+        let again = GraphLayout::new(graph, 2, Some(&layout));
+        for (before, after) in layout.positions.iter().zip(&again.positions) {
+            let moved = (before[0] - after[0]).hypot(before[1] - after[1]);
+            assert!(moved < LINK_LENGTH);
+        }
+        assert_eq!(again.generation, 2);
+    }
+}
@@ -0,0 +1,77 @@
+//! Previous versions of notes and how they differ from the current text
+
+use chrono::{DateTime, Utc};
+
+/// Note source as it was at some point, newest revisions come first in listings
+#[derive(Debug, Clone)]
+pub struct Revision {
+    /// Commit id for git history
+    pub id: String,
+    pub time: DateTime<Utc>,
+    pub message: String,
+    pub source: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Context(String),
+    Removed(String),
+    Added(String),
+    /// Unchanged lines between hunks are left out
+    Gap,
+}
+
+/// Line diff of `old` into `new` with a few lines of context around changes, empty when equal
+pub fn diff(old: &str, new: &str) -> Vec<DiffLine> {
+    let patch = diffy::create_patch(old, new);
+    let mut lines = Vec::new();
+    for (index, hunk) in patch.hunks().iter().enumerate() {
+        if index > 0 {
+            lines.push(DiffLine::Gap);
+        }
+        lines.extend(hunk.lines().iter().map(|line| match line {
+            diffy::Line::Context(text) => DiffLine::Context(strip_newline(text)),
+            diffy::Line::Delete(text) => DiffLine::Removed(strip_newline(text)),
+            diffy::Line::Insert(text) => DiffLine::Added(strip_newline(text)),
+        }));
+    }
+    lines
+}
+
+fn strip_newline(line: &str) -> String {
+    line.trim_end_matches(['\r', '\n']).to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_marks_changed_lines() {
+        assert!(diff("same\n", "same\n").is_empty());
+        assert_eq!(
+            diff("a\nb\nc\n", "a\nB\nc\n"),
+            [
+                DiffLine::Context("a".to_owned()),
+                DiffLine::Removed("b".to_owned()),
+                DiffLine::Added("B".to_owned()),
+                DiffLine::Context("c".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn distant_changes_are_separated_by_gap() {
+        let old = (0..20).map(|n| format!("{n}\n")).collect::<String>();
+        let new = old
+            .replacen("1\n", "one\n", 1)
+            .replace("18\n", "eighteen\n");
+        assert_eq!(
+            diff(&old, &new)
+                .iter()
+                .filter(|line| **line == DiffLine::Gap)
+                .count(),
+            1
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use std::path::Path;
+
+use notes::Note;
+
+use crate::links::LinkIndex;
+use crate::search::SearchIndex;
+use crate::tags::TagIndex;
+
+/// Indexes over every note of the vault, built in background and kept up to date with edits
+#[derive(Debug, Default)]
+pub struct VaultIndex {
+    pub search: SearchIndex,
+    pub tags: TagIndex,
+    pub links: LinkIndex,
+}
+
+impl VaultIndex {
+    pub fn update(&mut self, path: &Path, note: &Note) {
+        self.search.update(path, &note.text);
+        self.tags.update(path, note.tags());
+        self.links.update(path, note);
+    }
+
+    /// Remove notes at or below the path
+    pub fn remove(&mut self, path: &Path) {
+        self.search.remove(path);
+        self.tags.remove(path);
+        self.links.remove(path);
+    }
+
+    /// Follow renamed note or directory
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        self.search.rename(from, to);
+        self.tags.rename(from, to);
+        self.links.rename(from, to);
+    }
+}
@@ -0,0 +1,25 @@
+//! Notes on disk: a base directory of note files with a scratch pad and a trash, shared by the
+//! desktop application and the command line. [`app::NonBlockingApplication`] keeps them in
+//! memory and does file work in background, on any [`fs::Fs`]
+
+pub mod app;
+pub mod data;
+pub mod files;
+pub mod fs;
+pub mod git_history;
+pub mod graph;
+pub mod history;
+pub mod index;
+pub mod links;
+pub mod location;
+pub mod relink;
+pub mod search;
+pub mod settings;
+pub mod snapshots;
+pub mod tabs;
+pub mod tags;
+pub mod thread_pool;
+pub mod trash;
+pub mod util;
+
+pub use location::LocationConfig;
@@ -0,0 +1,543 @@
+//! Wiki link targets resolved to notes of the vault, and links between notes for backlinks.
+//! A target is a note name with or without extension, a path from the linking note or from the
+//! vault root, or an alias from front matter. Names match case-insensitively:
+//!
+//! ```text
+//! [[Note]]             note.md anywhere, the one next to the linking note first
+//! [[folder/note]]      folder/note.md below the linking note's folder or the vault root
+//! [[../note|Alias]]    note.md in the parent folder, shown as Alias
+//! ```
+//!
+//! Markdown links, `[text](../note.md)`, are paths from the linking note's folder
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+
+use notes::Note;
+use notes::links::{decode_local_url, markdown_links, wiki_links};
+
+use crate::data::{DirEntry, Directory};
+use crate::search::{SearchHit, snippet};
+
+/// Notes of the vault by name, resolves link targets
+#[derive(Debug, Clone, Default)]
+pub struct NoteNames {
+    /// Every note with its aliases
+    notes: BTreeMap<PathBuf, Vec<String>>,
+    /// Lowercase file name and name without extension to notes
+    by_name: HashMap<String, BTreeSet<PathBuf>>,
+    /// Lowercase alias to notes
+    by_alias: HashMap<String, BTreeSet<PathBuf>>,
+}
+
+/// Lowercase file name, and name without extension when it has one
+fn name_keys(path: &Path) -> Vec<String> {
+    let mut keys = Vec::new();
+    if let Some(name) = path.file_name() {
+        keys.push(name.to_string_lossy().to_lowercase());
+    }
+    if path.extension().is_some()
+        && let Some(stem) = path.file_stem()
+    {
+        keys.push(stem.to_string_lossy().to_lowercase());
+    }
+    keys
+}
+
+/// Lowercase path with `/` separators, `.` and `..` applied
+fn normalized(path: &Path) -> String {
+    let mut parts = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                parts.pop();
+            }
+            Component::Normal(part) => parts.push(part.to_string_lossy().to_lowercase()),
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    parts.join("/")
+}
+
+/// Path with `.` and `..` applied, case kept
+pub fn clean_path(path: &Path) -> PathBuf {
+    let mut clean = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                clean.pop();
+            }
+            Component::CurDir => {}
+            component => clean.push(component),
+        }
+    }
+    clean
+}
+
+/// File a markdown link URL in a note points to
+pub(crate) fn url_target(from: &Path, url: &str) -> PathBuf {
+    let from_dir = from.parent().unwrap_or(from);
+    clean_path(&from_dir.join(decode_local_url(url)))
+}
+
+fn insert_key(map: &mut HashMap<String, BTreeSet<PathBuf>>, key: String, path: &Path) {
+    map.entry(key).or_default().insert(path.to_path_buf());
+}
+
+fn remove_key(map: &mut HashMap<String, BTreeSet<PathBuf>>, key: &str, path: &Path) {
+    if let Some(paths) = map.get_mut(key) {
+        paths.remove(path);
+        if paths.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+impl NoteNames {
+    /// Notes in loaded directory listings, hidden ones are skipped like in the index
+    pub fn from_dirs<'a>(dirs: impl IntoIterator<Item = &'a Directory>) -> Self {
+        let mut names = Self::default();
+        for dir in dirs {
+            for (name, entry) in &dir.entries {
+                if let DirEntry::File(path) = entry
+                    && !name.starts_with('.')
+                {
+                    names.insert(path, &[]);
+                }
+            }
+        }
+        names
+    }
+
+    pub fn insert(&mut self, path: &Path, aliases: &[String]) {
+        self.remove(path);
+        for key in name_keys(path) {
+            insert_key(&mut self.by_name, key, path);
+        }
+        for alias in aliases {
+            insert_key(&mut self.by_alias, alias.to_lowercase(), path);
+        }
+        self.notes.insert(path.to_path_buf(), aliases.to_vec());
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        let Some(aliases) = self.notes.remove(path) else {
+            return;
+        };
+        for key in name_keys(path) {
+            remove_key(&mut self.by_name, &key, path);
+        }
+        for alias in aliases {
+            remove_key(&mut self.by_alias, &alias.to_lowercase(), path);
+        }
+    }
+
+    /// Notes at or below the path
+    pub fn paths_below(&self, path: &Path) -> Vec<PathBuf> {
+        self.notes
+            .range(path.to_path_buf()..)
+            .map(|(key, _)| key)
+            .take_while(|key| key.starts_with(path))
+            .cloned()
+            .collect()
+    }
+
+    /// Follow renamed note or directory, aliases are kept
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        for path in self.paths_below(from) {
+            let aliases = self.notes.get(&path).cloned().unwrap_or_default();
+            self.remove(&path);
+            if let Ok(relative) = path.strip_prefix(from) {
+                self.insert(&to.join(relative), &aliases);
+            }
+        }
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.notes.keys().map(PathBuf::as_path)
+    }
+
+    /// Note the target of a link from `from` points to, `None` for missing notes
+    pub fn resolve(&self, base: &Path, from: &Path, target: &str) -> Option<&Path> {
+        let target = target.trim().trim_matches('/');
+        let target_path = Path::new(target);
+        let key = target_path.file_name()?.to_string_lossy().to_lowercase();
+        let from_dir = from.parent().unwrap_or(base);
+        let near = normalized(&from_dir.join(target_path));
+        let from_root = normalized(&base.join(target_path));
+        let suffix = format!("/{}", normalized(target_path));
+
+        let Some(candidates) = self.by_name.get(&key) else {
+            return self.resolve_alias(target, from_dir);
+        };
+        candidates
+            .iter()
+            .filter_map(|path| {
+                let full = normalized(path);
+                let stem = normalized(&path.with_extension(""));
+                let is = |wanted: &str| full == wanted || stem == wanted;
+                // Next to the linking note beats the vault root, which beats a partial path
+                let closeness = if is(&near) {
+                    0
+                } else if is(&from_root) {
+                    1
+                } else if full.ends_with(&suffix) || stem.ends_with(&suffix) {
+                    2
+                } else {
+                    return None;
+                };
+                Some(((closeness, path.components().count()), path.as_path()))
+            })
+            .min_by_key(|(rank, _)| *rank)
+            .map(|(_, path)| path)
+            .or_else(|| self.resolve_alias(target, from_dir))
+    }
+
+    fn resolve_alias(&self, target: &str, from_dir: &Path) -> Option<&Path> {
+        let candidates = self.by_alias.get(&target.to_lowercase())?;
+        candidates
+            .iter()
+            .min_by_key(|path| (path.parent() != Some(from_dir), path.components().count()))
+            .map(PathBuf::as_path)
+    }
+
+    /// Shortest target that resolves to the note, its name without extension when no other
+    /// note has it, the path from the vault root otherwise
+    pub fn link_target(&self, base: &Path, path: &Path) -> String {
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let unique = self
+            .by_name
+            .get(&stem.to_lowercase())
+            .is_some_and(|paths| paths.len() == 1);
+        if unique {
+            return stem;
+        }
+        let relative = path.strip_prefix(base).unwrap_or(path).with_extension("");
+        relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    /// Notes with the query in their path from the vault root, names starting with it first
+    pub fn matching(&self, base: &Path, query: &str, limit: usize) -> Vec<&Path> {
+        let query = query.trim().to_lowercase();
+        let mut matches = self
+            .paths()
+            .filter_map(|path| {
+                let relative = path.strip_prefix(base).unwrap_or(path);
+                let relative_name = relative.to_string_lossy().to_lowercase();
+                if !relative_name.contains(&query) {
+                    return None;
+                }
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                let rank = (!name.starts_with(&query), relative_name.len());
+                Some((rank, path))
+            })
+            .collect::<Vec<_>>();
+        matches.sort();
+        matches
+            .into_iter()
+            .take(limit)
+            .map(|(_, path)| path)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LinkTarget {
+    /// Target of a wiki link, resolved by note names
+    Name(String),
+    /// URL of a markdown link, relative to the linking note
+    Url(String),
+}
+
+/// Link found in a note, with the line it is on
+#[derive(Debug, Clone)]
+struct NoteLink {
+    target: LinkTarget,
+    line_number: usize,
+    line_start: usize,
+    snippet: String,
+    /// Byte range of the link in snippet
+    highlight: Range<usize>,
+}
+
+fn note_links(text: &str) -> Vec<NoteLink> {
+    let wiki = wiki_links(text)
+        .into_iter()
+        .map(|link| (link.range, LinkTarget::Name(link.target.to_owned())));
+    let markdown = markdown_links(text)
+        .into_iter()
+        .map(|link| (link.url_range, LinkTarget::Url(link.url.to_owned())));
+    let mut links = wiki
+        .chain(markdown)
+        .map(|(range, target)| {
+            let line_start = text[..range.start].rfind('\n').map_or(0, |index| index + 1);
+            let line_end = text[range.end..]
+                .find('\n')
+                .map_or(text.len(), |index| range.end + index);
+            let highlight = range.start - line_start..range.end - line_start;
+            let (snippet, highlights) = snippet(&text[line_start..line_end], vec![highlight]);
+            NoteLink {
+                target,
+                line_number: text[..line_start].matches('\n').count(),
+                line_start,
+                snippet,
+                highlight: highlights.into_iter().next().unwrap_or_default(),
+            }
+        })
+        .collect::<Vec<_>>();
+    links.sort_by_key(|link| (link.line_start, link.highlight.start));
+    links
+}
+
+/// Names of every note in the vault and links between them
+#[derive(Debug, Default)]
+pub struct LinkIndex {
+    pub names: NoteNames,
+    links: HashMap<PathBuf, Vec<NoteLink>>,
+    /// Bumped on every change, lets callers cache backlinks
+    generation: u64,
+}
+
+impl LinkIndex {
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn update(&mut self, path: &Path, note: &Note) {
+        self.names.insert(path, &note.metadata.aliases);
+        let links = note_links(&note.text);
+        if links.is_empty() {
+            self.links.remove(path);
+        } else {
+            self.links.insert(path.to_path_buf(), links);
+        }
+        self.generation += 1;
+    }
+
+    /// Remove notes at or below the path
+    pub fn remove(&mut self, path: &Path) {
+        for note_path in self.names.paths_below(path) {
+            self.names.remove(&note_path);
+            self.links.remove(&note_path);
+        }
+        self.generation += 1;
+    }
+
+    /// Follow renamed note or directory
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        for note_path in self.names.paths_below(from) {
+            if let (Some(links), Ok(relative)) =
+                (self.links.remove(&note_path), note_path.strip_prefix(from))
+            {
+                self.links.insert(to.join(relative), links);
+            }
+        }
+        self.names.rename(from, to);
+        self.generation += 1;
+    }
+
+    /// File the link points to, `None` for wiki links to missing notes
+    fn resolve(&self, base: &Path, from: &Path, link: &NoteLink) -> Option<PathBuf> {
+        match &link.target {
+            LinkTarget::Name(target) => self
+                .names
+                .resolve(base, from, target)
+                .map(Path::to_path_buf),
+            LinkTarget::Url(url) => Some(url_target(from, url)),
+        }
+    }
+
+    /// Notes whose links change when the path is renamed or moved: the ones linking to
+    /// something at or below it, and the ones below it with any links
+    pub fn affected_by_rename(&self, base: &Path, path: &Path) -> Vec<PathBuf> {
+        let mut affected = self
+            .links
+            .iter()
+            .filter(|(from, links)| {
+                from.starts_with(path)
+                    || links.iter().any(|link| {
+                        self.resolve(base, from, link)
+                            .is_some_and(|target| target.starts_with(path))
+                    })
+            })
+            .map(|(from, _)| from.clone())
+            .collect::<Vec<_>>();
+        affected.sort();
+        affected
+    }
+
+    /// Links between notes as pairs of linking and linked note, each pair once. Links to
+    /// missing notes, to other files and to the note itself are left out
+    pub fn edges(&self, base: &Path) -> Vec<(PathBuf, PathBuf)> {
+        let mut edges = self
+            .links
+            .iter()
+            .flat_map(|(from, links)| {
+                links
+                    .iter()
+                    .filter_map(|link| self.resolve(base, from, link))
+                    .filter(move |target| target != from && self.names.notes.contains_key(target))
+                    .map(|target| (from.clone(), target))
+            })
+            .collect::<Vec<_>>();
+        edges.sort();
+        edges.dedup();
+        edges
+    }
+
+    /// Lines of other notes linking to the note, by note path and line
+    pub fn backlinks(&self, base: &Path, path: &Path) -> Vec<SearchHit> {
+        let mut hits = self
+            .links
+            .iter()
+            .filter(|(from, _)| *from != path)
+            .flat_map(|(from, links)| {
+                links
+                    .iter()
+                    .filter(|link| self.resolve(base, from, link).as_deref() == Some(path))
+                    .map(|link| SearchHit {
+                        path: from.clone(),
+                        line_number: link.line_number,
+                        line_start: link.line_start,
+                        snippet: link.snippet.clone(),
+                        highlights: vec![link.highlight.clone()],
+                    })
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| (&a.path, a.line_number).cmp(&(&b.path, b.line_number)));
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(paths: &[&str]) -> NoteNames {
+        let mut names = NoteNames::default();
+        for path in paths {
+            names.insert(Path::new(path), &[]);
+        }
+        names
+    }
+
+    #[test]
+    fn resolve_names_and_paths() {
+        let base = Path::new("/v");
+        let names = names(&[
+            "/v/Note.md",
+            "/v/dir/note.md",
+            "/v/dir/sub/other",
+            "/v/x/other",
+        ]);
+        let resolve = |from: &str, target: &str| {
+            names
+                .resolve(base, Path::new(from), target)
+                .map(Path::to_path_buf)
+        };
+
+        assert_eq!(resolve("/v/a", "note"), Some("/v/Note.md".into()));
+        assert_eq!(resolve("/v/dir/a", "Note"), Some("/v/dir/note.md".into()));
+        assert_eq!(
+            resolve("/v/a", "dir/note.md"),
+            Some("/v/dir/note.md".into())
+        );
+        assert_eq!(resolve("/v/dir/a", "../note"), Some("/v/Note.md".into()));
+        assert_eq!(
+            resolve("/v/a", "sub/other"),
+            Some("/v/dir/sub/other".into())
+        );
+        assert_eq!(resolve("/v/a", "other"), Some("/v/x/other".into()));
+        assert_eq!(resolve("/v/a", "missing"), None);
+        assert_eq!(resolve("/v/a", "y/other"), None);
+    }
+
+    #[test]
+    fn aliases_and_link_targets() {
+        let base = Path::new("/v");
+        let mut names = names(&["/v/a/note.md", "/v/b/note.md", "/v/c/unique.md"]);
+        names.insert(Path::new("/v/c/unique.md"), &["Nickname".to_owned()]);
+        assert_eq!(
+            names.resolve(base, Path::new("/v/x"), "nickname"),
+            Some(Path::new("/v/c/unique.md"))
+        );
+        assert_eq!(
+            names.link_target(base, Path::new("/v/c/unique.md")),
+            "unique"
+        );
+        assert_eq!(names.link_target(base, Path::new("/v/a/note.md")), "a/note");
+        assert_eq!(
+            names.matching(base, "no", 10),
+            [Path::new("/v/a/note.md"), Path::new("/v/b/note.md")]
+        );
+
+        names.rename(Path::new("/v/c"), Path::new("/v/d"));
+        assert_eq!(
+            names.resolve(base, Path::new("/v/x"), "Nickname"),
+            Some(Path::new("/v/d/unique.md"))
+        );
+    }
+
+    #[test]
+    fn backlinks_with_context() {
+        let base = Path::new("/v");
+        let mut index = LinkIndex::default();
+        let note = |text: &str| Note::from_text(text.to_owned());
+        index.update(Path::new("/v/target.md"), &note("[[target]] to itself"));
+        index.update(Path::new("/v/a"), &note("intro\nsee [[Target|it]] here\n"));
+        index.update(Path::new("/v/b"), &note("[[other]]"));
+        index.update(Path::new("/v/dir/c"), &note("[t](../target.md#top)"));
+
+        let hits = index.backlinks(base, Path::new("/v/target.md"));
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[1].path, Path::new("/v/dir/c"));
+        assert_eq!(
+            &hits[1].snippet[hits[1].highlights[0].clone()],
+            "../target.md#top"
+        );
+        assert_eq!(hits[0].path, Path::new("/v/a"));
+        assert_eq!((hits[0].line_number, hits[0].line_start), (1, 6));
+        assert_eq!(hits[0].snippet, "see [[Target|it]] here");
+        assert_eq!(
+            &hits[0].snippet[hits[0].highlights[0].clone()],
+            "[[Target|it]]"
+        );
+
+        assert_eq!(
+            index.affected_by_rename(base, Path::new("/v/target.md")),
+            [
+                Path::new("/v/a"),
+                Path::new("/v/dir/c"),
+                Path::new("/v/target.md")
+            ]
+        );
+        assert_eq!(
+            index.affected_by_rename(base, Path::new("/v/dir")),
+            [Path::new("/v/dir/c")]
+        );
+
+        assert_eq!(
+            index.edges(base),
+            [
+                (PathBuf::from("/v/a"), PathBuf::from("/v/target.md")),
+                (PathBuf::from("/v/dir/c"), PathBuf::from("/v/target.md"))
+            ]
+        );
+
+        index.rename(Path::new("/v/target.md"), Path::new("/v/moved.md"));
+        assert!(index.backlinks(base, Path::new("/v/moved.md")).is_empty());
+        index.remove(Path::new("/v/a"));
+        assert!(index.names.paths_below(Path::new("/v/a")).is_empty());
+    }
+}
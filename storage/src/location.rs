@@ -0,0 +1,82 @@
+use std::io;
+use std::ops::Not;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use notes::{DEFAULT_HISTORY_NAME, DEFAULT_TRASH_NAME};
+
+use crate::fs::Fs;
+
+#[derive(Debug, Clone)]
+pub struct LocationConfig {
+    pub base_path: Rc<Path>,
+    pub scratch_pad_path: Rc<Path>,
+    pub trash_path: Rc<Path>,
+    /// Snapshots of notes, see [`crate::snapshots`]
+    pub history_path: Rc<Path>,
+    /// Saves keep prior versions of notes in history directory
+    pub snapshots: bool,
+    /// Saved notes are committed to a git repository in base directory
+    pub git_history: bool,
+}
+
+impl LocationConfig {
+    /// Scratch pad defaults to a hidden file in base directory, trash and history are always there
+    pub fn new(base_path: &Path, scratch_pad_path: Option<&Path>) -> Self {
+        let base_path: Rc<Path> = Rc::from(base_path);
+
+        let scratch_pad_path: Rc<Path> = match scratch_pad_path {
+            Some(path) => Rc::from(path),
+            None => Rc::from(base_path.join(".scratchpad")),
+        };
+
+        let trash_path: Rc<Path> = Rc::from(base_path.join(format!(".{DEFAULT_TRASH_NAME}")));
+        let history_path: Rc<Path> = Rc::from(base_path.join(format!(".{DEFAULT_HISTORY_NAME}")));
+
+        Self {
+            base_path,
+            scratch_pad_path,
+            trash_path,
+            history_path,
+            snapshots: false,
+            git_history: false,
+        }
+    }
+
+    pub fn with_git_history(self, git_history: bool) -> Self {
+        Self {
+            git_history,
+            ..self
+        }
+    }
+
+    pub fn with_snapshots(self, snapshots: bool) -> Self {
+        Self { snapshots, ..self }
+    }
+
+    /// `questionable` in home directory, none when there is no home directory
+    pub fn default_base_path() -> Option<PathBuf> {
+        Some(std::env::home_dir()?.join("questionable"))
+    }
+
+    /// Whether the path is at or below trash, snapshot history or git repository of the vault,
+    /// which are kept by the application and are not notes
+    pub fn is_reserved(&self, path: &Path) -> bool {
+        [
+            &*self.trash_path,
+            &*self.history_path,
+            &self.base_path.join(".git"),
+        ]
+        .iter()
+        .any(|reserved| path.starts_with(reserved))
+    }
+
+    /// Create base directory and scratch pad when they do not exist yet
+    pub fn prepare(&self, fs: &dyn Fs) -> io::Result<()> {
+        fs.create_dir_all(&self.base_path)?;
+        if fs.try_exists(&self.scratch_pad_path)?.not() {
+            fs.write(&self.scratch_pad_path, b"")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,378 @@
+//! Links to a renamed or moved note or directory, rewritten so they keep pointing at it. Wiki
+//! links get the shortest target that still resolves, markdown links a new relative URL. Notes
+//! moved along have their own links fixed as well
+
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use notes::Note;
+use notes::links::{encode_local_path, markdown_links, wiki_links};
+
+use crate::data::DataNode;
+use crate::files::{self, Notes};
+use crate::fs::Fs;
+use crate::links::{NoteNames, url_target};
+use crate::snapshots::Snapshots;
+
+/// Link target replaced in note text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkEdit {
+    /// Byte range of the target in note text
+    pub range: Range<usize>,
+    pub old: String,
+    pub new: String,
+    pub line_number: usize,
+}
+
+/// Changed links of one note
+#[derive(Debug, Clone)]
+pub struct NoteEdits {
+    /// Path of the note before the rename
+    pub path: PathBuf,
+    pub edits: Vec<LinkEdit>,
+}
+
+impl NoteEdits {
+    /// Text with the links changed, `None` when the text no longer has the old targets
+    pub fn apply(&self, text: &str) -> Option<String> {
+        let mut text = text.to_owned();
+        for edit in self.edits.iter().rev() {
+            if text.get(edit.range.clone())? != edit.old {
+                return None;
+            }
+            text.replace_range(edit.range.clone(), &edit.new);
+        }
+        Some(text)
+    }
+}
+
+/// Rename with the links it changes, shown before anything is written
+#[derive(Debug, Clone)]
+pub struct RenamePlan {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Notes with changed links, by path before the rename
+    pub notes: Vec<NoteEdits>,
+}
+
+fn moved_path(from: &Path, to: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(from) {
+        Ok(relative) if relative.as_os_str().is_empty() => to.to_path_buf(),
+        Ok(relative) => to.join(relative),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Path from the vault root with `/` separators
+fn path_from_root(base: &Path, path: &Path) -> String {
+    path.strip_prefix(base)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Wiki link target for the note after the rename, written like the old one: with extension
+/// if it had one
+fn wiki_target(names: &NoteNames, base: &Path, from: &Path, target: &Path, old: &str) -> String {
+    let extension = target
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .filter(|extension| old.to_lowercase().ends_with(&extension.to_lowercase()))
+        .unwrap_or_default();
+    let full = path_from_root(base, target);
+    let candidates = [
+        format!("{}{extension}", names.link_target(base, target)),
+        format!(
+            "{}{extension}",
+            path_from_root(base, &target.with_extension(""))
+        ),
+    ];
+    candidates
+        .into_iter()
+        .find(|candidate| names.resolve(base, from, candidate) == Some(target))
+        .unwrap_or(full)
+}
+
+/// URL of the target relative to the folder
+fn relative_url(from_dir: &Path, target: &Path) -> String {
+    let from = from_dir.components().collect::<Vec<_>>();
+    let to = target.components().collect::<Vec<_>>();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+    let parts = std::iter::repeat_n("..".into(), from.len() - common)
+        .chain(
+            to[common..]
+                .iter()
+                .map(|component| component.as_os_str().to_string_lossy()),
+        )
+        .collect::<Vec<_>>();
+    encode_local_path(&parts.join("/"))
+}
+
+fn line_number(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count()
+}
+
+impl RenamePlan {
+    /// Edits to the notes, given by path before the rename, for links to keep their targets.
+    /// Names are the notes of the vault before the rename
+    pub fn new(base: &Path, names: &NoteNames, from: &Path, to: &Path, notes: &Notes) -> Self {
+        let mut names_after = names.clone();
+        names_after.rename(from, to);
+        let moved = |path: &Path| moved_path(from, to, path);
+
+        let mut notes = notes
+            .iter()
+            .filter_map(|(path, note)| {
+                let text = &note.text;
+                let new_path = moved(path);
+                let mut edits = Vec::new();
+                for link in wiki_links(text) {
+                    let Some(target) = names.resolve(base, path, link.target) else {
+                        continue;
+                    };
+                    let target = moved(target);
+                    if names_after.resolve(base, &new_path, link.target) == Some(&target) {
+                        continue;
+                    }
+                    edits.push(LinkEdit {
+                        range: link.target_range.clone(),
+                        old: link.target.to_owned(),
+                        new: wiki_target(&names_after, base, &new_path, &target, link.target),
+                        line_number: line_number(text, link.range.start),
+                    });
+                }
+                for link in markdown_links(text) {
+                    let target = moved(&url_target(path, link.url));
+                    if url_target(&new_path, link.url) == target {
+                        continue;
+                    }
+                    let new_dir = new_path.parent().unwrap_or(base);
+                    // Heading or query stays
+                    let suffix = link
+                        .url
+                        .find(['#', '?'])
+                        .map_or("", |index| &link.url[index..]);
+                    edits.push(LinkEdit {
+                        range: link.url_range.clone(),
+                        old: link.url.to_owned(),
+                        new: format!("{}{suffix}", relative_url(new_dir, &target)),
+                        line_number: line_number(text, link.url_range.start),
+                    });
+                }
+                edits.sort_by_key(|edit| edit.range.start);
+                (!edits.is_empty()).then(|| NoteEdits {
+                    path: path.clone(),
+                    edits,
+                })
+            })
+            .collect::<Vec<_>>();
+        notes.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Self {
+            from: from.to_path_buf(),
+            to: to.to_path_buf(),
+            notes,
+        }
+    }
+
+    pub fn link_count(&self) -> usize {
+        self.notes.iter().map(|note| note.edits.len()).sum()
+    }
+
+    /// Path after the rename
+    pub fn moved(&self, path: &Path) -> PathBuf {
+        moved_path(&self.from, &self.to, path)
+    }
+}
+
+/// What a rename with rewritten links changed, to put it back
+#[derive(Debug, Clone)]
+pub struct RelinkUndo {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Rewritten notes by path after the rename, with sources before and after
+    pub sources: Vec<(PathBuf, String, String)>,
+    pub link_count: usize,
+}
+
+fn rename_with_snapshots(
+    fs: &dyn Fs,
+    from: &Path,
+    to: &Path,
+    snapshots: Option<&Snapshots>,
+) -> io::Result<()> {
+    files::rename(fs, from, to)?;
+    if let Some(snapshots) = snapshots
+        && let Err(err) = snapshots.rename(fs, from, to)
+    {
+        log::warn!("Failed to move snapshots of {}: {err}", from.display());
+    }
+    Ok(())
+}
+
+/// Put back sources of notes written so far and rename back, after a failed write
+fn roll_back(
+    fs: &dyn Fs,
+    from: &Path,
+    to: &Path,
+    written: &[(PathBuf, String)],
+    snapshots: Option<&Snapshots>,
+) {
+    for (path, source) in written.iter().rev() {
+        if let Err(err) = fs.write(path, source.as_bytes()) {
+            log::error!("Failed to restore {}: {err}", path.display());
+        }
+    }
+    if let Err(err) = rename_with_snapshots(fs, to, from, snapshots) {
+        log::error!("Failed to rename {} back: {err}", to.display());
+    }
+}
+
+/// Rename, then write notes with their links changed. Notes are read from disk unless given,
+/// by path before the rename, e.g. with unsaved edits. When a write fails, written notes and
+/// the rename are put back
+pub fn rename_and_relink(
+    fs: &dyn Fs,
+    plan: &RenamePlan,
+    given: &Notes,
+    snapshots: Option<&Snapshots>,
+) -> io::Result<(Notes, RelinkUndo)> {
+    rename_with_snapshots(fs, &plan.from, &plan.to, snapshots)?;
+    let mut rewritten = Vec::new();
+    let mut sources = Vec::new();
+    let mut written = Vec::new();
+    for note_edits in &plan.notes {
+        let path = plan.moved(&note_edits.path);
+        let result = fs.read_to_string(&path).and_then(|disk_source| {
+            let original = match given.iter().find(|(path, _)| *path == note_edits.path) {
+                Some((_, note)) => note.clone(),
+                None => Note::from_source(disk_source.clone()),
+            };
+            let mut note = original.clone();
+            note.text = note_edits.apply(&note.text).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} changed since its links were found", path.display()),
+                )
+            })?;
+            note.touch();
+            let saved = files::save_note(fs, &path, &DataNode::new(note), snapshots)?;
+            Ok((disk_source, original.to_source(), saved.data))
+        });
+        match result {
+            Ok((disk_source, original, note)) => {
+                written.push((path.clone(), disk_source));
+                sources.push((path.clone(), original, note.to_source()));
+                rewritten.push((path, note));
+            }
+            Err(err) => {
+                roll_back(fs, &plan.from, &plan.to, &written, snapshots);
+                return Err(err);
+            }
+        }
+    }
+    let undo = RelinkUndo {
+        from: plan.from.clone(),
+        to: plan.to.clone(),
+        sources,
+        link_count: plan.link_count(),
+    };
+    Ok((rewritten, undo))
+}
+
+/// Rename back and restore rewritten notes, unless any of them changed since. Returns the
+/// restored notes by their original path
+pub fn undo_relink(
+    fs: &dyn Fs,
+    undo: &RelinkUndo,
+    snapshots: Option<&Snapshots>,
+) -> io::Result<Notes> {
+    for (path, _original, rewritten) in &undo.sources {
+        if fs.read_to_string(path)? != *rewritten {
+            return Err(io::Error::other(format!(
+                "{} changed since the rename, it is not undone",
+                path.display()
+            )));
+        }
+    }
+    rename_with_snapshots(fs, &undo.to, &undo.from, snapshots)?;
+    let mut restored = Vec::new();
+    let mut written = Vec::new();
+    for (path, original, rewritten) in &undo.sources {
+        let path = moved_path(&undo.to, &undo.from, path);
+        let note = Note::from_source(original.clone());
+        if let Err(err) = files::save_note(fs, &path, &DataNode::new(note.clone()), snapshots) {
+            roll_back(fs, &undo.to, &undo.from, &written, snapshots);
+            return Err(err);
+        }
+        written.push((path.clone(), rewritten.clone()));
+        restored.push((path, note));
+    }
+    Ok(restored)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(text: &str) -> Note {
+        Note::from_text(text.to_owned())
+    }
+
+    #[test]
+    fn links_follow_renamed_note() {
+        let base = Path::new("/v");
+        let mut names = NoteNames::default();
+        for path in ["/v/a.md", "/v/dir/b.md", "/v/other/b.md", "/v/c.md"] {
+            names.insert(Path::new(path), &[]);
+        }
+        let notes = vec![
+            (
+                PathBuf::from("/v/c.md"),
+                note("[[a]] [[a.md|A]] [[dir/b]]\n[x](a.md#top) [y](dir/My%20b.md)"),
+            ),
+            (PathBuf::from("/v/dir/b.md"), note("[[../a]] [up](../c.md)")),
+        ];
+
+        let plan = RenamePlan::new(
+            base,
+            &names,
+            Path::new("/v/a.md"),
+            Path::new("/v/new/b.md"),
+            &notes,
+        );
+        assert_eq!(plan.notes.len(), 2);
+        let edited = plan.notes[0].apply(&notes[0].1.text).unwrap();
+        assert_eq!(
+            edited,
+            "[[new/b]] [[new/b.md|A]] [[dir/b]]\n[x](new/b.md#top) [y](dir/My%20b.md)"
+        );
+        assert_eq!(plan.notes[0].edits[2].line_number, 1);
+        assert_eq!(
+            plan.notes[1].apply(&notes[1].1.text).unwrap(),
+            "[[new/b]] [up](../c.md)"
+        );
+        assert_eq!(plan.link_count(), 4);
+        assert_eq!(plan.notes[0].apply("changed"), None);
+
+        // Notes moved along keep links between them and fix the ones out
+        let plan = RenamePlan::new(
+            base,
+            &names,
+            Path::new("/v/dir"),
+            Path::new("/v/deep/er dir"),
+            &notes,
+        );
+        assert_eq!(
+            plan.notes[0].apply(&notes[0].1.text).unwrap(),
+            "[[a]] [[a.md|A]] [[deep/er dir/b]]\n[x](a.md#top) [y](deep/er%20dir/My%20b.md)"
+        );
+        assert_eq!(
+            plan.notes[1].apply(&notes[1].1.text).unwrap(),
+            "[[../a]] [up](../../c.md)"
+        );
+    }
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+const SNIPPET_CONTEXT_CHARS: usize = 60;
+
+/// Inverted index over note text for full-text search
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    notes: HashMap<PathBuf, IndexedNote>,
+    /// Term to number of its occurrences in each note
+    postings: BTreeMap<String, HashMap<PathBuf, u32>>,
+    /// Bumped on every change, lets callers cache search results
+    generation: u64,
+}
+
+#[derive(Debug)]
+struct IndexedNote {
+    text: String,
+    terms_count: u32,
+    unique_terms: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub path: PathBuf,
+    /// Zero based number of the line with the snippet
+    pub line_number: usize,
+    /// Byte offset of the line with the snippet in note text
+    pub line_start: usize,
+    pub snippet: String,
+    /// Byte ranges of matched terms in snippet
+    pub highlights: Vec<Range<usize>>,
+}
+
+/// Lowercase words with their byte ranges in text
+pub fn tokenize(text: &str) -> impl Iterator<Item = (Range<usize>, String)> + '_ {
+    let mut chars = text.char_indices().peekable();
+    std::iter::from_fn(move || {
+        let (start, _) = chars.by_ref().find(|(_, c)| c.is_alphanumeric())?;
+        let mut end = text.len();
+        while let Some((index, c)) = chars.peek() {
+            if !c.is_alphanumeric() {
+                end = *index;
+                break;
+            }
+            chars.next();
+        }
+        Some((start..end, text[start..end].to_lowercase()))
+    })
+}
+
+impl SearchIndex {
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn update(&mut self, path: &Path, text: &str) {
+        self.remove_note(path);
+
+        let mut term_counts = HashMap::<String, u32>::new();
+        let mut terms_count = 0;
+        for (_, term) in tokenize(text) {
+            *term_counts.entry(term).or_default() += 1;
+            terms_count += 1;
+        }
+        for (term, count) in &term_counts {
+            self.postings
+                .entry(term.clone())
+                .or_default()
+                .insert(path.to_path_buf(), *count);
+        }
+        self.notes.insert(
+            path.to_path_buf(),
+            IndexedNote {
+                text: text.to_owned(),
+                terms_count,
+                unique_terms: term_counts.into_keys().collect(),
+            },
+        );
+        self.generation += 1;
+    }
+
+    /// Remove notes at or below the path
+    pub fn remove(&mut self, path: &Path) {
+        for note_path in self.paths_below(path) {
+            self.remove_note(&note_path);
+        }
+        self.generation += 1;
+    }
+
+    /// Follow renamed note or directory
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        for note_path in self.paths_below(from) {
+            let Some(note) = self.notes.get(&note_path) else {
+                continue;
+            };
+            let text = note.text.clone();
+            self.remove_note(&note_path);
+            if let Ok(relative) = note_path.strip_prefix(from) {
+                self.update(&to.join(relative), &text);
+            }
+        }
+        self.generation += 1;
+    }
+
+    fn paths_below(&self, path: &Path) -> Vec<PathBuf> {
+        self.notes
+            .keys()
+            .filter(|key| key.starts_with(path))
+            .cloned()
+            .collect()
+    }
+
+    fn remove_note(&mut self, path: &Path) {
+        let Some(note) = self.notes.remove(path) else {
+            return;
+        };
+        for term in note.unique_terms {
+            if let Some(notes) = self.postings.get_mut(&term) {
+                notes.remove(path);
+                if notes.is_empty() {
+                    self.postings.remove(&term);
+                }
+            }
+        }
+    }
+
+    /// Notes containing every query word ranked by TF-IDF, the last word also matches as a prefix
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query_terms = tokenize(query).map(|(_, term)| term).collect::<Vec<_>>();
+        let Some(last_index) = query_terms.len().checked_sub(1) else {
+            return Vec::new();
+        };
+
+        let notes_count = self.notes.len() as f32;
+        let mut scores = HashMap::<&Path, f32>::new();
+        let mut matched_terms = HashSet::<&str>::new();
+        for (index, query_term) in query_terms.iter().enumerate() {
+            let candidates = if index == last_index {
+                self.postings
+                    .range(query_term.clone()..)
+                    .take_while(|(term, _)| term.starts_with(query_term.as_str()))
+                    .collect::<Vec<_>>()
+            } else {
+                self.postings
+                    .get_key_value(query_term)
+                    .into_iter()
+                    .collect::<Vec<_>>()
+            };
+
+            let mut term_scores = HashMap::<&Path, f32>::new();
+            for (term, notes) in candidates {
+                matched_terms.insert(term);
+                let idf = (1. + notes_count / notes.len() as f32).ln();
+                for (path, count) in notes {
+                    let terms_count = self.notes[path].terms_count.max(1) as f32;
+                    let score = *count as f32 / terms_count * idf;
+                    let best = term_scores.entry(path.as_path()).or_default();
+                    *best = best.max(score);
+                }
+            }
+
+            if index == 0 {
+                scores = term_scores;
+            } else {
+                scores.retain(|path, _| term_scores.contains_key(path));
+                scores
+                    .iter_mut()
+                    .for_each(|(path, score)| *score += term_scores[path]);
+            }
+        }
+
+        let mut ranked = scores.into_iter().collect::<Vec<_>>();
+        ranked.sort_by(|(a_path, a), (b_path, b)| b.total_cmp(a).then_with(|| a_path.cmp(b_path)));
+        ranked
+            .into_iter()
+            .take(limit)
+            .map(|(path, _score)| self.hit(path, &matched_terms))
+            .collect()
+    }
+
+    fn hit(&self, path: &Path, matched_terms: &HashSet<&str>) -> SearchHit {
+        let text = &self.notes[path].text;
+        let mut line_start = 0;
+        for (line_number, line) in text.split_inclusive('\n').enumerate() {
+            let highlights = tokenize(line)
+                .filter(|(_, term)| matched_terms.contains(term.as_str()))
+                .map(|(range, _)| range)
+                .collect::<Vec<_>>();
+            if !highlights.is_empty() {
+                let (snippet, highlights) = snippet(line.trim_end(), highlights);
+                return SearchHit {
+                    path: path.to_path_buf(),
+                    line_number,
+                    line_start,
+                    snippet,
+                    highlights,
+                };
+            }
+            line_start += line.len();
+        }
+
+        SearchHit {
+            path: path.to_path_buf(),
+            line_number: 0,
+            line_start: 0,
+            snippet: String::new(),
+            highlights: Vec::new(),
+        }
+    }
+}
+
+/// Cut long line around the first highlight and shift highlights accordingly
+pub(crate) fn snippet(line: &str, highlights: Vec<Range<usize>>) -> (String, Vec<Range<usize>>) {
+    let first = highlights.first().map_or(0, |range| range.start);
+    let start = line[..first]
+        .char_indices()
+        .rev()
+        .nth(SNIPPET_CONTEXT_CHARS)
+        .map_or(0, |(index, _)| index);
+    let end = line[first..]
+        .char_indices()
+        .nth(SNIPPET_CONTEXT_CHARS * 2)
+        .map_or(line.len(), |(index, _)| first + index);
+
+    let prefix = if start > 0 { "…" } else { "" };
+    let suffix = if end < line.len() { "…" } else { "" };
+    let shift = |offset: usize| offset - start + prefix.len();
+    let highlights = highlights
+        .into_iter()
+        .filter(|range| range.start >= start && range.end <= end)
+        .map(|range| shift(range.start)..shift(range.end))
+        .collect();
+
+    (format!("{prefix}{}{suffix}", &line[start..end]), highlights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index(notes: &[(&str, &str)]) -> SearchIndex {
+        let mut index = SearchIndex::default();
+        for (path, text) in notes {
+            index.update(Path::new(path), text);
+        }
+        index
+    }
+
+    fn paths(hits: &[SearchHit]) -> Vec<&Path> {
+        hits.iter().map(|hit| hit.path.as_path()).collect()
+    }
+
+    #[test]
+    fn tokenize_words() {
+        let tokens = tokenize("Hello, мир! x2").collect::<Vec<_>>();
+        assert_eq!(
+            tokens,
+            [
+                (0..5, "hello".to_owned()),
+                (7..13, "мир".to_owned()),
+                (15..17, "x2".to_owned())
+            ]
+        );
+    }
+
+    #[test]
+    fn all_words_must_match() {
+        let index = index(&[("/a", "apple banana"), ("/b", "apple cherry")]);
+        assert_eq!(paths(&index.search("apple", 10)).len(), 2);
+        assert_eq!(paths(&index.search("apple cherry", 10)), [Path::new("/b")]);
+        assert!(index.search("banana cherry", 10).is_empty());
+    }
+
+    #[test]
+    fn last_word_matches_prefix() {
+        let index = index(&[("/a", "searching"), ("/b", "sea")]);
+        assert_eq!(paths(&index.search("sea", 10)).len(), 2);
+        assert_eq!(paths(&index.search("sear", 10)), [Path::new("/a")]);
+        assert!(index.search("sear x", 10).is_empty());
+    }
+
+    #[test]
+    fn rank_by_term_frequency() {
+        let index = index(&[
+            ("/rare", "rust and many other words here"),
+            ("/often", "rust rust rust"),
+        ]);
+        assert_eq!(
+            paths(&index.search("rust", 10)),
+            [Path::new("/often"), Path::new("/rare")]
+        );
+    }
+
+    #[test]
+    fn hit_points_to_matching_line() {
+        let index = index(&[("/a", "first line\nsecond Line with needle\n")]);
+        let hit = &index.search("needle", 10)[0];
+        assert_eq!(hit.line_number, 1);
+        assert_eq!(hit.line_start, 11);
+        assert_eq!(hit.snippet, "second Line with needle");
+        assert_eq!(&hit.snippet[hit.highlights[0].clone()], "needle");
+    }
+
+    #[test]
+    fn long_line_snippet_is_cut_around_match() {
+        let text = format!("{} needle {}", "a ".repeat(100), "b ".repeat(100));
+        let index = index(&[("/a", &text)]);
+        let hit = &index.search("needle", 10)[0];
+        assert!(hit.snippet.starts_with('…') && hit.snippet.ends_with('…'));
+        assert_eq!(&hit.snippet[hit.highlights[0].clone()], "needle");
+    }
+
+    #[test]
+    fn update_rename_and_remove() {
+        let mut index = index(&[("/dir/a", "old text"), ("/b", "other")]);
+        index.update(Path::new("/dir/a"), "new text");
+        assert!(index.search("old", 10).is_empty());
+
+        index.rename(Path::new("/dir"), Path::new("/moved"));
+        assert_eq!(paths(&index.search("new", 10)), [Path::new("/moved/a")]);
+
+        index.remove(Path::new("/moved"));
+        assert!(index.search("new", 10).is_empty());
+        assert!(index.postings.keys().all(|term| term == "other"));
+    }
+}
@@ -0,0 +1,220 @@
+//! Settings of vaults and saving, kept in `config.toml` of the platform config directory,
+//! e.g. `~/.config/questionable/config.toml` on Linux. The desktop application keeps its own
+//! settings in the same file, the command line reads only these
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::app::{ApplicationConfig, DEFAULT_SAVE_DELAY};
+use crate::location::LocationConfig;
+use crate::snapshots::Retention;
+use crate::util::write_atomically;
+
+pub const APP_DIR_NAME: &str = "questionable";
+pub const CONFIG_FILE_NAME: &str = "config.toml";
+const DEFAULT_VAULT_NAME: &str = "Notes";
+const SECONDS_IN_DAY: u64 = 24 * 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotesSettings {
+    pub autosave: bool,
+    pub autosave_delay_ms: u64,
+    /// Snapshots kept per note in vaults with snapshots
+    pub snapshot_max_count: usize,
+    /// Snapshots older than that are removed
+    pub snapshot_max_age_days: u64,
+    /// Name of the open vault
+    pub current_vault: String,
+    /// Never empty, an empty list is read as the default vault
+    #[serde(deserialize_with = "non_empty_vaults")]
+    pub vaults: Vec<VaultSettings>,
+}
+
+/// Named notes tree with its own scratch pad and trash
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VaultSettings {
+    pub name: String,
+    #[serde(flatten)]
+    pub location: LocationSettings,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LocationSettings {
+    pub base_path: PathBuf,
+    /// Hidden file in base directory when not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scratch_pad_path: Option<PathBuf>,
+    /// Commit saved notes to a git repository in the notes folder
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub git_history: bool,
+    /// Keep prior versions of saved notes in a hidden folder of the notes folder
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub snapshots: bool,
+}
+
+impl Default for NotesSettings {
+    fn default() -> Self {
+        Self {
+            autosave: true,
+            autosave_delay_ms: DEFAULT_SAVE_DELAY.as_millis() as u64,
+            snapshot_max_count: Retention::default().max_count,
+            snapshot_max_age_days: Retention::default().max_age.as_secs() / SECONDS_IN_DAY,
+            current_vault: DEFAULT_VAULT_NAME.to_owned(),
+            vaults: vec![Default::default()],
+        }
+    }
+}
+
+impl Default for VaultSettings {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_VAULT_NAME.to_owned(),
+            location: Default::default(),
+        }
+    }
+}
+
+impl Default for LocationSettings {
+    fn default() -> Self {
+        Self {
+            // Empty without a home directory, the vault fails to open and is set in settings
+            base_path: LocationConfig::default_base_path().unwrap_or_default(),
+            scratch_pad_path: None,
+            git_history: false,
+            snapshots: false,
+        }
+    }
+}
+
+fn non_empty_vaults<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<VaultSettings>, D::Error> {
+    let mut vaults = Vec::<VaultSettings>::deserialize(deserializer)?;
+    if vaults.is_empty() {
+        vaults.push(Default::default());
+    }
+    Ok(vaults)
+}
+
+pub fn config_path() -> Option<PathBuf> {
+    Some(
+        dirs::config_dir()?
+            .join(APP_DIR_NAME)
+            .join(CONFIG_FILE_NAME),
+    )
+}
+
+/// Settings from the config file, defaults when there is no config file yet or it cannot be read
+pub fn load_config<T: DeserializeOwned + Default>() -> T {
+    let Some(path) = config_path() else {
+        log::warn!("No config directory, default settings are used");
+        return T::default();
+    };
+    match read_toml(&path) {
+        Ok(settings) => settings,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => T::default(),
+        Err(err) => {
+            log::error!("Failed to read {}: {err}", path.display());
+            T::default()
+        }
+    }
+}
+
+pub fn read_toml<T: DeserializeOwned>(path: &Path) -> io::Result<T> {
+    toml::from_str(&fs::read_to_string(path)?)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+/// Write atomically, creating missing parent directories
+pub fn write_toml<T: Serialize>(path: &Path, value: &T) -> io::Result<()> {
+    let toml = toml::to_string_pretty(value)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    write_atomically(path, toml.as_bytes())
+}
+
+impl LocationSettings {
+    pub fn location_config(&self) -> LocationConfig {
+        LocationConfig::new(&self.base_path, self.scratch_pad_path.as_deref())
+            .with_git_history(self.git_history)
+            .with_snapshots(self.snapshots)
+    }
+}
+
+impl NotesSettings {
+    pub fn save_delay(&self) -> Duration {
+        Duration::from_millis(self.autosave_delay_ms)
+    }
+
+    pub fn snapshot_retention(&self) -> Retention {
+        Retention {
+            max_count: self.snapshot_max_count,
+            max_age: Duration::from_secs(self.snapshot_max_age_days * SECONDS_IN_DAY),
+            ..Default::default()
+        }
+    }
+
+    /// Open vault, the first one when there is no vault with the current name
+    pub fn vault(&self) -> &VaultSettings {
+        self.vault_named(&self.current_vault)
+            .or(self.vaults.first())
+            .expect("settings have at least one vault")
+    }
+
+    pub fn vault_named(&self, name: &str) -> Option<&VaultSettings> {
+        self.vaults.iter().find(|vault| vault.name == name)
+    }
+
+    /// Config of the open vault
+    pub fn application_config(&self) -> ApplicationConfig {
+        self.vault_config(self.vault())
+    }
+
+    pub fn vault_config(&self, vault: &VaultSettings) -> ApplicationConfig {
+        ApplicationConfig {
+            autosave: self.autosave,
+            save_delay: self.save_delay(),
+            snapshot_retention: self.snapshot_retention(),
+            ..ApplicationConfig::new(vault.location.location_config())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vaults_are_looked_up_by_name() {
+        let settings = toml::from_str::<NotesSettings>(
+            "current_vault = \"gone\"\nsnapshot_max_count = 3\n\
+             [[vaults]]\nname = \"home\"\nbase_path = \"/home/notes\"\n\
+             [[vaults]]\nname = \"work\"\nbase_path = \"/work\"\n\
+             scratch_pad_path = \"/work/scratch.md\"\ngit_history = true\nsnapshots = true\n",
+        )
+        .unwrap();
+        assert_eq!(settings.vault().name, "home");
+
+        let work = settings.vault_config(settings.vault_named("work").unwrap());
+        assert_eq!(&*work.location.base_path, Path::new("/work"));
+        assert_eq!(
+            &*work.location.scratch_pad_path,
+            Path::new("/work/scratch.md")
+        );
+        assert!(work.location.git_history);
+        assert_eq!(work.snapshots().unwrap().retention.max_count, 3);
+        assert!(settings.vault_named("gone").is_none());
+
+        let settings = toml::from_str::<NotesSettings>("vaults = []\n").unwrap();
+        assert_eq!(settings.vault(), &VaultSettings::default());
+    }
+}
@@ -0,0 +1,289 @@
+//! Prior versions of notes kept in a hidden history directory of the vault, without git.
+//! Snapshots of a note live in a directory at the same relative path as the note, named by
+//! time and content hash:
+//!
+//! ```text
+//! .history/folder/note/1718000000000-8c1b2a9d3e4f5a6b
+//! ```
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::fs::Fs;
+use crate::history::Revision;
+
+/// Which snapshots are kept for each note
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retention {
+    /// Newest snapshots kept, older ones are removed
+    pub max_count: usize,
+    /// Snapshots older than that are removed
+    pub max_age: Duration,
+    /// Snapshot is taken only when the newest one is older than that, so a burst of saves
+    /// leaves the version before it
+    pub min_interval: Duration,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            max_count: 50,
+            max_age: Duration::from_secs(30 * 24 * 60 * 60),
+            min_interval: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+/// Where and how long snapshots of a vault are kept
+#[derive(Debug, Clone)]
+pub struct Snapshots {
+    pub base_path: PathBuf,
+    pub history_path: PathBuf,
+    pub retention: Retention,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct SnapshotName {
+    time: DateTime<Utc>,
+    hash: u64,
+}
+
+impl SnapshotName {
+    fn parse(name: &str) -> Option<Self> {
+        let (millis, hash) = name.split_once('-')?;
+        Some(Self {
+            time: DateTime::from_timestamp_millis(millis.parse().ok()?)?,
+            hash: u64::from_str_radix(hash, 16).ok()?,
+        })
+    }
+
+    fn format(&self) -> String {
+        format!("{}-{:016x}", self.time.timestamp_millis(), self.hash)
+    }
+}
+
+/// FNV-1a, stable across builds unlike `DefaultHasher`, as hashes are kept on disk
+fn content_hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+impl Snapshots {
+    fn dir_of(&self, note_path: &Path) -> io::Result<PathBuf> {
+        let relative = note_path.strip_prefix(&self.base_path).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is outside of the vault", note_path.display()),
+            )
+        })?;
+        Ok(self.history_path.join(relative))
+    }
+
+    /// Snapshot names of the note, newest first
+    fn names(&self, fs: &dyn Fs, dir: &Path) -> io::Result<Vec<(SnapshotName, PathBuf)>> {
+        if !fs.try_exists(dir)? {
+            return Ok(Vec::new());
+        }
+        let mut names = fs
+            .read_dir(dir)?
+            .into_iter()
+            .filter(|entry| !entry.metadata.is_dir)
+            .filter_map(|entry| {
+                let name = SnapshotName::parse(entry.path.file_name()?.to_str()?)?;
+                Some((name, entry.path))
+            })
+            .collect::<Vec<_>>();
+        names.sort_by_key(|(name, _)| std::cmp::Reverse(name.time));
+        Ok(names)
+    }
+
+    /// Keep the note as it is on disk before `source` replaces it. Versions already kept are
+    /// not kept twice
+    pub fn keep_prior(&self, fs: &dyn Fs, note_path: &Path, source: &str) -> io::Result<()> {
+        // Scratch pad may live outside of the vault, it has no snapshots then
+        if !note_path.starts_with(&self.base_path) {
+            return Ok(());
+        }
+        let prior = match fs.read_to_string(note_path) {
+            Ok(prior) => prior,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        if prior == source || prior.is_empty() {
+            return Ok(());
+        }
+        self.keep(fs, note_path, &prior, Utc::now())
+    }
+
+    /// Keep the note as it is on disk, e.g. before another program changes it
+    pub fn keep_current(&self, fs: &dyn Fs, note_path: &Path) -> io::Result<()> {
+        // Empty notes are not kept, so any version on disk differs from an empty source
+        self.keep_prior(fs, note_path, "")
+    }
+
+    fn keep(
+        &self,
+        fs: &dyn Fs,
+        note_path: &Path,
+        source: &str,
+        now: DateTime<Utc>,
+    ) -> io::Result<()> {
+        let dir = self.dir_of(note_path)?;
+        let names = self.names(fs, &dir)?;
+        let hash = content_hash(source);
+        let min_interval = TimeDelta::from_std(self.retention.min_interval).unwrap_or_default();
+        let recent = names
+            .first()
+            .is_some_and(|(newest, _)| now - newest.time < min_interval);
+        if recent || names.iter().any(|(name, _)| name.hash == hash) {
+            return Ok(());
+        }
+
+        fs.create_dir_all(&dir)?;
+        let name = SnapshotName { time: now, hash };
+        fs.write(&dir.join(name.format()), source.as_bytes())?;
+
+        let max_age = TimeDelta::from_std(self.retention.max_age).unwrap_or(TimeDelta::MAX);
+        for (index, (name, path)) in names.iter().enumerate() {
+            // The new snapshot is the newest one kept
+            if index + 1 >= self.retention.max_count || now - name.time > max_age {
+                fs.remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Kept versions of the note, newest first
+    pub fn list(&self, fs: &dyn Fs, note_path: &Path) -> io::Result<Vec<Revision>> {
+        let dir = self.dir_of(note_path)?;
+        self.names(fs, &dir)?
+            .into_iter()
+            .map(|(name, path)| {
+                Ok(Revision {
+                    id: name.format(),
+                    time: name.time,
+                    message: String::new(),
+                    source: fs.read_to_string(&path)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Snapshots follow renamed or moved note or folder
+    pub fn rename(&self, fs: &dyn Fs, from: &Path, to: &Path) -> io::Result<()> {
+        let from = self.dir_of(from)?;
+        if !fs.try_exists(&from)? {
+            return Ok(());
+        }
+        let to = self.dir_of(to)?;
+        if let Some(parent) = to.parent() {
+            fs.create_dir_all(parent)?;
+        }
+        fs.rename(&from, &to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::MemFs;
+
+    fn snapshots(retention: Retention) -> Snapshots {
+        Snapshots {
+            base_path: PathBuf::from("/notes"),
+            history_path: PathBuf::from("/notes/.history"),
+            retention,
+        }
+    }
+
+    fn sources(fs: &MemFs, snapshots: &Snapshots, path: &Path) -> Vec<String> {
+        snapshots
+            .list(fs, path)
+            .unwrap()
+            .into_iter()
+            .map(|revision| revision.source)
+            .collect()
+    }
+
+    #[test]
+    fn prior_versions_are_kept_once() {
+        let fs = MemFs::new();
+        fs.create_dir_all(Path::new("/notes/dir")).unwrap();
+        let path = Path::new("/notes/dir/a");
+        let snapshots = snapshots(Retention {
+            min_interval: Duration::ZERO,
+            ..Default::default()
+        });
+
+        snapshots.keep_prior(&fs, path, "one").unwrap();
+        fs.write(path, b"one").unwrap();
+        let start = Utc::now();
+        for (offset, source) in [(1, "one"), (2, "two"), (3, "one")] {
+            let time = start + TimeDelta::seconds(offset);
+            snapshots.keep(&fs, path, source, time).unwrap();
+        }
+
+        assert_eq!(sources(&fs, &snapshots, path), ["two", "one"]);
+        assert!(fs.is_dir(Path::new("/notes/.history/dir/a")));
+    }
+
+    #[test]
+    fn saves_within_interval_leave_one_snapshot() {
+        let fs = MemFs::new();
+        let path = Path::new("/notes/a");
+        let snapshots = snapshots(Default::default());
+        let start = Utc::now();
+        for (offset, source) in [(0, "one"), (10, "two"), (600, "three")] {
+            let time = start + TimeDelta::seconds(offset);
+            snapshots.keep(&fs, path, source, time).unwrap();
+        }
+
+        assert_eq!(sources(&fs, &snapshots, path), ["three", "one"]);
+    }
+
+    #[test]
+    fn old_and_extra_snapshots_are_removed() {
+        let fs = MemFs::new();
+        let path = Path::new("/notes/a");
+        let snapshots = snapshots(Retention {
+            max_count: 2,
+            max_age: Duration::from_secs(60 * 60),
+            min_interval: Duration::ZERO,
+        });
+        let start = Utc::now();
+        for (offset, source) in [(0, "old"), (7200, "one"), (7201, "two"), (7202, "three")] {
+            let time = start + TimeDelta::seconds(offset);
+            snapshots.keep(&fs, path, source, time).unwrap();
+            if source == "one" {
+                assert_eq!(sources(&fs, &snapshots, path), ["one"]);
+            }
+        }
+
+        assert_eq!(sources(&fs, &snapshots, path), ["three", "two"]);
+    }
+
+    #[test]
+    fn snapshots_follow_rename() {
+        let fs = MemFs::new();
+        fs.create_dir_all(Path::new("/notes/dir")).unwrap();
+        let snapshots = snapshots(Default::default());
+        snapshots
+            .keep(&fs, Path::new("/notes/dir/a"), "one", Utc::now())
+            .unwrap();
+
+        snapshots
+            .rename(&fs, Path::new("/notes/dir"), Path::new("/notes/moved/dir"))
+            .unwrap();
+        assert_eq!(
+            sources(&fs, &snapshots, Path::new("/notes/moved/dir/a")),
+            ["one"]
+        );
+        snapshots
+            .rename(&fs, Path::new("/notes/b"), Path::new("/notes/c"))
+            .unwrap();
+    }
+}
@@ -0,0 +1,228 @@
+//! Notes open in editor tabs
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tab {
+    pub path: PathBuf,
+    /// Preview tab, not pinned, is replaced by the next note opened for preview
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+/// Open notes in tab order, each note at most once and at most one preview tab
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Tabs {
+    tabs: Vec<Tab>,
+}
+
+impl Tabs {
+    /// Duplicates and extra preview tabs are dropped
+    pub fn from_tabs(tabs: impl IntoIterator<Item = Tab>) -> Self {
+        let mut result = Self::default();
+        for tab in tabs {
+            if result.contains(&tab.path) || (!tab.pinned && result.preview().is_some()) {
+                continue;
+            }
+            result.tabs.push(tab);
+        }
+        result
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Tab> {
+        self.tabs.iter()
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.position(path).is_some()
+    }
+
+    pub fn position(&self, path: &Path) -> Option<usize> {
+        self.tabs.iter().position(|tab| tab.path == path)
+    }
+
+    pub fn preview(&self) -> Option<&Path> {
+        self.tabs
+            .iter()
+            .find(|tab| !tab.pinned)
+            .map(|tab| tab.path.as_path())
+    }
+
+    /// Open note in a new tab right after `after`, or in place of the preview tab when opened
+    /// for preview. Returns path of the replaced preview tab
+    pub fn open(&mut self, path: &Path, pinned: bool, after: Option<&Path>) -> Option<PathBuf> {
+        if let Some(tab) = self.tabs.iter_mut().find(|tab| tab.path == path) {
+            tab.pinned |= pinned;
+            return None;
+        }
+        let tab = Tab {
+            path: path.to_path_buf(),
+            pinned,
+        };
+        if !pinned && let Some(preview) = self.tabs.iter_mut().find(|tab| !tab.pinned) {
+            return Some(std::mem::replace(preview, tab).path);
+        }
+        let index = after
+            .and_then(|after| self.position(after))
+            .map_or(self.tabs.len(), |index| index + 1);
+        self.tabs.insert(index, tab);
+        None
+    }
+
+    pub fn pin(&mut self, path: &Path) {
+        if let Some(tab) = self.tabs.iter_mut().find(|tab| tab.path == path) {
+            tab.pinned = true;
+        }
+    }
+
+    /// Tab to show when the tab of `path` closes, the one to the right or else to the left
+    pub fn neighbor(&self, path: &Path) -> Option<&Path> {
+        let index = self.position(path)?;
+        self.tabs
+            .get(index + 1)
+            .or_else(|| index.checked_sub(1).and_then(|index| self.tabs.get(index)))
+            .map(|tab| tab.path.as_path())
+    }
+
+    /// Returns whether the note had a tab
+    pub fn close(&mut self, path: &Path) -> bool {
+        let Some(index) = self.position(path) else {
+            return false;
+        };
+        self.tabs.remove(index);
+        true
+    }
+
+    /// Move tab at `from` to index `to` of the resulting order
+    pub fn move_tab(&mut self, from: usize, to: usize) {
+        if from >= self.tabs.len() {
+            return;
+        }
+        let tab = self.tabs.remove(from);
+        self.tabs.insert(to.min(self.tabs.len()), tab);
+    }
+
+    /// Follow renamed note or directory
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        for tab in &mut self.tabs {
+            if let Ok(relative) = tab.path.strip_prefix(from) {
+                tab.path = to.join(relative);
+            }
+        }
+    }
+
+    /// Tabs of notes at or below the path
+    pub fn below(&self, path: &Path) -> Vec<PathBuf> {
+        self.tabs
+            .iter()
+            .filter(|tab| tab.path.starts_with(path))
+            .map(|tab| tab.path.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(tabs: &Tabs) -> Vec<&str> {
+        tabs.iter().map(|tab| tab.path.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn preview_tab_is_replaced() {
+        let mut tabs = Tabs::default();
+        assert_eq!(tabs.open(Path::new("a"), true, None), None);
+        assert_eq!(tabs.open(Path::new("b"), false, Some(Path::new("a"))), None);
+        assert_eq!(
+            tabs.open(Path::new("c"), false, Some(Path::new("b"))),
+            Some(PathBuf::from("b"))
+        );
+        assert_eq!(paths(&tabs), ["a", "c"]);
+        assert_eq!(tabs.preview(), Some(Path::new("c")));
+    }
+
+    #[test]
+    fn opening_open_note_pins_it() {
+        let mut tabs = Tabs::default();
+        tabs.open(Path::new("a"), false, None);
+        assert_eq!(tabs.open(Path::new("a"), true, None), None);
+        assert_eq!(tabs.preview(), None);
+
+        tabs.open(Path::new("b"), false, None);
+        tabs.open(Path::new("a"), false, None);
+        assert_eq!(paths(&tabs), ["a", "b"]);
+        assert_eq!(tabs.preview(), Some(Path::new("b")));
+    }
+
+    #[test]
+    fn new_tab_opens_after_given_one() {
+        let mut tabs = Tabs::default();
+        tabs.open(Path::new("a"), true, None);
+        tabs.open(Path::new("b"), true, Some(Path::new("a")));
+        tabs.open(Path::new("c"), true, Some(Path::new("a")));
+        assert_eq!(paths(&tabs), ["a", "c", "b"]);
+    }
+
+    #[test]
+    fn neighbor_prefers_right() {
+        let tabs = Tabs::from_tabs(["a", "b", "c"].map(|path| Tab {
+            path: PathBuf::from(path),
+            pinned: true,
+        }));
+        assert_eq!(tabs.neighbor(Path::new("b")), Some(Path::new("c")));
+        assert_eq!(tabs.neighbor(Path::new("c")), Some(Path::new("b")));
+        assert_eq!(tabs.neighbor(Path::new("d")), None);
+    }
+
+    #[test]
+    fn move_and_close() {
+        let mut tabs = Tabs::from_tabs(["a", "b", "c"].map(|path| Tab {
+            path: PathBuf::from(path),
+            pinned: true,
+        }));
+        tabs.move_tab(0, 2);
+        assert_eq!(paths(&tabs), ["b", "c", "a"]);
+        tabs.move_tab(2, 0);
+        assert_eq!(paths(&tabs), ["a", "b", "c"]);
+
+        assert!(tabs.close(Path::new("b")));
+        assert!(!tabs.close(Path::new("b")));
+        assert_eq!(paths(&tabs), ["a", "c"]);
+    }
+
+    #[test]
+    fn rename_follows_directory() {
+        let mut tabs = Tabs::default();
+        tabs.open(Path::new("/notes/dir/a.md"), true, None);
+        tabs.open(Path::new("/notes/b.md"), true, None);
+        tabs.rename(Path::new("/notes/dir"), Path::new("/notes/moved"));
+        assert_eq!(paths(&tabs), ["/notes/moved/a.md", "/notes/b.md"]);
+        assert_eq!(
+            tabs.below(Path::new("/notes/moved")),
+            [PathBuf::from("/notes/moved/a.md")]
+        );
+    }
+
+    #[test]
+    fn restored_tabs_are_deduplicated() {
+        let tabs = Tabs::from_tabs([
+            Tab {
+                path: PathBuf::from("a"),
+                pinned: false,
+            },
+            Tab {
+                path: PathBuf::from("a"),
+                pinned: true,
+            },
+            Tab {
+                path: PathBuf::from("b"),
+                pinned: false,
+            },
+        ]);
+        assert_eq!(paths(&tabs), ["a"]);
+        assert_eq!(tabs.preview(), Some(Path::new("a")));
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+use notes::tags;
+
+/// Tags of every note in the vault
+#[derive(Debug, Default)]
+pub struct TagIndex {
+    notes: HashMap<PathBuf, Vec<String>>,
+    /// Number of notes with the tag or tags nested in it
+    counts: BTreeMap<String, usize>,
+}
+
+/// Tag with the tags nested in it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagTree {
+    /// Last segment of the tag, `alpha` of `project/alpha`
+    pub name: String,
+    pub tag: String,
+    pub count: usize,
+    pub children: Vec<TagTree>,
+}
+
+/// Tag and its parents, `a/b/c` gives `a`, `a/b` and `a/b/c`
+fn with_parents(tag: &str) -> impl Iterator<Item = &str> {
+    tag.match_indices('/')
+        .map(|(index, _)| &tag[..index])
+        .chain(std::iter::once(tag))
+}
+
+/// Tags of a note with their parents, each once
+fn counted_tags(tags: &[String]) -> Vec<&str> {
+    let mut counted = tags
+        .iter()
+        .flat_map(|tag| with_parents(tag))
+        .collect::<Vec<&str>>();
+    counted.sort_unstable();
+    counted.dedup();
+    counted
+}
+
+impl TagIndex {
+    pub fn update(&mut self, path: &Path, tags: Vec<String>) {
+        self.remove_note(path);
+        for tag in counted_tags(&tags) {
+            *self.counts.entry(tag.to_owned()).or_default() += 1;
+        }
+        if !tags.is_empty() {
+            self.notes.insert(path.to_path_buf(), tags);
+        }
+    }
+
+    /// Remove notes at or below the path
+    pub fn remove(&mut self, path: &Path) {
+        for note_path in self.paths_below(path) {
+            self.remove_note(&note_path);
+        }
+    }
+
+    /// Follow renamed note or directory
+    pub fn rename(&mut self, from: &Path, to: &Path) {
+        for note_path in self.paths_below(from) {
+            if let (Some(tags), Ok(relative)) =
+                (self.notes.remove(&note_path), note_path.strip_prefix(from))
+            {
+                self.notes.insert(to.join(relative), tags);
+            }
+        }
+    }
+
+    fn paths_below(&self, path: &Path) -> Vec<PathBuf> {
+        self.notes
+            .keys()
+            .filter(|key| key.starts_with(path))
+            .cloned()
+            .collect()
+    }
+
+    fn remove_note(&mut self, path: &Path) {
+        let Some(tags) = self.notes.remove(path) else {
+            return;
+        };
+        for tag in counted_tags(&tags) {
+            if let Some(count) = self.counts.get_mut(tag) {
+                *count -= 1;
+                if *count == 0 {
+                    self.counts.remove(tag);
+                }
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// Top level tags with nested ones below them, by name
+    pub fn tree(&self) -> Vec<TagTree> {
+        let mut roots = Vec::<TagTree>::new();
+        // Parents come before nested tags in sorted order
+        for (tag, count) in &self.counts {
+            let mut level = &mut roots;
+            let mut segments = tag.split('/').peekable();
+            while let Some(segment) = segments.next() {
+                if segments.peek().is_none() {
+                    level.push(TagTree {
+                        name: segment.to_owned(),
+                        tag: tag.clone(),
+                        count: *count,
+                        children: Vec::new(),
+                    });
+                    break;
+                }
+                let Some(parent) = level.iter_mut().find(|node| node.name == segment) else {
+                    break;
+                };
+                level = &mut parent.children;
+            }
+        }
+        roots
+    }
+
+    /// Notes with every one of the tags or tags nested in them, by path
+    pub fn notes_with_all(&self, filter: &[String]) -> Vec<PathBuf> {
+        let mut paths = self
+            .notes
+            .iter()
+            .filter(|(_, note_tags)| {
+                filter.iter().all(|wanted| {
+                    note_tags
+                        .iter()
+                        .any(|note_tag| tags::is_within(note_tag, wanted))
+                })
+            })
+            .map(|(path, _)| path.clone())
+            .collect::<Vec<PathBuf>>();
+        paths.sort();
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn nested_tags_count_towards_parents() {
+        let mut index = TagIndex::default();
+        index.update(Path::new("/a"), tags(&["project/alpha", "project"]));
+        index.update(Path::new("/b"), tags(&["project/beta", "work"]));
+
+        let tree = index.tree();
+        assert_eq!(tree.len(), 2);
+        assert_eq!((tree[0].tag.as_str(), tree[0].count), ("project", 2));
+        let children = tree[0]
+            .children
+            .iter()
+            .map(|child| (child.name.as_str(), child.count))
+            .collect::<Vec<_>>();
+        assert_eq!(children, [("alpha", 1), ("beta", 1)]);
+
+        index.remove(Path::new("/b"));
+        let tree = index.tree();
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].count, 1);
+    }
+
+    #[test]
+    fn filter_by_all_tags() {
+        let mut index = TagIndex::default();
+        index.update(Path::new("/dir/a"), tags(&["project/alpha", "work"]));
+        index.update(Path::new("/dir/b"), tags(&["project/beta"]));
+        index.update(Path::new("/c"), Vec::new());
+
+        assert_eq!(
+            index.notes_with_all(&tags(&["project"])),
+            [Path::new("/dir/a"), Path::new("/dir/b")]
+        );
+        assert_eq!(
+            index.notes_with_all(&tags(&["project", "work"])),
+            [Path::new("/dir/a")]
+        );
+
+        index.rename(Path::new("/dir"), Path::new("/moved"));
+        assert_eq!(
+            index.notes_with_all(&tags(&["work"])),
+            [Path::new("/moved/a")]
+        );
+    }
+}
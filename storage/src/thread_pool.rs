@@ -40,21 +40,25 @@ impl ThreadPoolExecutor {
         }
     }
 
+    /// Tasks run right away on the calling thread, e.g. for deterministic tests
+    pub fn inline() -> Self {
+        Self {
+            request_tx: None,
+            workers: Vec::new(),
+        }
+    }
+
     fn exec_loop(request_rx: Arc<Mutex<Receiver<Executeable>>>) {
-        loop {
-            match request_rx.lock().unwrap().recv() {
-                Ok(executable) => executable(),
-                Err(_) => break,
-            }
+        while let Ok(executable) = request_rx.lock().unwrap().recv() {
+            executable()
         }
     }
 
     pub fn execute(&self, task: impl FnOnce() + Send + 'static) {
-        self.request_tx
-            .as_ref()
-            .unwrap()
-            .send(Box::new(task))
-            .unwrap();
+        match &self.request_tx {
+            Some(request_tx) => request_tx.send(Box::new(task)).unwrap(),
+            None => task(),
+        }
     }
 }
 
@@ -0,0 +1,204 @@
+//! Trash keeps trashed items in `files` and their [`DeletedMetadata`] sidecars in `info`
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::data::{DeletedMetadata, Trash, TrashEntry};
+use crate::fs::Fs;
+use crate::util::generate_unique_name;
+
+const TRASH_INFO_EXT: &str = "trashinfo";
+
+fn files_dir(trash_path: &Path) -> PathBuf {
+    trash_path.join("files")
+}
+
+fn info_dir(trash_path: &Path) -> PathBuf {
+    trash_path.join("info")
+}
+
+fn info_path(trash_path: &Path, name: &str) -> PathBuf {
+    info_dir(trash_path).join(format!("{name}.{TRASH_INFO_EXT}"))
+}
+
+pub fn load_trash(fs: &dyn Fs, trash_path: &Path) -> io::Result<Trash> {
+    let info_dir = info_dir(trash_path);
+    if !fs.try_exists(&info_dir)? {
+        return Ok(Trash::default());
+    }
+
+    let mut entries = fs
+        .read_dir(&info_dir)?
+        .into_iter()
+        .filter_map(|entry| {
+            let info_path = entry.path;
+            if info_path.extension()? != TRASH_INFO_EXT {
+                return None;
+            }
+            let name = info_path.file_stem()?.to_str()?.to_owned();
+            let path = files_dir(trash_path).join(&name);
+            let deleted = fs
+                .read_to_string(&info_path)
+                .and_then(|info| DeletedMetadata::from_trash_info(&info))
+                .inspect_err(|err| log::warn!("Skip trash entry {name}: {err}"))
+                .ok()?;
+            Some(TrashEntry {
+                is_dir: fs.is_dir(&path),
+                name,
+                path,
+                deleted,
+            })
+        })
+        .filter(|entry| fs.exists(&entry.path))
+        .collect::<Vec<TrashEntry>>();
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted.deletion_time));
+
+    Ok(Trash { entries })
+}
+
+/// Returns unique name of the item in trash
+pub fn move_to_trash(fs: &dyn Fs, path: &Path, trash_path: &Path) -> io::Result<String> {
+    let files_dir = files_dir(trash_path);
+    fs.create_dir_all(&files_dir)?;
+    fs.create_dir_all(&info_dir(trash_path))?;
+
+    let existing_names = fs.names_in(&files_dir)?;
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?
+        .to_string_lossy()
+        .into_owned();
+    let name = generate_unique_name(existing_names.iter().map(String::as_str), file_name);
+
+    let info_path = info_path(trash_path, &name);
+    let deleted = DeletedMetadata::deleted_now(path.to_path_buf());
+    fs.write(&info_path, deleted.to_trash_info().as_bytes())?;
+    if let Err(err) = fs.rename(path, &files_dir.join(&name)) {
+        let _ = fs.remove_file(&info_path);
+        return Err(err);
+    }
+
+    Ok(name)
+}
+
+/// Put item back where it was, under a unique name when the place is taken. Returns its path
+pub fn restore_from_trash(fs: &dyn Fs, name: &str, trash_path: &Path) -> io::Result<PathBuf> {
+    let info_path = info_path(trash_path, name);
+    let deleted = DeletedMetadata::from_trash_info(&fs.read_to_string(&info_path)?)?;
+
+    let origin_dir = deleted
+        .origin_path
+        .parent()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no origin directory"))?;
+    fs.create_dir_all(origin_dir)?;
+
+    let path = if fs.try_exists(&deleted.origin_path)? {
+        let existing_names = fs.names_in(origin_dir)?;
+        let origin_name = deleted
+            .origin_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| name.to_owned());
+        origin_dir.join(generate_unique_name(
+            existing_names.iter().map(String::as_str),
+            origin_name,
+        ))
+    } else {
+        deleted.origin_path
+    };
+
+    fs.rename(&files_dir(trash_path).join(name), &path)?;
+    fs.remove_file(&info_path)?;
+
+    Ok(path)
+}
+
+pub fn purge_from_trash(fs: &dyn Fs, name: &str, trash_path: &Path) -> io::Result<()> {
+    let path = files_dir(trash_path).join(name);
+    if fs.is_dir(&path) {
+        fs.remove_dir_all(&path)?;
+    } else if fs.try_exists(&path)? {
+        fs.remove_file(&path)?;
+    }
+    fs.remove_file(&info_path(trash_path, name))?;
+    Ok(())
+}
+
+pub fn empty_trash(fs: &dyn Fs, trash_path: &Path) -> io::Result<()> {
+    for dir in [files_dir(trash_path), info_dir(trash_path)] {
+        if fs.try_exists(&dir)? {
+            fs.remove_dir_all(&dir)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::fs::{MemFs, RealFs};
+
+    #[test]
+    fn restore_takes_unique_name_when_origin_is_taken() {
+        let dir = tempfile::tempdir().unwrap();
+        let trash_path = dir.path().join(".trash");
+        let path = dir.path().join("note");
+        fs::write(&path, "old").unwrap();
+
+        let name = move_to_trash(&RealFs, &path, &trash_path).unwrap();
+        assert!(!path.exists());
+        let trash = load_trash(&RealFs, &trash_path).unwrap();
+        assert_eq!(trash.entries.len(), 1);
+        assert_eq!(trash.entries[0].deleted.origin_path, path);
+
+        fs::write(&path, "new").unwrap();
+        let restored = restore_from_trash(&RealFs, &name, &trash_path).unwrap();
+        assert_eq!(restored, dir.path().join("note #2"));
+        assert_eq!(fs::read_to_string(restored).unwrap(), "old");
+        assert!(load_trash(&RealFs, &trash_path).unwrap().entries.is_empty());
+    }
+
+    #[test]
+    fn origin_names_with_spaces_and_line_breaks_are_restored() {
+        let fs = MemFs::new();
+        let trash_path = Path::new("/notes/.trash");
+        fs.create_dir_all(Path::new("/notes/dir")).unwrap();
+        for name in [" spaced ", "line\nbreak", "100% done", "мысли"] {
+            let path = Path::new("/notes/dir").join(name);
+            fs.write(&path, name.as_bytes()).unwrap();
+
+            let trashed = move_to_trash(&fs, &path, trash_path).unwrap();
+            let info = fs.read_to_string(&info_path(trash_path, &trashed)).unwrap();
+            assert_eq!(info.lines().count(), 3, "{info}");
+            let trash = load_trash(&fs, trash_path).unwrap();
+            assert_eq!(trash.entries[0].deleted.origin_path, path);
+
+            assert_eq!(restore_from_trash(&fs, &trashed, trash_path).unwrap(), path);
+            assert_eq!(fs.read_to_string(&path).unwrap(), name);
+        }
+    }
+
+    #[test]
+    fn purge_and_empty() {
+        let fs = MemFs::new();
+        let trash_path = Path::new("/notes/.trash");
+        fs.create_dir_all(Path::new("/notes/dir")).unwrap();
+        fs.write(Path::new("/notes/dir/a"), b"").unwrap();
+        fs.write(Path::new("/notes/b"), b"").unwrap();
+
+        let dir_name = move_to_trash(&fs, Path::new("/notes/dir"), trash_path).unwrap();
+        move_to_trash(&fs, Path::new("/notes/b"), trash_path).unwrap();
+        let trash = load_trash(&fs, trash_path).unwrap();
+        assert_eq!(trash.entries.len(), 2);
+        assert!(trash.entries.iter().any(|entry| entry.is_dir));
+
+        purge_from_trash(&fs, &dir_name, trash_path).unwrap();
+        assert!(!fs.exists(&files_dir(trash_path).join(&dir_name)));
+        assert_eq!(load_trash(&fs, trash_path).unwrap().entries.len(), 1);
+
+        empty_trash(&fs, trash_path).unwrap();
+        assert!(load_trash(&fs, trash_path).unwrap().entries.is_empty());
+    }
+}
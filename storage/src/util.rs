@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+use chrono::{DateTime, Utc};
+use regex::Regex;
+
+/// Times before the epoch are kept, unlike with `duration_since(UNIX_EPOCH)`
+pub fn to_date_time_utc(sys_time: SystemTime) -> DateTime<Utc> {
+    DateTime::from(sys_time)
+}
+
+/// Tells apart temporary files of writes running at the same time in this process
+static TEMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Write file through a hidden temporary file next to it, so after a crash the file has either
+/// old or new contents. Each write has its own temporary file, so concurrent writes to one path
+/// never mix their contents
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a file path", path.display()),
+        ));
+    };
+    let temp_path = dir.join(format!(
+        ".{}.{}.{}.tmp",
+        name.to_string_lossy(),
+        std::process::id(),
+        TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    // Leftover of another write is not ours to truncate or remove
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)?;
+    let mut write = || -> io::Result<()> {
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        // Persist the rename itself
+        #[cfg(unix)]
+        File::open(dir)?.sync_all()?;
+        Ok(())
+    };
+    write().inspect_err(|_| {
+        let _ = fs::remove_file(&temp_path);
+    })
+}
+
+pub fn generate_unique_name<'x>(
+    existing_names: impl IntoIterator<Item = &'x str>,
+    candidate_name: String,
+) -> String {
+    let start_count = 1;
+    let counts = existing_names
+        .into_iter()
+        .filter_map(|existing_name| {
+            Regex::new(&format!(
+                r"^{}( #(?<count>\d+))*$",
+                regex::escape(&candidate_name)
+            ))
+            .unwrap()
+            .captures(existing_name)
+        })
+        .map(|caps| {
+            caps.name("count")
+                .and_then(|c| c.as_str().parse::<i32>().ok())
+                .unwrap_or(start_count)
+        })
+        .map(|count| (count, true))
+        .collect::<HashMap<i32, bool>>();
+    let max_count = counts.keys().max().cloned().unwrap_or(-1);
+    for i in start_count..=max_count + 1 {
+        if !counts.contains_key(&i) {
+            return if i == 0 {
+                candidate_name
+            } else {
+                format!("{candidate_name} #{i}")
+            };
+        }
+    }
+
+    candidate_name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atomic_write_replaces_file_without_leftovers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note");
+        fs::write(&path, "old").unwrap();
+
+        write_atomically(&path, b"new").unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        let names = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|dirent| dirent.unwrap().file_name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["note"]);
+    }
+
+    #[test]
+    fn concurrent_atomic_writes_never_mix() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note");
+        let contents = ["a", "b", "c", "d"].map(|letter| letter.repeat(64 * 1024));
+
+        std::thread::scope(|scope| {
+            for text in &contents {
+                let path = &path;
+                scope.spawn(move || {
+                    for _ in 0..20 {
+                        write_atomically(path, text.as_bytes()).unwrap();
+                    }
+                });
+            }
+        });
+
+        assert!(contents.contains(&fs::read_to_string(&path).unwrap()));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn atomic_write_fails_in_missing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing").join("note");
+        assert!(write_atomically(&path, b"text").is_err());
+    }
+
+    #[test]
+    fn unique_name() {
+        assert_eq!(generate_unique_name(["a"], "b".to_owned()), "b");
+        assert_eq!(generate_unique_name(["a"], "a".to_owned()), "a #2");
+        assert_eq!(generate_unique_name(["a", "a #2"], "a".to_owned()), "a #3");
+    }
+}